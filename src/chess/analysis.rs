@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+
+use super::game::{Game, GameResult, GameState};
+use super::moves::{HistoryMove, MoveFailureReason, NewMove};
+
+use AnalysisError::*;
+
+pub type NodeId = usize;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Error)]
+pub enum AnalysisError {
+    #[error("There is no such position")]
+    NoSuchNode,
+    #[error("There is no next move")]
+    NoNextMove,
+    #[error("There is no previous move")]
+    NoPreviousMove,
+    #[error("There is no move {0} in this line")]
+    NoSuchPly(u32),
+    #[error("This line is already the mainline")]
+    AlreadyMainline,
+    #[error("The starting position cannot be deleted")]
+    CannotDeleteRoot,
+    #[error("Invalid move: {0}")]
+    InvalidMove(MoveFailureReason),
+}
+
+pub struct AnalysisNode {
+    pub id: NodeId,
+    pub parent: Option<NodeId>,
+    pub children: Vec<NodeId>,
+    pub last_move: Option<HistoryMove>,
+    pub state: GameState,
+    pub result: Option<GameResult>,
+    pub ply: u32,
+    pub comment: String,
+    pub nags: Vec<u8>,
+}
+
+impl AnalysisNode {
+    fn new(id: NodeId, parent: Option<NodeId>, state: GameState, result: Option<GameResult>, ply: u32) -> Self {
+        Self {
+            id,
+            parent,
+            children: Vec::new(),
+            last_move: state.board.last_move,
+            state,
+            result,
+            ply,
+            comment: String::new(),
+            nags: Vec::new(),
+        }
+    }
+}
+
+/// A tree of positions that can be freely explored without touching a real game.
+/// The first child of every node continues its line, the other children are side variations.
+pub struct AnalysisBoard {
+    nodes: HashMap<NodeId, AnalysisNode>,
+    root: NodeId,
+    current: NodeId,
+    next_id: NodeId,
+}
+
+impl AnalysisBoard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_game(game: &Game) -> Self {
+        let mut states = game.state_history.iter().chain(std::iter::once(&game.state));
+        let mut analysis = Self::from_state(states.next().unwrap().clone());
+
+        for state in states {
+            analysis.push_node(state.clone(), None);
+        }
+
+        analysis.get_current_node_mut().result = game.result;
+        analysis.current = analysis.root;
+        analysis
+    }
+
+    fn from_state(state: GameState) -> Self {
+        let mut nodes = HashMap::new();
+        nodes.insert(0, AnalysisNode::new(0, None, state, None, 0));
+
+        Self {
+            nodes,
+            root: 0,
+            current: 0,
+            next_id: 1,
+        }
+    }
+
+    fn push_node(&mut self, state: GameState, result: Option<GameResult>) -> NodeId {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let parent = self.current;
+        let ply = self.get_current_node().ply + 1;
+
+        self.nodes.insert(id, AnalysisNode::new(id, Some(parent), state, result, ply));
+        self.nodes.get_mut(&parent).unwrap().children.push(id);
+        self.current = id;
+
+        id
+    }
+
+    pub fn get_node(&self, id: NodeId) -> Option<&AnalysisNode> {
+        self.nodes.get(&id)
+    }
+
+    pub fn get_nodes(&self) -> impl Iterator<Item = &AnalysisNode> {
+        self.nodes.values()
+    }
+
+    pub fn get_root(&self) -> NodeId {
+        self.root
+    }
+
+    pub fn get_current(&self) -> NodeId {
+        self.current
+    }
+
+    pub fn get_current_node(&self) -> &AnalysisNode {
+        &self.nodes[&self.current]
+    }
+
+    fn get_current_node_mut(&mut self) -> &mut AnalysisNode {
+        self.nodes.get_mut(&self.current).unwrap()
+    }
+
+    /// Ids of the positions from the root up to and including the given node.
+    pub fn get_path_to(&self, id: NodeId) -> Vec<NodeId> {
+        let mut path = Vec::new();
+        let mut next = Some(id);
+
+        while let Some(id) = next {
+            path.push(id);
+            next = self.nodes[&id].parent;
+        }
+
+        path.reverse();
+        path
+    }
+
+    /// Ids of the positions that follow the given node by always taking the first child.
+    pub fn get_line_from(&self, id: NodeId) -> Vec<NodeId> {
+        let mut line = vec![id];
+        let mut node = &self.nodes[&id];
+
+        while let Some(&child) = node.children.first() {
+            line.push(child);
+            node = &self.nodes[&child];
+        }
+
+        line
+    }
+
+    pub fn get_mainline(&self) -> Vec<NodeId> {
+        self.get_line_from(self.root)
+    }
+
+    pub fn make_move(&mut self, m: NewMove) -> Result<NodeId, AnalysisError> {
+        let node = self.get_current_node();
+
+        if let Some(&existing) = node.children.iter().find(|child| self.nodes[child].last_move.is_some_and(|last_move| last_move.is_same_move(&m))) {
+            self.current = existing;
+            return Ok(existing);
+        }
+
+        if node.result.is_some() {
+            return Err(InvalidMove(MoveFailureReason::GameEnded));
+        }
+
+        let mut path = self.get_path_to(self.current);
+        path.pop();
+
+        let history = path
+            .iter()
+            .map(|id| {
+                let mut state = self.nodes[id].state.clone();
+                state.board_hash = state.board.state.get_hash();
+                state
+            })
+            .collect();
+
+        let mut game = Game::from_position(node.state.clone(), history);
+        game.make_move(m).map_err(InvalidMove)?;
+
+        Ok(self.push_node(game.state, game.result))
+    }
+
+    pub fn go_to(&mut self, id: NodeId) -> Result<(), AnalysisError> {
+        if !self.nodes.contains_key(&id) {
+            return Err(NoSuchNode);
+        }
+
+        self.current = id;
+        Ok(())
+    }
+
+    pub fn go_to_next(&mut self) -> Result<(), AnalysisError> {
+        let next = *self.get_current_node().children.first().ok_or(NoNextMove)?;
+        self.go_to(next)
+    }
+
+    pub fn go_to_previous(&mut self) -> Result<(), AnalysisError> {
+        let previous = self.get_current_node().parent.ok_or(NoPreviousMove)?;
+        self.go_to(previous)
+    }
+
+    pub fn go_to_start(&mut self) {
+        self.current = self.root;
+    }
+
+    pub fn go_to_end(&mut self) {
+        self.current = *self.get_line_from(self.current).last().unwrap();
+    }
+
+    /// Jumps to the given ply of the line the current position is in.
+    pub fn jump_to_ply(&mut self, ply: u32) -> Result<(), AnalysisError> {
+        let current_ply = self.get_current_node().ply;
+
+        let target = if ply <= current_ply {
+            self.get_path_to(self.current).get(ply as usize).copied()
+        } else {
+            self.get_line_from(self.current).get((ply - current_ply) as usize).copied()
+        };
+
+        self.go_to(target.ok_or(NoSuchPly(ply))?)
+    }
+
+    /// Moves the variation containing the current position one level up, making it the main continuation at its branching point.
+    pub fn promote_variation(&mut self) -> Result<(), AnalysisError> {
+        for id in self.get_path_to(self.current).into_iter().rev() {
+            let parent = match self.nodes[&id].parent {
+                Some(parent) => parent,
+                None => break,
+            };
+
+            let siblings = &mut self.nodes.get_mut(&parent).unwrap().children;
+            let index = siblings.iter().position(|&sibling| sibling == id).unwrap();
+
+            if index != 0 {
+                siblings.remove(index);
+                siblings.insert(0, id);
+                return Ok(());
+            }
+        }
+
+        Err(AlreadyMainline)
+    }
+
+    /// Removes the current position together with everything that follows it.
+    pub fn delete_variation(&mut self) -> Result<(), AnalysisError> {
+        let parent = self.get_current_node().parent.ok_or(CannotDeleteRoot)?;
+        let removed = self.current;
+
+        self.nodes.get_mut(&parent).unwrap().children.retain(|&child| child != removed);
+
+        let mut to_remove = vec![removed];
+
+        while let Some(id) = to_remove.pop() {
+            if let Some(node) = self.nodes.remove(&id) {
+                to_remove.extend(node.children);
+            }
+        }
+
+        self.current = parent;
+        Ok(())
+    }
+
+    pub fn set_comment(&mut self, comment: String) {
+        self.get_current_node_mut().comment = comment;
+    }
+
+    pub fn add_nag(&mut self, nag: u8) {
+        let nags = &mut self.get_current_node_mut().nags;
+
+        if !nags.contains(&nag) {
+            nags.push(nag);
+        }
+    }
+
+    pub fn remove_nag(&mut self, nag: u8) {
+        self.get_current_node_mut().nags.retain(|&other| other != nag);
+    }
+}
+
+impl Default for AnalysisBoard {
+    fn default() -> Self {
+        Self::from_state(Game::new().state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn play(analysis: &mut AnalysisBoard, m: &str) -> NodeId {
+        analysis.make_move(NewMove::from_str(m).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn other_moves_start_variations() {
+        let mut analysis = AnalysisBoard::new();
+        let e4 = play(&mut analysis, "E2E4");
+        let e5 = play(&mut analysis, "E7E5");
+
+        analysis.go_to_previous().unwrap();
+        assert_eq!(play(&mut analysis, "E7E5"), e5, "playing the same move again follows the existing line");
+
+        analysis.go_to_previous().unwrap();
+        let c5 = play(&mut analysis, "C7C5");
+
+        assert_eq!(analysis.get_node(e4).unwrap().children, vec![e5, c5]);
+        assert_eq!(analysis.get_mainline(), vec![analysis.get_root(), e4, e5]);
+        assert_eq!(analysis.get_current_node().ply, 2);
+
+        analysis.jump_to_ply(1).unwrap();
+        assert_eq!(analysis.get_current(), e4);
+        assert_eq!(analysis.jump_to_ply(3), Err(NoSuchPly(3)));
+    }
+
+    #[test]
+    fn promoted_variations_become_the_mainline() {
+        let mut analysis = AnalysisBoard::new();
+        let e4 = play(&mut analysis, "E2E4");
+        let e5 = play(&mut analysis, "E7E5");
+        analysis.go_to_previous().unwrap();
+        let c5 = play(&mut analysis, "C7C5");
+        let nf3 = play(&mut analysis, "G1F3");
+
+        analysis.promote_variation().unwrap();
+
+        assert_eq!(analysis.get_node(e4).unwrap().children, vec![c5, e5]);
+        assert_eq!(analysis.get_mainline(), vec![analysis.get_root(), e4, c5, nf3]);
+        assert_eq!(analysis.promote_variation(), Err(AlreadyMainline));
+    }
+
+    #[test]
+    fn deleting_a_variation_removes_what_follows() {
+        let mut analysis = AnalysisBoard::new();
+        let e4 = play(&mut analysis, "E2E4");
+        let e5 = play(&mut analysis, "E7E5");
+        analysis.go_to_previous().unwrap();
+        let c5 = play(&mut analysis, "C7C5");
+        let nf3 = play(&mut analysis, "G1F3");
+
+        analysis.go_to(c5).unwrap();
+        analysis.delete_variation().unwrap();
+
+        assert_eq!(analysis.get_current(), e4);
+        assert_eq!(analysis.get_node(e4).unwrap().children, vec![e5]);
+        assert!(analysis.get_node(c5).is_none() && analysis.get_node(nf3).is_none());
+
+        analysis.go_to_start();
+        assert_eq!(analysis.delete_variation(), Err(CannotDeleteRoot));
+    }
+
+    #[test]
+    fn positions_keep_comments_and_nags() {
+        let mut analysis = AnalysisBoard::new();
+        let e4 = play(&mut analysis, "E2E4");
+
+        analysis.set_comment(String::from("Best by test"));
+        analysis.add_nag(1);
+        analysis.add_nag(1);
+        analysis.add_nag(14);
+        analysis.remove_nag(1);
+        play(&mut analysis, "E7E5");
+
+        let node = analysis.get_node(e4).unwrap();
+        assert_eq!(node.comment, "Best by test");
+        assert_eq!(node.nags, vec![14]);
+        assert!(analysis.get_current_node().comment.is_empty());
+    }
+
+    #[test]
+    fn games_load_as_the_mainline() {
+        let mut game = Game::new();
+
+        for m in ["F2F3", "E7E5", "G2G4", "D8H4"].iter() {
+            game.make_move(NewMove::from_str(m).unwrap()).unwrap();
+        }
+
+        let mut analysis = AnalysisBoard::from_game(&game);
+
+        assert_eq!(analysis.get_current(), analysis.get_root());
+        assert_eq!(analysis.get_mainline().len(), 5);
+
+        analysis.go_to_end();
+        assert!(analysis.get_current_node().result.is_some());
+        assert_eq!(analysis.make_move(NewMove::from_str("A2A3").unwrap()), Err(InvalidMove(MoveFailureReason::GameEnded)));
+    }
+}
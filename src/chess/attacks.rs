@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+
+use super::board::{Board, Color, Square};
+use super::pieces::{Piece, Type};
+
+#[derive(Clone, Default)]
+pub struct SquareControl {
+    pub occupant: Option<Color>,
+    pub white_attackers: Vec<Square>,
+    pub black_attackers: Vec<Square>,
+}
+
+impl SquareControl {
+    pub fn get_attackers(&self, color: Color) -> &[Square] {
+        match color {
+            Color::White => &self.white_attackers,
+            Color::Black => &self.black_attackers,
+        }
+    }
+
+    fn get_attackers_mut(&mut self, color: Color) -> &mut Vec<Square> {
+        match color {
+            Color::White => &mut self.white_attackers,
+            Color::Black => &mut self.black_attackers,
+        }
+    }
+
+    /// Positive when white controls the square with more pieces, negative when black does.
+    pub fn get_balance(&self) -> i32 {
+        self.white_attackers.len() as i32 - self.black_attackers.len() as i32
+    }
+}
+
+#[derive(Clone)]
+pub struct Pin {
+    pub pinned: Square,
+    pub pinner: Square,
+    pub king: Square,
+    /// Squares the pinned piece may still move to: everything between the pinner and the king, the pinner included.
+    pub ray: Vec<Square>,
+}
+
+#[derive(Clone)]
+pub struct XRay {
+    pub attacker: Square,
+    pub blocker: Square,
+    pub target: Square,
+}
+
+/// Which pieces attack which squares on a board, along with the pins and x-rays the sliding pieces create.
+pub struct AttackMap {
+    squares: HashMap<Square, SquareControl>,
+    kings: HashMap<Color, Square>,
+    pins: Vec<Pin>,
+    x_rays: Vec<XRay>,
+}
+
+impl AttackMap {
+    pub fn new(board: &Board) -> Self {
+        let mut map = Self {
+            squares: HashMap::with_capacity(64),
+            kings: HashMap::with_capacity(2),
+            pins: Vec::new(),
+            x_rays: Vec::new(),
+        };
+
+        for file in 1..9 {
+            for rank in 1..9 {
+                let square = Square::new(file, rank);
+
+                map.squares.insert(
+                    square,
+                    SquareControl {
+                        occupant: board.get_piece(square).map(|piece| piece.color),
+                        ..SquareControl::default()
+                    },
+                );
+            }
+        }
+
+        for piece in board.state.pieces.values() {
+            if piece.piece_type == Type::King {
+                map.kings.insert(piece.color, piece.location);
+            }
+
+            for ray in piece.get_attack_rays() {
+                map.walk_ray(board, piece, &ray);
+            }
+        }
+
+        map
+    }
+
+    fn walk_ray(&mut self, board: &Board, piece: &Piece, ray: &[Square]) {
+        let mut squares = ray.iter();
+        let mut blocker = None;
+
+        // Direct attacks reach up to and including the first piece in the way
+        for &square in squares.by_ref() {
+            self.squares.get_mut(&square).unwrap().get_attackers_mut(piece.color).push(piece.location);
+
+            if let Some(other) = board.get_piece(square) {
+                blocker = Some(other);
+                break;
+            }
+        }
+
+        let blocker = match blocker {
+            Some(blocker) => blocker,
+            None => return,
+        };
+
+        // Look through the blocker for the next piece on the same line
+        let target = match squares.find_map(|&square| board.get_piece(square)) {
+            Some(target) => target,
+            None => return,
+        };
+
+        self.x_rays.push(XRay {
+            attacker: piece.location,
+            blocker: blocker.location,
+            target: target.location,
+        });
+
+        if target.piece_type == Type::King && target.color != piece.color && blocker.color == target.color {
+            let mut pin_ray: Vec<Square> = ray
+                .iter()
+                .take_while(|&&square| square != target.location)
+                .filter(|&&square| square != blocker.location)
+                .copied()
+                .collect();
+            pin_ray.push(piece.location);
+
+            self.pins.push(Pin {
+                pinned: blocker.location,
+                pinner: piece.location,
+                king: target.location,
+                ray: pin_ray,
+            });
+        }
+    }
+
+    pub fn get_control(&self, square: Square) -> &SquareControl {
+        &self.squares[&square]
+    }
+
+    pub fn get_attackers(&self, square: Square, color: Color) -> &[Square] {
+        self.get_control(square).get_attackers(color)
+    }
+
+    /// Pieces protecting the piece standing on the square, empty if the square is empty.
+    pub fn get_defenders(&self, square: Square) -> &[Square] {
+        let control = self.get_control(square);
+
+        match control.occupant {
+            Some(color) => control.get_attackers(color),
+            None => &[],
+        }
+    }
+
+    pub fn is_attacked(&self, square: Square, color: Color) -> bool {
+        !self.get_attackers(square, color).is_empty()
+    }
+
+    /// Pieces giving check to the king of the given color.
+    pub fn get_checkers(&self, color: Color) -> &[Square] {
+        match self.kings.get(&color) {
+            Some(&king) => self.get_attackers(king, color.get_opposite()),
+            None => &[],
+        }
+    }
+
+    pub fn get_pins(&self) -> &[Pin] {
+        &self.pins
+    }
+
+    pub fn get_pin(&self, square: Square) -> Option<&Pin> {
+        self.pins.iter().find(|pin| pin.pinned == square)
+    }
+
+    pub fn get_x_rays(&self) -> &[XRay] {
+        &self.x_rays
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn square(name: &str) -> Square {
+        Square::from_str(name).unwrap()
+    }
+
+    #[test]
+    fn pin_ray_excludes_the_pinned_piece() {
+        let mut board = Board::new();
+        board.set_piece(Piece::new(square("E1"), Color::White, Type::King));
+        board.set_piece(Piece::new(square("E3"), Color::White, Type::Knight));
+        board.set_piece(Piece::new(square("E6"), Color::Black, Type::Rook));
+
+        let map = board.get_attack_map();
+        let pin = map.get_pin(square("E3")).unwrap();
+
+        assert_eq!(pin.pinner, square("E6"));
+        assert_eq!(pin.king, square("E1"));
+
+        let mut ray = pin.ray.clone();
+        ray.sort_by_key(Square::get_unique_index);
+        let mut expected = vec![square("E2"), square("E4"), square("E5"), square("E6")];
+        expected.sort_by_key(Square::get_unique_index);
+
+        assert_eq!(ray, expected);
+    }
+}
@@ -2,8 +2,9 @@ use std::collections::{hash_map::DefaultHasher, HashMap};
 use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+use super::attacks::AttackMap;
 use super::moves::{Extra, HistoryMove, MoveFailureReason, MoveFailureReason::*, MoveParsingError};
 use super::pieces::{Piece, Type};
 
@@ -25,7 +26,7 @@ impl Color {
     }
 }
 
-#[derive(Eq, PartialEq, Copy, Clone, Hash)]
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Debug)]
 pub struct Square {
     pub file_number: u8,
     pub rank_number: u8,
@@ -125,7 +126,7 @@ impl FromStr for Square {
         let file_character = chars[0];
         let rank_character = chars[1];
 
-        if file_character < b'A' || file_character > b'H' || rank_character < b'1' || rank_character > b'8' {
+        if !(b'A'..=b'H').contains(&file_character) || !(b'1'..=b'8').contains(&rank_character) {
             return Err(MoveParsingError::InvalidSquare);
         }
 
@@ -145,20 +146,55 @@ impl Display for Square {
     }
 }
 
-#[derive(Eq, PartialEq, Copy, Clone)]
+impl Serialize for Square {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Square {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        Square::from_str(&string).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub struct CastlingRights {
     pub short_castle: bool,
     pub long_castle: bool,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct BoardState {
     pub white_castling_rights: CastlingRights,
     pub black_castling_rights: CastlingRights,
     pub en_passant_square: Option<Square>,
+    #[serde(with = "pieces_as_list")]
     pub pieces: HashMap<Square, Piece>,
 }
 
+/// Pieces are stored as a list ordered by square, as the square is already a part of every piece.
+mod pieces_as_list {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Square;
+    use crate::chess::pieces::Piece;
+
+    pub fn serialize<S: Serializer>(pieces: &HashMap<Square, Piece>, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut list: Vec<&Piece> = pieces.values().collect();
+        list.sort_by_key(|piece| piece.location.get_unique_index());
+        list.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<Square, Piece>, D::Error> {
+        let list = Vec::<Piece>::deserialize(deserializer)?;
+        Ok(list.into_iter().map(|piece| (piece.location, piece)).collect())
+    }
+}
+
 impl BoardState {
     pub fn get_castling_rights_mut_for(&mut self, color: Color) -> &mut CastlingRights {
         match color {
@@ -253,7 +289,7 @@ impl PartialEq for BoardState {
     }
 }
 
-#[derive(PartialEq, Clone)]
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
 pub struct Board {
     pub highlighted_squares: Vec<Square>,
     pub state: BoardState,
@@ -325,32 +361,49 @@ impl Board {
     }
 
     pub fn is_path_clear(&self, path: Vec<Square>) -> bool {
-        path.iter().all(|&square| self.get_piece(square).is_none())
+        self.find_blocking_square(&path).is_none()
     }
 
-    pub fn is_attacked(&self, square: Square, color: Option<Color>) -> bool {
-        for (_, piece) in self.state.pieces.iter() {
-            if let Some(required_color) = color {
-                if required_color != piece.color {
-                    continue;
+    pub fn find_blocking_square(&self, path: &[Square]) -> Option<Square> {
+        path.iter().find(|&&square| self.get_piece(square).is_some()).copied()
+    }
+
+    pub fn check_path_clear(&self, path: &[Square]) -> Result<(), MoveFailureReason> {
+        match self.find_blocking_square(path) {
+            Some(square) => Err(PathBlocked(square)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn get_attack_map(&self) -> AttackMap {
+        AttackMap::new(self)
+    }
+
+    pub fn does_piece_attack(&self, piece: &Piece, square: Square) -> bool {
+        piece.get_attack_rays().iter().any(|ray| {
+            for &ray_square in ray {
+                if ray_square == square {
+                    return true;
                 }
-            }
 
-            for valid_move in &piece.valid_moves {
-                if *valid_move != square {
-                    continue;
+                if self.get_piece(ray_square).is_some() {
+                    return false;
                 }
+            }
 
-                let move_check = NewMove {
-                    from: piece.location,
-                    to: *valid_move,
-                    extra: Extra::MoveCheck,
-                };
+            false
+        })
+    }
 
-                if !piece.is_move_valid(self, move_check) {
+    pub fn is_attacked(&self, square: Square, color: Option<Color>) -> bool {
+        for (_, piece) in self.state.pieces.iter() {
+            if let Some(required_color) = color {
+                if required_color != piece.color {
                     continue;
                 }
+            }
 
+            if self.does_piece_attack(piece, square) {
                 return true;
             }
         }
@@ -358,6 +411,14 @@ impl Board {
         false
     }
 
+    pub fn get_king_location(&self, color: Color) -> Option<Square> {
+        self.state
+            .pieces
+            .values()
+            .find(|piece| piece.piece_type == Type::King && piece.color == color)
+            .map(|piece| piece.location)
+    }
+
     pub fn is_in_check(&self, color: Color) -> bool {
         for (_, piece) in self.state.pieces.iter() {
             if piece.piece_type != Type::King || piece.color != color {
@@ -437,21 +498,16 @@ impl Board {
     }
 
     pub fn make_move_if_valid(&mut self, m: NewMove) -> Result<(), MoveFailureReason> {
-        let piece_color: Color;
-        let piece_type: Type;
-
         let piece = match self.get_piece(m.from) {
             Some(piece) => piece,
             None => return Err(NoPiece),
         };
 
         // Check if move was valid
-        if !piece.is_move_valid(self, m) {
-            return Err(MoveInvalid);
-        }
+        piece.validate_move(self, m)?;
 
-        piece_color = piece.color;
-        piece_type = piece.piece_type;
+        let piece_color = piece.color;
+        let piece_type = piece.piece_type;
 
         let mut was_capture = false;
 
@@ -518,3 +574,46 @@ impl Default for Board {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(name: &str) -> Square {
+        Square::from_str(name).unwrap()
+    }
+
+    #[test]
+    fn sliding_attacks_stop_at_the_first_piece() {
+        let mut board = Board::new();
+        board.set_piece(Piece::new(square("A1"), Color::White, Type::Rook));
+        board.set_piece(Piece::new(square("A4"), Color::Black, Type::Pawn));
+
+        assert!(board.is_attacked(square("A3"), Some(Color::White)));
+        assert!(board.is_attacked(square("A4"), Some(Color::White)));
+        assert!(!board.is_attacked(square("A5"), Some(Color::White)));
+        assert!(!board.is_attacked(square("A3"), Some(Color::Black)));
+    }
+
+    #[test]
+    fn pawns_only_attack_diagonally() {
+        let mut board = Board::new();
+        board.set_piece(Piece::new(square("E4"), Color::White, Type::Pawn));
+        board.set_piece(Piece::new(square("D5"), Color::White, Type::Knight));
+
+        assert!(!board.is_attacked(square("E5"), Some(Color::White)));
+        assert!(board.is_attacked(square("F5"), Some(Color::White)));
+        // Defending an own piece counts as attacking its square
+        assert!(board.is_attacked(square("D5"), Some(Color::White)));
+    }
+
+    #[test]
+    fn diagonal_attacks_give_check() {
+        let mut board = Board::new();
+        board.set_piece(Piece::new(square("E1"), Color::White, Type::King));
+        board.set_piece(Piece::new(square("H4"), Color::Black, Type::Bishop));
+
+        assert!(board.is_attacked(square("E1"), Some(Color::Black)));
+        assert!(board.is_in_check(Color::White));
+    }
+}
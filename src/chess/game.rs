@@ -1,17 +1,11 @@
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
-};
-
-use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use super::board::{Board, Color};
 use super::moves::{HistoryMove, MoveFailureReason};
 use super::pieces::Type;
 
 use crate::chess::moves::NewMove;
-use crate::system::game::GameManager;
+use crate::system::events::{GameEvent, GameEventSender, Offer};
 
 use GameResult::*;
 use MoveFailureReason::*;
@@ -27,6 +21,9 @@ pub enum GameResult {
     ThreefoldRepetition,
     FiftyMoves,
     DrawAgreed,
+    /// Ended before both players made a move, the game does not count.
+    Aborted,
+    Abandoned(Color),
 }
 
 impl GameResult {
@@ -34,8 +31,17 @@ impl GameResult {
         use GameResult::*;
 
         match self {
-            Ongoing | Stalemated | InsufficientMaterial | ThreefoldRepetition | FiftyMoves | DrawAgreed => None,
-            CheckMate(color) | Resignation(color) | OutOfTime(color) => Some(color.get_opposite()),
+            Ongoing | Stalemated | InsufficientMaterial | ThreefoldRepetition | FiftyMoves | DrawAgreed | Aborted => None,
+            CheckMate(color) | Resignation(color) | OutOfTime(color) | Abandoned(color) => Some(color.get_opposite()),
+        }
+    }
+
+    pub fn get_score(&self) -> &'static str {
+        match self.get_winner() {
+            Some(Color::White) => "1-0",
+            Some(Color::Black) => "0-1",
+            None if *self == Ongoing || *self == Aborted => "*",
+            None => "½-½",
         }
     }
 
@@ -44,17 +50,19 @@ impl GameResult {
             Ongoing => String::from("The game is still ongoing."),
             CheckMate(color) => format!("{:?} is checkmated.", color),
             Resignation(color) => format!("{:?} has resigned.", color),
-            OutOfTime(color) => format!("{:?} has resigned.", color),
+            OutOfTime(color) => format!("{:?} has run out of time.", color),
             Stalemated => String::from("Stalemate."),
             InsufficientMaterial => String::from("Insufficient material. "),
             ThreefoldRepetition => String::from("Three-fold repetition."),
             FiftyMoves => String::from("50-move rule violation."),
             DrawAgreed => String::from("Both players agreed to a draw. "),
+            Aborted => String::from("The game was aborted."),
+            Abandoned(color) => format!("{:?} has abandoned the game.", color),
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct GameState {
     pub board: Board,
     pub half_move_clock: u32,
@@ -77,12 +85,13 @@ impl GameState {
     }
 }
 
+#[derive(Clone)]
 pub struct Game {
-    pub manager: Option<Arc<RwLock<GameManager>>>,
     pub state: GameState,
     pub state_history: Vec<GameState>,
     pub result: Option<GameResult>,
-    pub dirty: AtomicBool,
+    /// Where changes are published to, not set for games outside of the game manager.
+    pub events: Option<GameEventSender>,
 }
 
 impl Game {
@@ -90,6 +99,15 @@ impl Game {
         Self::default()
     }
 
+    pub fn from_position(state: GameState, state_history: Vec<GameState>) -> Self {
+        Self {
+            state,
+            state_history,
+            result: None,
+            events: None,
+        }
+    }
+
     pub fn reset(&mut self) {
         self.state.board.setup_default_board();
         self.state.half_move_clock = 0;
@@ -99,24 +117,61 @@ impl Game {
 
         self.state_history.clear();
         self.result = None;
-        self.state_changed();
+        self.publish(vec![GameEvent::MovesTakenBack(0)]);
+    }
+
+    pub fn get_ply(&self) -> usize {
+        self.state_history.len()
     }
 
     pub fn takeback_move(&mut self) -> Result<(), MoveFailureReason> {
+        self.takeback_moves(1)
+    }
+
+    pub fn takeback_moves(&mut self, plies: usize) -> Result<(), MoveFailureReason> {
         if self.result.is_some() {
             return Err(GameEnded);
         }
 
-        match self.state_history.pop() {
-            Some(state) => {
-                self.state = state;
-                self.state.board.recalculate_all_pieces_movements();
+        if plies == 0 || plies > self.get_ply() {
+            return Err(NoPreviousPositions);
+        }
 
-                self.state_changed();
-                Ok(())
-            }
-            None => Err(NoPreviousPositions),
+        self.revert_to_ply(self.get_ply() - plies)
+    }
+
+    /// Takes back moves so that it is the given side's turn again, reverting its last move and any reply to it.
+    pub fn takeback_last_move_of(&mut self, color: Color) -> Result<(), MoveFailureReason> {
+        self.takeback_moves(self.get_plies_to_takeback_for(color))
+    }
+
+    fn get_plies_to_takeback_for(&self, color: Color) -> usize {
+        if self.state.current_turn == color {
+            2
+        } else {
+            1
+        }
+    }
+
+    pub fn revert_to_ply(&mut self, ply: usize) -> Result<(), MoveFailureReason> {
+        if self.result.is_some() {
+            return Err(GameEnded);
+        }
+
+        if ply >= self.get_ply() {
+            return Err(NoSuchPly(ply));
         }
+
+        self.state_history.truncate(ply + 1);
+        self.state = self.state_history.pop().unwrap();
+        self.state.board.recalculate_all_pieces_movements();
+
+        // Offers made in the restored position are no longer relevant
+        self.state.draw_offers.clear();
+        self.state.takeback_offers.clear();
+
+        self.publish(vec![GameEvent::MovesTakenBack(ply)]);
+        Ok(())
     }
 
     pub fn draw(&mut self) -> Result<GameResult, MoveFailureReason> {
@@ -125,11 +180,12 @@ impl Game {
         }
 
         self.result = Some(DrawAgreed);
-        self.state_changed();
+        self.publish(vec![GameEvent::GameEnded(DrawAgreed)]);
         Ok(self.result.unwrap())
     }
 
-    pub fn make_move(&mut self, m: NewMove) -> Result<HistoryMove, MoveFailureReason> {
+    /// Returns the board after the move if the side to move may make it.
+    fn play_on_copy(&self, m: NewMove) -> Result<Board, MoveFailureReason> {
         if self.result.is_some() {
             return Err(GameEnded);
         }
@@ -144,16 +200,33 @@ impl Game {
             return Err(NotYourPiece);
         }
 
+        let piece_type = piece.piece_type;
         let mut new_board = self.state.board.clone();
 
         new_board.make_move_if_valid(m)?;
 
         if new_board.is_in_check(self.state.current_turn) {
-            return Err(InCheckAfterTurn);
+            return Err(self.explain_check_after_move(piece_type, m));
         }
 
-        // Clone this state
+        Ok(new_board)
+    }
+
+    /// Validates a move without making it.
+    pub fn check_move(&self, m: NewMove) -> Result<HistoryMove, MoveFailureReason> {
+        Ok(self.play_on_copy(m)?.last_move.unwrap())
+    }
+
+    pub fn make_move(&mut self, m: NewMove) -> Result<HistoryMove, MoveFailureReason> {
+        let new_board = self.play_on_copy(m)?;
+
+        // Clone this state, offers made in it are declined by making a move
+        let mut events: Vec<GameEvent> = self.state.draw_offers.iter().map(|&color| GameEvent::OfferDeclined(Offer::Draw, color)).collect();
+        events.extend(self.state.takeback_offers.iter().map(|&color| GameEvent::OfferDeclined(Offer::Takeback, color)));
+
         let mut previous_state = self.state.clone();
+        previous_state.draw_offers.clear();
+        previous_state.takeback_offers.clear();
 
         // Make a new state
         self.state.board = new_board;
@@ -190,8 +263,32 @@ impl Game {
             self.result = Some(FiftyMoves);
         }
 
-        self.state_changed();
-        Ok(self.state.board.last_move.unwrap())
+        let last_move = self.state.board.last_move.unwrap();
+        events.push(GameEvent::MoveMade(last_move));
+        events.extend(self.result.map(GameEvent::GameEnded));
+
+        self.publish(events);
+        Ok(last_move)
+    }
+
+    fn explain_check_after_move(&self, piece_type: Type, m: NewMove) -> MoveFailureReason {
+        let color = self.state.current_turn;
+
+        if piece_type == Type::King {
+            return KingMovesIntoCheck(m.to);
+        }
+
+        let attack_map = self.state.board.get_attack_map();
+
+        if !attack_map.get_checkers(color).is_empty() {
+            return CheckNotResolved;
+        }
+
+        if let Some(pin) = attack_map.get_pin(m.from) {
+            return PiecePinned(pin.pinner);
+        }
+
+        InCheckAfterTurn
     }
 
     pub fn resign(&mut self, color: Color) -> Result<GameResult, MoveFailureReason> {
@@ -200,7 +297,37 @@ impl Game {
         }
 
         self.result = Some(Resignation(color));
-        self.state_changed();
+        self.publish(vec![GameEvent::GameEnded(Resignation(color))]);
+        Ok(self.result.unwrap())
+    }
+
+    pub fn flag(&mut self, color: Color) -> Result<GameResult, MoveFailureReason> {
+        if self.result.is_some() {
+            return Err(GameEnded);
+        }
+
+        self.result = Some(OutOfTime(color));
+        self.publish(vec![GameEvent::ClockFlagged(color), GameEvent::GameEnded(OutOfTime(color))]);
+        Ok(self.result.unwrap())
+    }
+
+    pub fn abort(&mut self) -> Result<GameResult, MoveFailureReason> {
+        if self.result.is_some() {
+            return Err(GameEnded);
+        }
+
+        self.result = Some(Aborted);
+        self.publish(vec![GameEvent::GameEnded(Aborted)]);
+        Ok(self.result.unwrap())
+    }
+
+    pub fn abandon(&mut self, color: Color) -> Result<GameResult, MoveFailureReason> {
+        if self.result.is_some() {
+            return Err(GameEnded);
+        }
+
+        self.result = Some(Abandoned(color));
+        self.publish(vec![GameEvent::GameEnded(Abandoned(color))]);
         Ok(self.result.unwrap())
     }
 
@@ -216,7 +343,7 @@ impl Game {
             return self.draw();
         }
 
-        self.state_changed();
+        self.publish(vec![GameEvent::OfferMade(Offer::Draw, color)]);
         Ok(Ongoing)
     }
 
@@ -225,15 +352,21 @@ impl Game {
             return Err(GameEnded);
         }
 
+        if self.state.takeback_offers.is_empty() && self.get_plies_to_takeback_for(color) > self.get_ply() {
+            // The requester has no move of their own to take back
+            return Err(NoPreviousPositions);
+        }
+
         self.state.takeback_offers.push(color);
         self.state.takeback_offers.dedup();
 
         if self.state.takeback_offers.len() == 2 {
-            self.takeback_move()?;
+            let requester = self.state.takeback_offers[0];
+            self.takeback_last_move_of(requester)?;
             return Ok(true);
         }
 
-        self.state_changed();
+        self.publish(vec![GameEvent::OfferMade(Offer::Takeback, color)]);
         Ok(false)
     }
 
@@ -277,27 +410,10 @@ impl Game {
         positions_count >= 3
     }
 
-    pub fn state_changed(&mut self) {
-        self.dirty.store(true, Ordering::Relaxed);
-
-        if let Some(manager) = self.manager.clone() {
-            tokio::spawn(async move {
-                let mut manager = manager.write().await;
-                manager.notify_change();
-            });
-        }
-    }
-
-    pub fn get_and_clear_dirty_state(&mut self) -> bool {
-        let dirty = self.dirty.load(Ordering::Relaxed);
-
-        if !dirty {
-            return false;
+    fn publish(&self, events: Vec<GameEvent>) {
+        if let Some(sender) = &self.events {
+            sender.publish(events);
         }
-
-        self.dirty.store(false, Ordering::Relaxed);
-
-        true
     }
 }
 
@@ -307,11 +423,211 @@ impl Default for Game {
             state: GameState::new(Board::new(), 0, Color::White),
             state_history: Vec::new(),
             result: None,
-            manager: None,
-            dirty: AtomicBool::new(true),
+            events: None,
         };
 
         new.reset();
         new
     }
 }
+
+pub const GAME_FORMAT_VERSION: u32 = 1;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Error)]
+pub enum GameLoadError {
+    #[error("Unsupported game format version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Move {0} cannot be replayed: {1}")]
+    IllegalMove(usize, MoveFailureReason),
+    #[error("The stored position does not match the replayed moves")]
+    InconsistentState,
+}
+
+/// Stable representation of a complete game.
+/// Only the starting position and the moves are needed to rebuild the history, the current state keeps the pending offers.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SerializedGame {
+    pub version: u32,
+    pub start_position: GameState,
+    pub moves: Vec<HistoryMove>,
+    pub state: GameState,
+    pub result: Option<GameResult>,
+}
+
+impl Game {
+    pub fn to_serialized(&self) -> SerializedGame {
+        let mut start_position = self.state_history.first().unwrap_or(&self.state).clone();
+        start_position.board_hash = 0;
+
+        SerializedGame {
+            version: GAME_FORMAT_VERSION,
+            start_position,
+            moves: self
+                .state_history
+                .iter()
+                .skip(1)
+                .chain(std::iter::once(&self.state))
+                .filter_map(|state| state.board.last_move)
+                .take(self.get_ply())
+                .collect(),
+            state: self.state.clone(),
+            result: self.result,
+        }
+    }
+
+    pub fn from_serialized(serialized: SerializedGame) -> Result<Self, GameLoadError> {
+        if serialized.version != GAME_FORMAT_VERSION {
+            return Err(GameLoadError::UnsupportedVersion(serialized.version));
+        }
+
+        let mut game = Game::from_position(serialized.start_position, Vec::new());
+
+        for (index, history_move) in serialized.moves.iter().enumerate() {
+            game.make_move(history_move.to_new_move()).map_err(|reason| GameLoadError::IllegalMove(index, reason))?;
+        }
+
+        if game.state.board != serialized.state.board || game.state.current_turn != serialized.state.current_turn {
+            return Err(GameLoadError::InconsistentState);
+        }
+
+        game.state = serialized.state;
+        game.result = serialized.result;
+
+        Ok(game)
+    }
+}
+
+impl Serialize for Game {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_serialized().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Game {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Game::from_serialized(SerializedGame::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::chess::board::Square;
+
+    fn play(game: &mut Game, moves: &[&str]) {
+        for m in moves {
+            game.make_move(NewMove::from_str(m).unwrap()).unwrap_or_else(|reason| panic!("{} was rejected: {}", m, reason));
+        }
+    }
+
+    fn try_move(game: &mut Game, m: &str) -> Result<HistoryMove, MoveFailureReason> {
+        game.make_move(NewMove::from_str(m).unwrap())
+    }
+
+    #[test]
+    fn castling_out_of_check_is_refused() {
+        let mut game = Game::new();
+
+        // The bishop on b4 checks the king while the way to the rook is clear
+        play(&mut game, &["E2E3", "E7E5", "G1F3", "D7D5", "F1E2", "A7A6", "D2D4", "F8B4"]);
+
+        assert_eq!(try_move(&mut game, "E1G1").err(), Some(MoveFailureReason::CastlingOutOfCheck));
+        assert_eq!(game.get_ply(), 8);
+    }
+
+    #[test]
+    fn rejected_moves_name_the_piece_in_the_way() {
+        let mut game = Game::new();
+
+        assert_eq!(try_move(&mut game, "A1A3").err(), Some(MoveFailureReason::PathBlocked(Square::from_str("A2").unwrap())));
+
+        play(&mut game, &["E2E4", "E7E5", "G1F3", "B8C6", "F1B5", "D7D6", "A2A3"]);
+
+        assert_eq!(try_move(&mut game, "C6D4").err(), Some(MoveFailureReason::PiecePinned(Square::from_str("B5").unwrap())));
+    }
+
+    const CAPTURES_AND_PROMOTION: [&str; 15] = ["E2E4", "H7H6", "E4E5", "D7D5", "E5D6", "H6H5", "G1F3", "H5H4", "F1E2", "A7A6", "E1G1", "A6A5", "D6C7", "A5A4", "C7B8R"];
+
+    fn assert_same_position(game: &Game, moves: &[&str]) {
+        let mut expected = Game::new();
+        play(&mut expected, moves);
+
+        assert!(game.state.board == expected.state.board, "the board differs from the one after {:?}", moves);
+        assert_eq!(game.state.current_turn, expected.state.current_turn);
+        assert_eq!(game.get_ply(), expected.get_ply());
+    }
+
+    #[test]
+    fn takebacks_restore_captured_and_promoted_pieces() {
+        let mut game = Game::new();
+        play(&mut game, &CAPTURES_AND_PROMOTION);
+
+        // Black to move, so white's promotion is its last move
+        game.takeback_last_move_of(Color::White).unwrap();
+        assert_same_position(&game, &CAPTURES_AND_PROMOTION[..14]);
+
+        game.takeback_moves(10).unwrap();
+        assert_same_position(&game, &CAPTURES_AND_PROMOTION[..4]);
+        assert!(game.state.board.get_piece(Square::from_str("D5").unwrap()).is_some_and(|pawn| pawn.color == Color::Black));
+        assert!(game.state.board.get_piece(Square::from_str("B8").unwrap()).is_some_and(|knight| knight.piece_type == Type::Knight));
+
+        // The position can be played again after reverting
+        play(&mut game, &CAPTURES_AND_PROMOTION[4..]);
+        assert_same_position(&game, &CAPTURES_AND_PROMOTION);
+    }
+
+    #[test]
+    fn games_revert_to_the_start() {
+        let mut game = Game::new();
+        play(&mut game, &CAPTURES_AND_PROMOTION[..6]);
+
+        game.revert_to_ply(0).unwrap();
+        assert_same_position(&game, &[]);
+        assert!(game.state_history.is_empty());
+    }
+
+    #[test]
+    fn plies_beyond_the_history_are_refused() {
+        let mut game = Game::new();
+        play(&mut game, &CAPTURES_AND_PROMOTION[..4]);
+
+        assert_eq!(game.revert_to_ply(4), Err(MoveFailureReason::NoSuchPly(4)));
+        assert_eq!(game.revert_to_ply(7), Err(MoveFailureReason::NoSuchPly(7)));
+        assert_eq!(game.takeback_moves(5), Err(MoveFailureReason::NoPreviousPositions));
+        assert_same_position(&game, &CAPTURES_AND_PROMOTION[..4]);
+    }
+
+    #[test]
+    fn serialized_games_load_unchanged() {
+        let mut game = Game::new();
+
+        // En passant, castling and a promotion to a rook
+        play(&mut game, &CAPTURES_AND_PROMOTION);
+        game.offer_draw(Color::White).unwrap();
+        game.offer_takeback(Color::Black).unwrap();
+
+        let json = serde_json::to_string(&game).unwrap();
+        let loaded: Game = serde_json::from_str(&json).unwrap();
+
+        assert!(loaded.state.board == game.state.board);
+        assert_eq!(loaded.state.current_turn, game.state.current_turn);
+        assert_eq!(loaded.state.half_move_clock, game.state.half_move_clock);
+        assert_eq!(loaded.state.draw_offers, vec![Color::White]);
+        assert_eq!(loaded.state.takeback_offers, vec![Color::Black]);
+        assert_eq!(loaded.get_ply(), game.get_ply());
+        assert!(loaded.result.is_none());
+
+        for (loaded, original) in loaded.state_history.iter().zip(game.state_history.iter()) {
+            assert!(loaded.board == original.board);
+            assert_eq!(loaded.current_turn, original.current_turn);
+        }
+
+        let castled_king = loaded.state.board.get_piece(Square::from_str("G1").unwrap()).unwrap();
+        assert!(castled_king.piece_type == Type::King);
+        let promoted = loaded.state.board.get_piece(Square::from_str("B8").unwrap()).unwrap();
+        assert!(promoted.piece_type == Type::Rook && promoted.color == Color::White);
+        assert!(loaded.state.board.get_piece(Square::from_str("D5").unwrap()).is_none());
+    }
+}
@@ -1,4 +1,7 @@
+pub mod analysis;
+pub mod attacks;
 pub mod board;
 pub mod game;
 pub mod moves;
+pub mod pgn;
 pub mod pieces;
@@ -1,10 +1,13 @@
 use super::board::{Color, Square};
 use super::pieces::Type;
 
+use serde::{Deserialize, Serialize};
+
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
-#[derive(Eq, PartialEq, Copy, Clone, Debug, Error)]
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Error, Serialize)]
+#[serde(tag = "code", content = "details")]
 pub enum MoveFailureReason {
     #[error("No piece")]
     NoPiece,
@@ -18,20 +21,48 @@ pub enum MoveFailureReason {
     IllegalPieceMove,
     #[error("In check after turn")]
     InCheckAfterTurn,
+    #[error("This piece is pinned to your king by the piece on {0}")]
+    PiecePinned(Square),
+    #[error("Your king would be in check on {0}")]
+    KingMovesIntoCheck(Square),
+    #[error("You are in check and this move does not get you out of it")]
+    CheckNotResolved,
+    #[error("The path is blocked by the piece on {0}")]
+    PathBlocked(Square),
+    #[error("You cannot castle out of check")]
+    CastlingOutOfCheck,
+    #[error("You cannot castle through check, {0} is attacked")]
+    CastlingThroughCheck(Square),
+    #[error("You cannot castle into check")]
+    CastlingIntoCheck,
+    #[error("You have lost the right to castle on this side")]
+    CastlingRightsLost,
+    #[error("There is no rook to castle with")]
+    NoRookToCastleWith,
+    #[error("Pawns cannot capture forward")]
+    PawnCannotCaptureForward,
+    #[error("Pawns can only move diagonally when capturing")]
+    PawnNothingToCapture,
+    #[error("You have to choose a piece to promote to")]
+    PromotionPieceMissing,
+    #[error("You cannot promote to a {0:?}")]
+    InvalidPromotionPiece(Type),
     #[error("No previous positions")]
     NoPreviousPositions,
+    #[error("There is no position at ply {0}")]
+    NoSuchPly(usize),
     #[error("Game ended")]
     GameEnded,
 }
 
-#[derive(Eq, PartialEq, Copy, Clone)]
+#[derive(Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum Extra {
     Promotion(Type),
     MoveCheck,
     None,
 }
 
-#[derive(Eq, PartialEq, Copy, Clone)]
+#[derive(Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub struct NewMove {
     pub from: Square,
     pub to: Square,
@@ -43,24 +74,33 @@ pub enum MoveParsingError {
     IncorrectMoveFormat,
     IncorrectSquareFormat,
     InvalidSquare,
+    InvalidPromotionPiece,
 }
 
 impl FromStr for NewMove {
     type Err = MoveParsingError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() != 4 {
+        if !s.is_ascii() || (s.len() != 4 && s.len() != 5) {
             return Err(MoveParsingError::IncorrectMoveFormat);
         }
 
         let from = Square::from_str(&s[0..2].to_uppercase())?;
         let to = Square::from_str(&s[2..4].to_uppercase())?;
-        // TODO: Extra
+
+        // Promotion piece is optional and defaults to a queen
+        let promotion = match s[4..].to_uppercase().as_str() {
+            "" | "Q" => Type::Queen,
+            "R" => Type::Rook,
+            "B" => Type::Bishop,
+            "N" => Type::Knight,
+            _ => return Err(MoveParsingError::InvalidPromotionPiece),
+        };
 
         Ok(NewMove {
             from,
             to,
-            extra: Extra::Promotion(Type::Queen),
+            extra: Extra::Promotion(promotion),
         })
     }
 }
@@ -71,7 +111,7 @@ impl Display for MoveParsingError {
     }
 }
 
-#[derive(Eq, PartialEq, Copy, Clone)]
+#[derive(Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub struct HistoryMove {
     pub piece_color: Color,
     pub piece_type: Type,
@@ -80,3 +120,48 @@ pub struct HistoryMove {
     pub capture: bool,
     pub extra: Extra,
 }
+
+impl HistoryMove {
+    pub fn to_new_move(&self) -> NewMove {
+        NewMove {
+            from: self.from,
+            to: self.to,
+            extra: self.extra,
+        }
+    }
+
+    pub fn is_promotion(&self) -> bool {
+        self.piece_type == Type::Pawn && (self.to.rank_number == 1 || self.to.rank_number == 8)
+    }
+
+    pub fn is_same_move(&self, m: &NewMove) -> bool {
+        if self.from != m.from || self.to != m.to {
+            return false;
+        }
+
+        match (self.extra, m.extra) {
+            (Extra::Promotion(ours), Extra::Promotion(theirs)) if self.is_promotion() => ours == theirs,
+            _ => true,
+        }
+    }
+}
+
+impl Display for HistoryMove {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.from, self.to)?;
+
+        if let (true, Extra::Promotion(new_type)) = (self.is_promotion(), self.extra) {
+            let letter = match new_type {
+                Type::Queen => 'Q',
+                Type::Rook => 'R',
+                Type::Bishop => 'B',
+                Type::Knight => 'N',
+                Type::King | Type::Pawn => '?',
+            };
+
+            write!(f, "{}", letter)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+use super::board::{Board, Color, Square};
+use super::game::{Game, GameResult, GameState};
+use super::moves::{Extra, HistoryMove};
+use super::pieces::Type;
+
+fn get_piece_letter(piece_type: Type) -> char {
+    match piece_type {
+        Type::King => 'K',
+        Type::Queen => 'Q',
+        Type::Rook => 'R',
+        Type::Bishop => 'B',
+        Type::Knight => 'N',
+        Type::Pawn => 'P',
+    }
+}
+
+fn write_square(square: Square) -> String {
+    square.to_string().to_lowercase()
+}
+
+/// Writes a move in standard algebraic notation, like `Nbd7`, `exd5` or `e8=Q+`.
+pub fn to_san(before: &Board, m: &HistoryMove, after: &Board) -> String {
+    let mut san = String::new();
+
+    if m.piece_type == Type::King && (m.from.file_number as i8 - m.to.file_number as i8).abs() == 2 {
+        san.push_str(if m.to.file_number > m.from.file_number { "O-O" } else { "O-O-O" });
+    } else if m.piece_type == Type::Pawn {
+        if m.capture || m.from.file_number != m.to.file_number {
+            san.push(m.from.get_file_as_letter().to_ascii_lowercase());
+            san.push('x');
+        }
+
+        san.push_str(&write_square(m.to));
+
+        if let (true, Extra::Promotion(new_type)) = (m.is_promotion(), m.extra) {
+            san.push('=');
+            san.push(get_piece_letter(new_type));
+        }
+    } else {
+        san.push(get_piece_letter(m.piece_type));
+
+        // Other pieces of the same type that could have moved to the same square
+        let others: Vec<Square> = before
+            .get_valid_moves_for(m.piece_color)
+            .iter()
+            .filter(|other| other.piece_type == m.piece_type && other.to == m.to && other.from != m.from)
+            .map(|other| other.from)
+            .collect();
+
+        if !others.is_empty() {
+            if others.iter().all(|other| other.file_number != m.from.file_number) {
+                san.push(m.from.get_file_as_letter().to_ascii_lowercase());
+            } else if others.iter().all(|other| other.rank_number != m.from.rank_number) {
+                san.push_str(&m.from.rank_number.to_string());
+            } else {
+                san.push_str(&write_square(m.from));
+            }
+        }
+
+        if m.capture {
+            san.push('x');
+        }
+
+        san.push_str(&write_square(m.to));
+    }
+
+    let opponent = m.piece_color.get_opposite();
+
+    if after.is_in_check(opponent) {
+        san.push(if after.get_valid_moves_for(opponent).is_empty() { '#' } else { '+' });
+    }
+
+    san
+}
+
+/// Writes a position in Forsyth-Edwards Notation.
+pub fn write_fen(state: &GameState, fullmove_number: usize) -> String {
+    let board = &state.board;
+    let mut ranks = Vec::with_capacity(8);
+
+    for rank in (1..=8).rev() {
+        let mut row = String::new();
+        let mut empty = 0;
+
+        for file in 1..=8 {
+            match board.get_piece(Square::new(file, rank)) {
+                Some(piece) => {
+                    if empty > 0 {
+                        row.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+
+                    let letter = get_piece_letter(piece.piece_type);
+                    row.push(if piece.color == Color::White { letter } else { letter.to_ascii_lowercase() });
+                }
+                None => empty += 1,
+            }
+        }
+
+        if empty > 0 {
+            row.push_str(&empty.to_string());
+        }
+
+        ranks.push(row);
+    }
+
+    let mut castling = String::new();
+    let white = board.state.get_castling_rights_for(Color::White);
+    let black = board.state.get_castling_rights_for(Color::Black);
+
+    for (allowed, letter) in [(white.short_castle, 'K'), (white.long_castle, 'Q'), (black.short_castle, 'k'), (black.long_castle, 'q')].iter() {
+        if *allowed {
+            castling.push(*letter);
+        }
+    }
+
+    if castling.is_empty() {
+        castling.push('-');
+    }
+
+    format!(
+        "{} {} {} {} {} {}",
+        ranks.join("/"),
+        if state.current_turn == Color::White { 'w' } else { 'b' },
+        castling,
+        board.state.en_passant_square.map(write_square).unwrap_or_else(|| String::from("-")),
+        state.half_move_clock,
+        fullmove_number
+    )
+}
+
+pub fn get_result_token(result: Option<GameResult>) -> &'static str {
+    match result {
+        None | Some(GameResult::Ongoing) | Some(GameResult::Aborted) => "*",
+        Some(result) => match result.get_winner() {
+            Some(Color::White) => "1-0",
+            Some(Color::Black) => "0-1",
+            None => "1/2-1/2",
+        },
+    }
+}
+
+/// Writes the moves of a game like `1. e4 e5 2. Nf3`, followed by the result.
+pub fn write_movetext(game: &Game) -> String {
+    let mut tokens = Vec::new();
+    let positions: Vec<&GameState> = game.state_history.iter().chain(std::iter::once(&game.state)).collect();
+
+    for (ply, pair) in positions.windows(2).enumerate() {
+        let (before, after) = (pair[0], pair[1]);
+        let m = match after.board.last_move {
+            Some(m) => m,
+            None => continue,
+        };
+
+        let number = ply / 2 + 1;
+
+        if before.current_turn == Color::White {
+            tokens.push(format!("{}.", number));
+        } else if ply == 0 {
+            tokens.push(format!("{}...", number));
+        }
+
+        tokens.push(to_san(&before.board, &m, &after.board));
+    }
+
+    tokens.push(String::from(get_result_token(game.result)));
+    tokens.join(" ")
+}
@@ -1,10 +1,10 @@
 use super::board::{Board, Color, Square};
-use super::moves::Extra;
+use super::moves::{Extra, MoveFailureReason, MoveFailureReason::*};
 
 use crate::chess::moves::NewMove;
 use serde::{Deserialize, Serialize};
 
-#[derive(Eq, PartialEq, Copy, Clone, Hash, Serialize, Deserialize)]
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Debug, Serialize, Deserialize)]
 pub enum Type {
     King,
     Queen,
@@ -14,14 +14,30 @@ pub enum Type {
     Pawn,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "SerializedPiece")]
 pub struct Piece {
     pub location: Square,
     pub color: Color,
     pub piece_type: Type,
+    #[serde(skip)]
     pub valid_moves: Vec<Square>,
 }
 
+/// Valid moves are not stored, they are recalculated when a piece is loaded.
+#[derive(Deserialize)]
+struct SerializedPiece {
+    location: Square,
+    color: Color,
+    piece_type: Type,
+}
+
+impl From<SerializedPiece> for Piece {
+    fn from(piece: SerializedPiece) -> Self {
+        Piece::new(piece.location, piece.color, piece.piece_type)
+    }
+}
+
 impl Piece {
     pub fn new(location: Square, color: Color, piece_type: Type) -> Self {
         let mut piece = Self {
@@ -61,20 +77,36 @@ impl Piece {
     }
 
     pub fn is_move_valid(&self, board: &Board, m: NewMove) -> bool {
+        self.validate_move(board, m).is_ok()
+    }
+
+    pub fn validate_move(&self, board: &Board, m: NewMove) -> Result<(), MoveFailureReason> {
         if !m.to.is_valid() {
-            return false;
+            return Err(MoveInvalid);
         }
 
-        if !self.valid_moves.iter().any(|&valid_move| valid_move == m.to) {
-            return false;
+        if !self.valid_moves.contains(&m.to) {
+            return Err(IllegalPieceMove);
         }
 
-        self.get_move_controller().check_if_move_valid(board, &self, m)
+        self.get_move_controller().check_if_move_valid(board, self, m)
     }
 
     pub fn after_move(&self, board: &mut Board) {
         self.get_move_controller().after_move(board)
     }
+
+    /// Lines of squares this piece attacks, ordered from the piece outwards, ignoring any pieces in the way.
+    pub fn get_attack_rays(&self) -> Vec<Vec<Square>> {
+        let mut rays = self.get_move_controller().get_attack_rays(self);
+
+        for ray in rays.iter_mut() {
+            ray.retain(|square| square.is_valid());
+        }
+
+        rays.retain(|ray| !ray.is_empty());
+        rays
+    }
 }
 
 impl PartialEq for Piece {
@@ -86,11 +118,26 @@ impl PartialEq for Piece {
 pub trait MoveController {
     fn recalculate_valid_moves(&self, piece: &mut Piece);
 
-    fn check_if_move_valid(&self, board: &Board, piece: &Piece, m: NewMove) -> bool;
+    fn check_if_move_valid(&self, board: &Board, piece: &Piece, m: NewMove) -> Result<(), MoveFailureReason>;
 
     fn after_move(&self, board: &mut Board);
+
+    fn get_attack_rays(&self, piece: &Piece) -> Vec<Vec<Square>>;
+}
+
+fn get_sliding_rays(piece: &Piece, directions: &[(i8, i8)]) -> Vec<Vec<Square>> {
+    directions.iter().map(|&(file, rank)| piece.location.get_relatives_until_invalid(file, rank)).collect()
 }
 
+fn get_leaping_rays(piece: &Piece, offsets: &[(i8, i8)]) -> Vec<Vec<Square>> {
+    offsets.iter().map(|&(file, rank)| vec![piece.location.get_relative(file, rank)]).collect()
+}
+
+const STRAIGHT_DIRECTIONS: [(i8, i8); 4] = [(-1, 0), (1, 0), (0, 1), (0, -1)];
+const DIAGONAL_DIRECTIONS: [(i8, i8); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+const KING_OFFSETS: [(i8, i8); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [(1, 2), (-1, 2), (1, -2), (-1, -2), (2, 1), (-2, 1), (2, -1), (-2, -1)];
+
 pub struct PawnMoveController {}
 
 impl MoveController for PawnMoveController {
@@ -106,7 +153,7 @@ impl MoveController for PawnMoveController {
         }
     }
 
-    fn check_if_move_valid(&self, board: &Board, piece: &Piece, m: NewMove) -> bool {
+    fn check_if_move_valid(&self, board: &Board, piece: &Piece, m: NewMove) -> Result<(), MoveFailureReason> {
         let advance_direction = piece.get_advance_direction();
         let destination_rank = ((piece.location.rank_number as i8) + advance_direction) as u8;
         let first_move_destination_rank = ((piece.location.rank_number as i8) + advance_direction * 2) as u8;
@@ -117,27 +164,25 @@ impl MoveController for PawnMoveController {
                 // Not a +1 move, maybe its a first move?
                 if piece.location.rank_number == 2 || piece.location.rank_number == 7 {
                     if first_move_destination_rank != m.to.rank_number {
-                        return false;
+                        return Err(IllegalPieceMove);
                     }
 
                     // Cannot jump over pieces
-                    if board.get_piece(Square::new(piece.location.file_number, destination_rank)).is_some() {
-                        return false;
-                    }
+                    board.check_path_clear(&[Square::new(piece.location.file_number, destination_rank)])?;
                 } else {
-                    return false;
+                    return Err(IllegalPieceMove);
                 }
             }
 
             if board.get_piece(m.to).is_some() {
                 // Pawns cannot capture forward
-                return false;
+                return Err(PawnCannotCaptureForward);
             }
         } else if piece.location.file_number == m.to.file_number - 1 || piece.location.file_number == m.to.file_number + 1 {
             // Capture diagonally
             if destination_rank != m.to.rank_number {
                 // Can only move 1 when capturing
-                return false;
+                return Err(IllegalPieceMove);
             }
 
             let mut capture_piece = board.get_piece(m.to);
@@ -152,32 +197,29 @@ impl MoveController for PawnMoveController {
             if let Some(captured_piece) = capture_piece {
                 if captured_piece.color == piece.color {
                     // Cannot capture own pieces
-                    return false;
+                    return Err(CannotCaptureOwnPiece);
                 }
             } else {
                 // Not a capture, cannot go
-                return false;
+                return Err(PawnNothingToCapture);
             }
         } else {
             // Invalid move
-            return false;
+            return Err(IllegalPieceMove);
         }
 
         if m.to.rank_number == 1 || m.to.rank_number == 8 {
             // Promotion rank
-            if let Extra::MoveCheck = m.extra {
-                return true;
-            }
-
-            if let Extra::Promotion(_) = m.extra {
-                return true;
-            }
-
-            // No promotion arguments
-            return false;
+            return match m.extra {
+                Extra::MoveCheck => Ok(()),
+                Extra::Promotion(new_type) if new_type == Type::King || new_type == Type::Pawn => Err(InvalidPromotionPiece(new_type)),
+                Extra::Promotion(_) => Ok(()),
+                // No promotion arguments
+                Extra::None => Err(PromotionPieceMissing),
+            };
         }
 
-        true
+        Ok(())
     }
 
     fn after_move(&self, board: &mut Board) {
@@ -221,6 +263,11 @@ impl MoveController for PawnMoveController {
             board.remove_piece(capture_square)
         }
     }
+
+    fn get_attack_rays(&self, piece: &Piece) -> Vec<Vec<Square>> {
+        let advance_direction = piece.get_advance_direction();
+        get_leaping_rays(piece, &[(-1, advance_direction), (1, advance_direction)])
+    }
 }
 
 pub struct RookMoveController {}
@@ -233,8 +280,8 @@ impl MoveController for RookMoveController {
         piece.valid_moves.append(&mut piece.location.get_relatives_until_invalid(0, -1));
     }
 
-    fn check_if_move_valid(&self, board: &Board, piece: &Piece, m: NewMove) -> bool {
-        board.is_path_clear(piece.location.find_path_to(&m.to).unwrap())
+    fn check_if_move_valid(&self, board: &Board, piece: &Piece, m: NewMove) -> Result<(), MoveFailureReason> {
+        board.check_path_clear(&piece.location.find_path_to(&m.to).unwrap())
     }
 
     fn after_move(&self, board: &mut Board) {
@@ -248,6 +295,9 @@ impl MoveController for RookMoveController {
             board.state.get_castling_rights_mut_for(last_move.piece_color).long_castle = false;
         }
     }
+    fn get_attack_rays(&self, piece: &Piece) -> Vec<Vec<Square>> {
+        get_sliding_rays(piece, &STRAIGHT_DIRECTIONS)
+    }
 }
 
 pub struct KnightMoveController {}
@@ -264,11 +314,15 @@ impl MoveController for KnightMoveController {
         piece.valid_moves.push(piece.location.get_relative(-2, -1));
     }
 
-    fn check_if_move_valid(&self, _board: &Board, _piece: &Piece, _m: NewMove) -> bool {
-        true
+    fn check_if_move_valid(&self, _board: &Board, _piece: &Piece, _m: NewMove) -> Result<(), MoveFailureReason> {
+        Ok(())
     }
 
     fn after_move(&self, _board: &mut Board) {}
+
+    fn get_attack_rays(&self, piece: &Piece) -> Vec<Vec<Square>> {
+        get_leaping_rays(piece, &KNIGHT_OFFSETS)
+    }
 }
 
 pub struct BishopMoveController {}
@@ -281,11 +335,15 @@ impl MoveController for BishopMoveController {
         piece.valid_moves.append(&mut piece.location.get_relatives_until_invalid(1, 1));
     }
 
-    fn check_if_move_valid(&self, board: &Board, piece: &Piece, m: NewMove) -> bool {
-        board.is_path_clear(piece.location.find_path_to(&m.to).unwrap())
+    fn check_if_move_valid(&self, board: &Board, piece: &Piece, m: NewMove) -> Result<(), MoveFailureReason> {
+        board.check_path_clear(&piece.location.find_path_to(&m.to).unwrap())
     }
 
     fn after_move(&self, _board: &mut Board) {}
+
+    fn get_attack_rays(&self, piece: &Piece) -> Vec<Vec<Square>> {
+        get_sliding_rays(piece, &DIAGONAL_DIRECTIONS)
+    }
 }
 
 pub struct QueenMoveController {}
@@ -302,11 +360,15 @@ impl MoveController for QueenMoveController {
         piece.valid_moves.append(&mut piece.location.get_relatives_until_invalid(1, 1));
     }
 
-    fn check_if_move_valid(&self, board: &Board, piece: &Piece, m: NewMove) -> bool {
-        board.is_path_clear(piece.location.find_path_to(&m.to).unwrap())
+    fn check_if_move_valid(&self, board: &Board, piece: &Piece, m: NewMove) -> Result<(), MoveFailureReason> {
+        board.check_path_clear(&piece.location.find_path_to(&m.to).unwrap())
     }
 
     fn after_move(&self, _board: &mut Board) {}
+
+    fn get_attack_rays(&self, piece: &Piece) -> Vec<Vec<Square>> {
+        [get_sliding_rays(piece, &STRAIGHT_DIRECTIONS), get_sliding_rays(piece, &DIAGONAL_DIRECTIONS)].concat()
+    }
 }
 
 pub struct KingMoveController {}
@@ -326,16 +388,16 @@ impl MoveController for KingMoveController {
         piece.valid_moves.push(piece.location.get_relative(2, 0));
     }
 
-    fn check_if_move_valid(&self, board: &Board, piece: &Piece, m: NewMove) -> bool {
+    fn check_if_move_valid(&self, board: &Board, piece: &Piece, m: NewMove) -> Result<(), MoveFailureReason> {
         if m.to.file_number as i8 == (piece.location.file_number as i8) + 2 {
-            return self.can_castle_short(board, piece);
+            return self.check_can_castle_short(board, piece);
         }
 
         if m.to.file_number as i8 == (piece.location.file_number as i8) - 2 {
-            return self.can_castle_long(board, piece);
+            return self.check_can_castle_long(board, piece);
         }
 
-        true
+        Ok(())
     }
 
     fn after_move(&self, board: &mut Board) {
@@ -363,57 +425,67 @@ impl MoveController for KingMoveController {
             }
         }
 
-        let mut castling_rights = board.state.get_castling_rights_mut_for(last_move.piece_color);
+        let castling_rights = board.state.get_castling_rights_mut_for(last_move.piece_color);
         castling_rights.short_castle = false;
         castling_rights.long_castle = false;
     }
+
+    fn get_attack_rays(&self, piece: &Piece) -> Vec<Vec<Square>> {
+        get_leaping_rays(piece, &KING_OFFSETS)
+    }
 }
 
 impl KingMoveController {
-    pub fn can_castle_short(&self, board: &Board, king: &Piece) -> bool {
+    pub fn check_can_castle_short(&self, board: &Board, king: &Piece) -> Result<(), MoveFailureReason> {
         if !board.state.get_castling_rights_for(king.color).short_castle {
-            return false;
+            return Err(CastlingRightsLost);
         }
 
-        if board.is_attacked(Square::new(6, king.location.rank_number), Some(king.color.get_opposite())) {
-            // f1 / f8 is attacked
-            return false;
+        // f1 / f8 must not be attacked, g1 / g8 is where the king ends up
+        let rank = king.location.rank_number;
+        self.validate_can_castle(board, king, Square::new(8, rank), Square::new(6, rank), Square::new(7, rank))
+    }
+
+    pub fn check_can_castle_long(&self, board: &Board, king: &Piece) -> Result<(), MoveFailureReason> {
+        if !board.state.get_castling_rights_for(king.color).long_castle {
+            return Err(CastlingRightsLost);
         }
 
-        self.validate_can_castle(board, king, Square::new(8, king.location.rank_number))
+        // d1 / d8 must not be attacked, c1 / c8 is where the king ends up
+        let rank = king.location.rank_number;
+        self.validate_can_castle(board, king, Square::new(1, rank), Square::new(4, rank), Square::new(3, rank))
     }
 
-    pub fn can_castle_long(&self, board: &Board, king: &Piece) -> bool {
-        if !board.state.get_castling_rights_for(king.color).long_castle {
-            return false;
+    fn validate_can_castle(&self, board: &Board, king: &Piece, rook_location: Square, passed_square: Square, destination: Square) -> Result<(), MoveFailureReason> {
+        if (king.color == Color::White && king.location.to_string() != "E1") || (king.color == Color::Black && king.location.to_string() != "E8") {
+            return Err(CastlingRightsLost);
         }
 
-        if board.is_attacked(Square::new(4, king.location.rank_number), Some(king.color.get_opposite())) {
-            // d1 / d8 is attacked
-            return false;
+        match board.get_piece(rook_location) {
+            Some(rook) if rook.color == king.color && rook.piece_type == Type::Rook => {}
+            _ => return Err(NoRookToCastleWith),
         }
 
-        self.validate_can_castle(board, king, Square::new(1, king.location.rank_number))
-    }
+        match king.location.find_path_to(&rook_location) {
+            Some(path) => board.check_path_clear(&path)?,
+            None => return Err(IllegalPieceMove),
+        }
 
-    fn validate_can_castle(&self, board: &Board, king: &Piece, rook_location: Square) -> bool {
-        if (king.color == Color::White && king.location.to_string() != "E1") || (king.color == Color::Black && king.location.to_string() != "E8") {
-            return false;
+        let enemy = Some(king.color.get_opposite());
+
+        if board.is_attacked(king.location, enemy) {
+            return Err(CastlingOutOfCheck);
         }
 
-        if let Some(rook) = board.get_piece(rook_location) {
-            if rook.color != king.color || rook.piece_type != Type::Rook {
-                return false;
-            }
-        } else {
-            return false;
+        if board.is_attacked(passed_square, enemy) {
+            return Err(CastlingThroughCheck(passed_square));
         }
 
-        if let Some(path) = king.location.find_path_to(&rook_location) {
-            return board.is_path_clear(path);
+        if board.is_attacked(destination, enemy) {
+            return Err(CastlingIntoCheck);
         }
 
-        false
+        Ok(())
     }
 }
 
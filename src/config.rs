@@ -24,11 +24,59 @@ pub struct OAuth2Config {
     pub redirect_url: String,
 }
 
+#[derive(Serialize, Deserialize, Copy, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    Json,
+    Sqlite,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+    /// Directory for the JSON backend, database file for the SQLite one.
+    pub path: String,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            backend: StorageBackend::Json,
+            path: String::from("games"),
+        }
+    }
+}
+
+/// Minutes the side to move may stay inactive before its game is acted on, correspondence games use their deadlines instead.
+#[derive(Serialize, Deserialize, Copy, Clone)]
+pub struct AbandonmentConfig {
+    /// Games in which a player never makes their first move are aborted.
+    pub abort_after: u64,
+    /// The waiting player may claim the win.
+    pub claim_after: u64,
+    /// The inactive player loses the game.
+    pub forfeit_after: u64,
+}
+
+impl Default for AbandonmentConfig {
+    fn default() -> Self {
+        AbandonmentConfig {
+            abort_after: 10,
+            claim_after: 15,
+            forfeit_after: 60,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Config {
     pub discord: DiscordConfig,
     pub http: HttpConfig,
     pub oauth2: OAuth2Config,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub abandonment: AbandonmentConfig,
 }
 
 const CONFIG_FILE_NAME: &str = "config.toml";
@@ -43,7 +91,7 @@ pub fn load_config() -> std::io::Result<Config> {
 
     let string = std::fs::read_to_string(path)?;
 
-    Ok(toml::from_str(&*string).unwrap())
+    Ok(toml::from_str(&string).unwrap())
 }
 
 impl Default for Config {
@@ -64,6 +112,8 @@ impl Default for Config {
                 client_secret: String::from("CHANGEME"),
                 redirect_url: String::from("CHANGEME"),
             },
+            storage: StorageConfig::default(),
+            abandonment: AbandonmentConfig::default(),
         }
     }
 }
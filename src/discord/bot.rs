@@ -9,18 +9,26 @@ use serenity::framework::standard::{
     macros::{help, hook},
     Args, CommandGroup, CommandResult, Delimiter, DispatchError, HelpOptions, StandardFramework,
 };
-use serenity::model::channel::Message;
+use serenity::model::channel::{Message, Reaction, ReactionType};
 use serenity::model::gateway::Ready;
 use serenity::model::id::{ChannelId, UserId};
+use serenity::model::misc::Mentionable;
 use serenity::prelude::{Context, EventHandler, TypeMapKey};
 use serenity::{client::Client, framework::standard::CommandError};
 use tokio::sync::RwLock;
 
 use super::commands::admin::ADMIN_GROUP;
-use super::commands::game::make_move;
+use super::commands::arena::ARENAS_GROUP;
 use super::commands::game::GAMECOMMANDS_GROUP;
+use super::commands::game::{make_move, make_rematch_reply, Reply};
+use super::commands::rating::RATINGS_GROUP;
+use super::commands::seek::MATCHMAKING_GROUP;
+use super::commands::simul::SIMULS_GROUP;
+use super::commands::stats::STATISTICS_GROUP;
+use super::commands::tournament::TOURNAMENTS_GROUP;
 use crate::config::DiscordConfig;
 use crate::system::game::GameManager;
+use crate::system::rematch::REMATCH_EMOJI;
 use crate::util::board_visualizer::BoardVisualizer;
 
 struct Handler;
@@ -30,10 +38,43 @@ impl EventHandler for Handler {
     async fn ready(&self, _: Context, ready: Ready) {
         println!("{} is connected!", ready.user.name);
     }
+
+    /// Reacting to the result of a game offers a rematch.
+    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        if reaction.emoji != ReactionType::Unicode(String::from(REMATCH_EMOJI)) {
+            return;
+        }
+
+        let player = match reaction.user_id {
+            Some(player) if player != ctx.cache.current_user_id().await => player,
+            _ => return,
+        };
+
+        let data = ctx.data.read().await;
+        let data = data.get::<BotData>().unwrap();
+
+        let reply = {
+            let mut game_manager = data.game_manager.write().await;
+
+            let id = match game_manager.get_rematch_by_message(reaction.message_id) {
+                Some(id) => id,
+                None => return,
+            };
+
+            match game_manager.offer_rematch(id, player, None) {
+                Ok(outcome) => make_rematch_reply(data, player, outcome),
+                Err(why) => Reply::Message(format!("{}, {}", player.mention(), why)),
+            }
+        };
+
+        if let Err(why) = reply.send(&ctx, reaction.channel_id).await {
+            eprintln!("Failed to answer a rematch reaction: {}", why);
+        }
+    }
 }
 
 pub struct BotData {
-    pub visualizer: BoardVisualizer,
+    pub visualizer: Arc<BoardVisualizer>,
     pub game_manager: Arc<RwLock<GameManager>>,
     pub prefix: String,
     pub play_url: String,
@@ -44,7 +85,7 @@ impl TypeMapKey for BotData {
 }
 
 pub async fn start_bot(config: DiscordConfig, data: BotData) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let mut client = Client::new(config.token.clone())
+    let mut client = Client::builder(config.token.clone())
         .type_map_insert::<BotData>(data)
         .event_handler(Handler)
         .framework(
@@ -60,7 +101,13 @@ pub async fn start_bot(config: DiscordConfig, data: BotData) -> Result<(), Box<d
                 .after(command_error_handler)
                 .help(&MY_HELP)
                 .group(&ADMIN_GROUP)
-                .group(&GAMECOMMANDS_GROUP),
+                .group(&GAMECOMMANDS_GROUP)
+                .group(&RATINGS_GROUP)
+                .group(&STATISTICS_GROUP)
+                .group(&MATCHMAKING_GROUP)
+                .group(&TOURNAMENTS_GROUP)
+                .group(&ARENAS_GROUP)
+                .group(&SIMULS_GROUP),
         )
         .await
         .expect("client");
@@ -98,7 +145,7 @@ async fn my_help(context: &Context, msg: &Message, args: Args, help_options: &'s
 
 #[hook]
 async fn normal_message(ctx: &Context, msg: &Message) {
-    static REGEX: Lazy<Regex> = Lazy::new(|| Regex::new("^([A-H][1-8]){2}$").unwrap());
+    static REGEX: Lazy<Regex> = Lazy::new(|| Regex::new("^([A-H][1-8]){2}[QRBN]?$").unwrap());
 
     let args;
     {
@@ -119,7 +166,9 @@ async fn normal_message(ctx: &Context, msg: &Message) {
         args = Args::new(&move_str, &[Delimiter::Single(' ')])
     }
 
-    let _ = make_move(ctx, msg, args).await;
+    if let Err(why) = make_move(ctx, msg, args).await {
+        let _ = msg.reply(ctx, why).await;
+    }
 }
 
 #[hook]
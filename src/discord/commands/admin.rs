@@ -4,15 +4,14 @@ use serenity::framework::standard::{
 };
 use serenity::model::channel::Message;
 use serenity::model::id::UserId;
-use serenity::model::misc::Mentionable;
 use serenity::prelude::Context;
 
 use super::GeneralError;
-use crate::chess::moves::NewMove;
+use crate::chess::moves::{MoveFailureReason, NewMove};
 use crate::discord::bot::BotData;
 use crate::discord::commands::game::send_board;
 use crate::http::http_server::UserInfo;
-use crate::system::game::GameAnnouncer;
+use crate::system::game::{GameAnnouncer, GameOptions, GameSelector};
 
 #[derive(Error, Debug)]
 pub enum AdminCommandError {
@@ -22,12 +21,14 @@ pub enum AdminCommandError {
     FailedToDraw,
     #[error("Failed to takeback a move.")]
     FailedToTakeback,
+    #[error("Failed to revert the game: {0}")]
+    FailedToRevert(MoveFailureReason),
 }
 
 #[group]
 #[prefixes("admin")]
 #[description = "Admin commands."]
-#[commands(start, force_resign, force_draw, force_takeback, force_move)]
+#[commands(start, force_resign, force_draw, force_takeback, force_goto, force_move)]
 #[owners_only]
 pub struct Admin;
 
@@ -39,120 +40,170 @@ async fn start(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let white = args.single::<UserId>()?.to_user(&ctx).await?;
     let black = args.single::<UserId>()?.to_user(&ctx).await?;
 
-    let mut data = ctx.data.write().await;
-    let data = data.get_mut::<BotData>().unwrap();
-    let mut game_manager = data.game_manager.write().await;
-
-    let game = game_manager
-        .create_game(UserInfo::from(&white), UserInfo::from(&black), Some(GameAnnouncer::new(ctx.http.clone(), msg.channel_id)))
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+
+    let game = data
+        .game_manager
+        .write()
+        .await
+        .create_game(
+            UserInfo::from(&white),
+            UserInfo::from(&black),
+            Some(GameAnnouncer::new(ctx.http.clone(), msg.channel_id)),
+            GameOptions::default(),
+        )
         .ok_or(GeneralError::FailedToCreateGame)?;
 
-    send_board(
-        ctx,
-        msg.channel_id,
-        &data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
-        format!("{}, {}, the game has started! \nYou can play at {}", white, black, data.play_url),
-    )
-    .await?;
+    let (image, header) = {
+        let game = game.lock().unwrap();
+
+        (
+            data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
+            format!("{}, {}, game #{} has started! \nYou can play at {}", white, black, game.id, data.play_url),
+        )
+    };
+
+    send_board(ctx, msg.channel_id, &image, header).await?;
 
     Ok(())
 }
 
 #[command]
 #[description = "Forcefully resign as a player. "]
+#[usage = "@player [#game|@opponent]"]
 #[min_args(1)]
 async fn force_resign(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let player = args.single::<UserId>()?;
+    let selector = args.single::<GameSelector>().ok();
 
-    let mut data = ctx.data.write().await;
-    let data = data.get_mut::<BotData>().unwrap();
-    let mut game_manager = data.game_manager.write().await;
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game = data.game_manager.read().await.find_game(player, selector)?;
 
-    let game = game_manager.get_game(player).ok_or(GeneralError::PlayerNotInGame)?;
+    let image = {
+        let mut game = game.lock().unwrap();
+        let side = game.get_side_of_player(player).unwrap();
 
-    game.chess_game.resign(game.get_side_of_player(player).unwrap()).map_err(|_| GeneralError::FailedToResign)?;
+        game.chess_game.resign(side).map_err(|_| GeneralError::FailedToResign)?;
+        data.visualizer.visualize(&game.chess_game.state.board).unwrap()
+    };
 
-    send_board(
-        ctx,
-        msg.channel_id,
-        &data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
-        String::from("The game was forcefully resigned. "),
-    )
-    .await?;
+    send_board(ctx, msg.channel_id, &image, String::from("The game was forcefully resigned. ")).await?;
 
     Ok(())
 }
 
 #[command]
 #[description = "Forcefully draw a player's game. "]
+#[usage = "@player [#game|@opponent]"]
 #[min_args(1)]
 async fn force_draw(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let player = args.single::<UserId>()?;
+    let selector = args.single::<GameSelector>().ok();
 
-    let mut data = ctx.data.write().await;
-    let data = data.get_mut::<BotData>().unwrap();
-    let mut game_manager = data.game_manager.write().await;
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game = data.game_manager.read().await.find_game(player, selector)?;
 
-    let game = game_manager.get_game(player).ok_or(GeneralError::PlayerNotInGame)?;
+    let image = {
+        let mut game = game.lock().unwrap();
 
-    game.chess_game.draw().map_err(|_| AdminCommandError::FailedToDraw)?;
+        game.chess_game.draw().map_err(|_| AdminCommandError::FailedToDraw)?;
+        data.visualizer.visualize(&game.chess_game.state.board).unwrap()
+    };
 
-    send_board(
-        ctx,
-        msg.channel_id,
-        &data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
-        String::from("The game was forcefully drawn. "),
-    )
-    .await?;
+    send_board(ctx, msg.channel_id, &image, String::from("The game was forcefully drawn. ")).await?;
 
     Ok(())
 }
 
 #[command]
-#[description = "Forcefully resign as a player. "]
+#[description = "Forcefully take back one or more moves (plies) in a player's game. "]
+#[usage = "@player [#game|@opponent] [plies]"]
 #[min_args(1)]
 async fn force_takeback(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let player = args.single::<UserId>()?;
+    let selector = args.single::<GameSelector>().ok();
+    let plies = if args.is_empty() { 1 } else { args.single::<usize>()? };
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game = data.game_manager.read().await.find_game(player, selector)?;
+
+    let (image, header) = {
+        let mut game = game.lock().unwrap();
+
+        game.chess_game.takeback_moves(plies).map_err(|_| AdminCommandError::FailedToTakeback)?;
+        (
+            data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
+            format!(
+                "{} taken back, the game is now at ply {}. Your turn {} ",
+                if plies == 1 { String::from("The move was") } else { format!("{} moves were", plies) },
+                game.chess_game.get_ply(),
+                game.mention_side(game.chess_game.state.current_turn)
+            ),
+        )
+    };
+
+    send_board(ctx, msg.channel_id, &image, header).await?;
 
-    let mut data = ctx.data.write().await;
-    let data = data.get_mut::<BotData>().unwrap();
-    let mut game_manager = data.game_manager.write().await;
+    Ok(())
+}
 
-    let game = game_manager.get_game(player).ok_or(GeneralError::PlayerNotInGame)?;
+#[command]
+#[description = "Revert a player's game to the position after the given ply, 0 being the starting position. "]
+#[usage = "@player [#game|@opponent] <ply>"]
+#[aliases("goto", "revert")]
+#[min_args(2)]
+async fn force_goto(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let player = args.single::<UserId>()?;
+    let selector = args.single::<GameSelector>().ok();
+    let ply = args.single::<usize>()?;
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game = data.game_manager.read().await.find_game(player, selector)?;
 
-    game.chess_game.takeback_move().map_err(|_| AdminCommandError::FailedToTakeback)?;
-    send_board(
-        ctx,
-        msg.channel_id,
-        &data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
-        format!("The move was taken back. Your turn {} ", game.get_player_id_by_side(game.chess_game.state.current_turn).mention()),
-    )
-    .await?;
+    let (image, header) = {
+        let mut game = game.lock().unwrap();
+
+        game.chess_game.revert_to_ply(ply).map_err(AdminCommandError::FailedToRevert)?;
+        (
+            data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
+            format!("The game was reverted to ply {}. Your turn {} ", ply, game.mention_side(game.chess_game.state.current_turn)),
+        )
+    };
+
+    send_board(ctx, msg.channel_id, &image, header).await?;
 
     Ok(())
 }
 
 #[command]
 #[description = "Make a move in a player's game"]
+#[usage = "@player [#game|@opponent] <move>"]
 #[min_args(2)]
 async fn force_move(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let player = args.single::<UserId>()?;
+    let selector = args.single::<GameSelector>().ok();
     let new_move = args.single::<NewMove>()?;
 
-    let mut data = ctx.data.write().await;
-    let data = data.get_mut::<BotData>().unwrap();
-    let mut game_manager = data.game_manager.write().await;
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game = data.game_manager.read().await.find_game(player, selector)?;
+
+    let (image, header) = {
+        let mut game = game.lock().unwrap();
 
-    let game = game_manager.get_game(player).ok_or(GeneralError::PlayerNotInGame)?;
+        game.chess_game.make_move(new_move).map_err(GeneralError::FailedToMove)?;
+        (
+            data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
+            format!("Your move {}", game.mention_side(game.chess_game.state.current_turn)),
+        )
+    };
 
-    game.chess_game.make_move(new_move).map_err(GeneralError::FailedToMove)?;
-    send_board(
-        ctx,
-        msg.channel_id,
-        &data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
-        format!("Your move {}", game.get_player_id_by_side(game.chess_game.state.current_turn).mention()),
-    )
-    .await?;
+    send_board(ctx, msg.channel_id, &image, header).await?;
 
     Ok(())
 }
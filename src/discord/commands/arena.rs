@@ -0,0 +1,189 @@
+use std::time::Duration;
+
+use serenity::framework::standard::{
+    macros::{command, group},
+    Args, CommandResult,
+};
+use serenity::model::channel::Message;
+use serenity::prelude::Context;
+
+use crate::discord::bot::BotData;
+use crate::http::http_server::UserInfo;
+use crate::system::arena::{ArenaError, ArenaId, ArenaState};
+use crate::system::game::{GameAnnouncer, GameOptions};
+
+#[group]
+#[prefixes("arena")]
+#[description = "Arenas, players are paired again as soon as their game ends until time runs out."]
+#[commands(create, join, leave, start, scoreboard, list)]
+#[only_in(guilds)]
+pub struct Arenas;
+
+#[command]
+#[description = "Create an arena lasting the given number of minutes, everything after the game options is its name."]
+#[usage = "<minutes> [5+3|3d|unlimited] [rated|casual] [standard] [name]"]
+#[min_args(1)]
+async fn create(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let minutes = args.single::<u64>()?;
+    let mut options = GameOptions::default();
+
+    while let Ok(option) = args.parse::<String>() {
+        if option.to_lowercase() == "private" || !options.parse_option(&option) {
+            break;
+        }
+
+        args.advance();
+    }
+
+    let name = match args.remains() {
+        Some(name) => name.to_string(),
+        None => format!("{}'s arena", msg.author.name),
+    };
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let mut game_manager = data.game_manager.write().await;
+
+    let arena = game_manager.create_arena(
+        name,
+        msg.author.id,
+        Duration::from_secs(minutes * 60),
+        options,
+        Some(GameAnnouncer::new(ctx.http.clone(), msg.channel_id)),
+    );
+
+    msg.channel_id
+        .say(
+            &ctx,
+            format!(
+                "{} created the {} minute arena #{} ({}) with {} games.\nType {prefix}arena join {id} to join.",
+                msg.author,
+                minutes,
+                arena.id,
+                arena.name,
+                arena.options,
+                prefix = data.prefix,
+                id = arena.id
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Join an arena, also while it is running."]
+#[usage = "<arena>"]
+#[min_args(1)]
+async fn join(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = args.single::<ArenaId>()?;
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let mut game_manager = data.game_manager.write().await;
+
+    let arena = game_manager.join_arena(id, UserInfo::from(&msg.author))?;
+
+    let message = match arena.state {
+        ArenaState::Running => format!("{} joined arena #{} and will be paired as soon as an opponent is free.", msg.author, arena.id),
+        _ => format!("{} joined arena #{}, {} players are registered.", msg.author, arena.id, arena.players.len()),
+    };
+
+    msg.channel_id.say(&ctx, message).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Leave an arena. Once it is running you keep your score but are not paired anymore."]
+#[usage = "<arena>"]
+#[min_args(1)]
+async fn leave(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = args.single::<ArenaId>()?;
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let mut game_manager = data.game_manager.write().await;
+
+    let arena = game_manager.leave_arena(id, msg.author.id)?;
+
+    msg.channel_id.say(&ctx, format!("{} left arena #{}.", msg.author, arena.id)).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Start your arena, the scoreboard is posted and kept up to date in the channel it was created in."]
+#[usage = "<arena>"]
+#[min_args(1)]
+async fn start(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = args.single::<ArenaId>()?;
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let mut game_manager = data.game_manager.write().await;
+
+    let arena = game_manager.start_arena(id, msg.author.id)?;
+
+    msg.channel_id
+        .say(
+            &ctx,
+            format!(
+                "Arena #{} has started with {} players and ends in {} minutes.",
+                arena.id,
+                arena.players.len(),
+                arena.duration.as_secs() / 60
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Show the scores of an arena."]
+#[usage = "<arena>"]
+#[min_args(1)]
+async fn scoreboard(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = args.single::<ArenaId>()?;
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let mut game_manager = data.game_manager.write().await;
+
+    let arena = game_manager.get_arena(id).ok_or(ArenaError::NoSuchArena(id))?;
+
+    msg.channel_id.say(&ctx, arena.create_scoreboard()).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "List the arenas that have not finished yet."]
+async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let mut game_manager = data.game_manager.write().await;
+
+    let lines: Vec<String> = game_manager
+        .get_arenas()
+        .iter()
+        .filter(|arena| arena.state != ArenaState::Finished)
+        .map(|arena| {
+            let state = match arena.state {
+                ArenaState::Running => format!("{} minutes left", arena.get_remaining_minutes()),
+                _ => String::from("registration open"),
+            };
+
+            format!("#{}: {} ({} games, {} players, {})", arena.id, arena.name, arena.options, arena.players.len(), state)
+        })
+        .collect();
+
+    if lines.is_empty() {
+        msg.channel_id.say(&ctx, "There are no arenas right now.").await?;
+    } else {
+        msg.channel_id.say(&ctx, format!("Arenas:\n{}", lines.join("\n"))).await?;
+    }
+
+    Ok(())
+}
@@ -10,79 +10,111 @@ use serenity::prelude::Context;
 use serenity::Result;
 
 use super::GeneralError;
-use crate::chess::game::GameResult;
+use crate::chess::board::Color;
+use crate::chess::game::{Game as ChessGame, GameResult};
 use crate::chess::moves::NewMove;
 use crate::discord::bot::BotData;
 use crate::http::http_server::UserInfo;
-use crate::system::game::GameAnnouncer;
+use crate::system::archive::{parse_date, ArchiveQuery, ArchivedGame, Outcome};
+use crate::system::challenge::{format_expiry, parse_expiry, Challenge, DEFAULT_CHALLENGE_EXPIRY};
+use crate::system::clock::format_clock_time;
+use crate::system::consultation::{ConsultationError, MoveSelection, TeamAction, DEFAULT_VOTING_WINDOW};
+use crate::system::game::{ColorPreference, GameAnnouncer, GameId, GameLookupError, GameManager, GameOptions, GameSelector, GameWatcher, RematchOutcome, SharedGame, TimeControl};
+use crate::system::handicap::Odds;
+use crate::system::rematch::REMATCH_EMOJI;
+
+use std::time::{Duration, UNIX_EPOCH};
 
 #[derive(Error, Debug)]
 enum CommandError {
-    #[error("You cannot invite yourself")]
-    CannotInviteSelf,
     #[error("Invalid user.")]
     InvalidUser,
-    #[error("You are not in a game.")]
-    NotInGame,
-    #[error("You are already in a game.")]
-    AlreadyInGame,
-    #[error("This user is already in a game.")]
-    UserAlreadyInGame,
-    #[error("You already invited this user!")]
-    AlreadyInvited,
-    #[error("There are no invites from this user.")]
-    NoInvitation,
     #[error("Failed to send a takeback request.")]
     FailedToTakeback,
     #[error("Failed to send a draw request.")]
     FailedToDraw,
+    #[error("Unknown filter '{0}'. Use a mention of the opponent, win, loss or draw, or since: and until: with a date like 2024-01-31.")]
+    InvalidHistoryFilter(String),
+    #[error("Unknown option '{0}'.")]
+    InvalidGameOption(String),
+    #[error("You are not watching this game in this channel.")]
+    NotWatching,
+    #[error("Separate the two teams with 'vs'.")]
+    MissingTeamSeparator,
 }
 
+const HISTORY_LENGTH: usize = 10;
+
 #[group]
 #[prefixes("game")]
 #[description = "Game-related commands."]
-#[commands(invite, accept, decline, draw, resign, make_move, board, takeback)]
+#[commands(invite, accept, decline, team, rematch, draw, resign, claim, make_move, board, heatmap, takeback, history, watch, unwatch)]
 #[only_in(guilds)]
 pub struct GameCommands;
 
 #[command]
-#[description = "Invite someone to a game."]
+#[description = "Invite someone to a game. Private games cannot be watched by others, rated games change both players' ratings. The colour is the one you play, the invite expires after 30 seconds unless another duration is given. You can give odds, time odds set your own clock in live games."]
+#[usage = "@user [white|black|random] [private] [rated|casual] [5+3|3d|unlimited] [standard] [knight-odds|rook-odds|queen-odds|pawn-odds|time-odds:1+0] [30s|10m|2h]"]
 #[min_args(1)]
 async fn invite(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let mention = args.single::<UserId>()?;
-
-    if mention == msg.author.id {
-        return Err(CommandError::CannotInviteSelf.into());
+    let mut options = GameOptions::default();
+    let mut color = ColorPreference::Random;
+    let mut odds = None;
+    let mut expiry = DEFAULT_CHALLENGE_EXPIRY;
+
+    while !args.is_empty() {
+        let option = args.single::<String>()?;
+
+        if let Ok(preference) = option.parse::<ColorPreference>() {
+            color = preference;
+        } else if let Ok(given) = option.parse::<Odds>() {
+            odds = Some(given);
+        } else if let Some(duration) = parse_expiry(&option) {
+            expiry = duration;
+        } else if !options.parse_option(&option) {
+            return Err(CommandError::InvalidGameOption(option).into());
+        }
     }
 
     let user = mention.to_user(&ctx).await.map_err(|_| CommandError::InvalidUser)?;
 
-    let mut data = ctx.data.write().await;
-    let data = data.get_mut::<BotData>().unwrap();
-    let mut game_manager = data.game_manager.write().await;
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
 
-    if game_manager.get_game(msg.author.id).is_some() {
-        return Err(CommandError::AlreadyInGame.into());
-    }
+    let (invitee_rating, inviter_rating) = {
+        let mut game_manager = data.game_manager.write().await;
+        game_manager.create_challenge(Challenge::new(UserInfo::from(&msg.author), user.id, options, color, odds, expiry))?;
 
-    if game_manager.get_game(user.id).is_some() {
-        return Err(CommandError::UserAlreadyInGame.into());
-    }
+        let pool = options.get_rating_pool();
+        let ratings = game_manager.get_ratings();
 
-    if game_manager.get_invite(user.id, msg.author.id).is_some() {
-        return Err(CommandError::AlreadyInvited.into());
-    }
+        (ratings.get_rating(user.id, pool), ratings.get_rating(msg.author.id, pool))
+    };
+
+    let mut colors = match (color, odds.and_then(|odds| odds.get_required_color())) {
+        (_, Some(color)) => format!("playing {:?}", color).to_lowercase(),
+        (ColorPreference::Random, None) => String::from("with random colours"),
+        (color, None) => format!("playing {}", color),
+    };
 
-    game_manager.invite(user.id, msg.author.id);
+    if let Some(odds) = odds {
+        colors.push_str(&format!(" and giving {}", odds));
+    }
 
     msg.channel_id
         .say(
             &ctx,
             format!(
-                "Hey, {mentionedUser} you were invited to a game of chess.\nType {prefix}game accept {author} to accept.\nType {prefix}game decline {author} to decline",
+                "Hey, {mentionedUser} ({invitee_rating}) you were invited to a {options} game of chess by {author} ({inviter_rating}), {colors}.\nType {prefix}game accept {author} to accept within {expiry}.\nType {prefix}game decline {author} to decline",
                 prefix = data.prefix,
                 mentionedUser = user,
-                author = msg.author
+                author = msg.author,
+                options = options,
+                colors = colors,
+                expiry = format_expiry(expiry),
+                invitee_rating = invitee_rating,
+                inviter_rating = inviter_rating,
             ),
         )
         .await?;
@@ -92,47 +124,56 @@ async fn invite(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
 
 #[command]
 #[description = "Accept a game invitation."]
+#[usage = "@user"]
 #[min_args(1)]
 async fn accept(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let mention = args.single::<UserId>()?;
-    let other_user = mention.to_user(&ctx).await?;
 
-    let mut data = ctx.data.write().await;
-    let data = data.get_mut::<BotData>().unwrap();
-    let mut game_manager = data.game_manager.write().await;
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
 
-    if game_manager.get_invite(msg.author.id, mention).is_none() {
-        return Err(CommandError::NoInvitation.into());
-    }
-    game_manager.remove_invite(msg.author.id, mention);
+    let game = data
+        .game_manager
+        .write()
+        .await
+        .accept_challenge(UserInfo::from(&msg.author), mention, Some(GameAnnouncer::new(ctx.http.clone(), msg.channel_id)))?;
+
+    let (image, header) = {
+        let game = game.lock().unwrap();
+        let handicap = match game.handicap {
+            Some(handicap) => format!(" with {}", handicap.odds),
+            None => String::new(),
+        };
 
-    let game = game_manager
-        .create_game(UserInfo::from(&other_user), UserInfo::from(&msg.author), Some(GameAnnouncer::new(ctx.http.clone(), msg.channel_id)))
-        .ok_or(GeneralError::FailedToCreateGame)?;
+        (
+            data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
+            format!(
+                "{} (white) vs {} (black), game #{}{} has started! \nYou can play at {}",
+                game.white_player.id.mention(),
+                game.black_player.id.mention(),
+                game.id,
+                handicap,
+                data.play_url
+            ),
+        )
+    };
 
-    send_board(
-        ctx,
-        msg.channel_id,
-        &data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
-        format!("{}, {}, the game has started! \nYou can play at {}", msg.author.id.mention(), mention.mention(), data.play_url),
-    )
-    .await?;
+    send_board(ctx, msg.channel_id, &image, header).await?;
 
     Ok(())
 }
 
 #[command]
 #[description = "Decline a game invitation."]
+#[usage = "@user"]
 #[min_args(1)]
 async fn decline(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let mention = args.single::<UserId>()?;
 
-    let mut data = ctx.data.write().await;
-    let data = data.get_mut::<BotData>().unwrap();
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
 
-    if !data.game_manager.write().await.remove_invite(msg.author.id, mention) {
-        return Err(CommandError::NoInvitation.into());
-    }
+    data.game_manager.write().await.remove_challenge(msg.author.id, mention)?;
 
     msg.channel_id.say(&ctx, format!("{}, your invitation was declined", mention.mention())).await?;
 
@@ -140,68 +181,211 @@ async fn decline(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult
 }
 
 #[command]
-#[description = "Send a draw request."]
-async fn draw(ctx: &Context, msg: &Message) -> CommandResult {
-    let mut data = ctx.data.write().await;
-    let data = data.get_mut::<BotData>().unwrap();
-    let mut game_manager = data.game_manager.write().await;
+#[description = "Start a casual game between two teams, you have to be in one of them. Team members propose moves, the team plays the most voted move or the one of its captain, the first player listed. The decision is made at the latest a minute after the first proposal unless another duration is given."]
+#[usage = "@white... vs @black... [vote|captain] [30s|10m|2h] [5+3|3d|unlimited] [private] [standard]"]
+#[min_args(3)]
+async fn team(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut white_team = Vec::new();
+    let mut black_team = Vec::new();
+    let mut black = false;
+    let mut options = GameOptions::default();
+    let mut selection = MoveSelection::Vote;
+    let mut window = DEFAULT_VOTING_WINDOW;
+
+    while !args.is_empty() {
+        if let Ok(user) = args.single::<UserId>() {
+            let user = user.to_user(&ctx).await.map_err(|_| CommandError::InvalidUser)?;
+            let team = if black { &mut black_team } else { &mut white_team };
+            team.push(UserInfo::from(&user));
+            continue;
+        }
+
+        let option = args.single::<String>()?;
+
+        if option.eq_ignore_ascii_case("vs") {
+            black = true;
+        } else if let Ok(parsed) = option.parse::<MoveSelection>() {
+            selection = parsed;
+        } else if let Some(duration) = parse_expiry(&option) {
+            window = duration;
+        } else if !options.parse_option(&option) {
+            return Err(CommandError::InvalidGameOption(option).into());
+        }
+    }
+
+    if !black {
+        return Err(CommandError::MissingTeamSeparator.into());
+    }
+
+    if !white_team.iter().chain(black_team.iter()).any(|member| member.id == msg.author.id) {
+        return Err(ConsultationError::NotInTeam.into());
+    }
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+
+    let game = data
+        .game_manager
+        .write()
+        .await
+        .create_team_game(white_team, black_team, selection, window, Some(GameAnnouncer::new(ctx.http.clone(), msg.channel_id)), options)?;
+
+    let (image, header) = {
+        let game = game.lock().unwrap();
+
+        (
+            data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
+            format!(
+                "{} (white) vs {} (black), team game #{} has started! Moves are chosen by {} within {} of the first proposal.\nPropose moves with {}game move or at {}",
+                game.mention_side(Color::White),
+                game.mention_side(Color::Black),
+                game.id,
+                selection,
+                format_expiry(window),
+                data.prefix,
+                data.play_url
+            ),
+        )
+    };
+
+    send_board(ctx, msg.channel_id, &image, header).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Offer a rematch of a game that just ended, with swapped colours and the same settings."]
+#[usage = "[#game|@opponent]"]
+async fn rematch(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let selector = args.single::<GameSelector>().ok();
 
-    let game = game_manager.get_game(msg.author.id).ok_or(CommandError::NotInGame)?;
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
 
-    let author_color = game.get_side_of_player(msg.author.id).unwrap();
-    let other_player = game.get_player_id_by_side(author_color.get_opposite());
+    let reply = {
+        let mut game_manager = data.game_manager.write().await;
 
-    let result = game.chess_game.offer_draw(author_color).map_err(|_| CommandError::FailedToDraw)?;
+        let id = game_manager.find_rematch(msg.author.id, selector)?;
+        let outcome = game_manager.offer_rematch(id, msg.author.id, Some(GameAnnouncer::new(ctx.http.clone(), msg.channel_id)))?;
 
-    match result {
-        GameResult::DrawAgreed => {
-            send_board(
-                ctx,
-                msg.channel_id,
-                &data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
-                format!("{} and {} agreed to a draw.", msg.author.id.mention(), other_player.mention()),
+        make_rematch_reply(data, msg.author.id, outcome)
+    };
+
+    reply.send(ctx, msg.channel_id).await?;
+
+    Ok(())
+}
+
+/// Tells the opponent about a rematch offer, or shows the board of the new game.
+pub fn make_rematch_reply(data: &BotData, player: UserId, outcome: RematchOutcome<'_>) -> Reply {
+    match outcome {
+        RematchOutcome::Offered(rematch) => Reply::Message(format!(
+            "{}, {} offers a rematch of game #{}.\nType {}game rematch or react with {} to the result to accept.",
+            rematch.get_opponent(player).id.mention(),
+            player.mention(),
+            rematch.game,
+            data.prefix,
+            REMATCH_EMOJI
+        )),
+        RematchOutcome::Started(game) => {
+            let game = game.lock().unwrap();
+            let score = game.get_match_score();
+
+            Reply::Board(
+                data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
+                format!(
+                    "{} (white) vs {} (black), rematch game #{} has started! \nMatch score: {}\nYou can play at {}",
+                    game.white_player.id.mention(),
+                    game.black_player.id.mention(),
+                    game.id,
+                    score.describe(&game.white_player, &game.black_player),
+                    data.play_url
+                ),
             )
-            .await?;
-        }
-        _ => {
-            msg.channel_id
-                .say(
-                    &ctx,
-                    format!("{}, {} wants a draw. Type {}game draw to accept", other_player.mention(), msg.author.id.mention(), data.prefix),
-                )
-                .await?;
         }
     }
+}
+
+#[command]
+#[description = "Send a draw request."]
+#[usage = "[#game|@opponent]"]
+async fn draw(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let selector = args.single::<GameSelector>().ok();
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game = data.game_manager.read().await.find_game(msg.author.id, selector)?;
+
+    let reply = {
+        let mut game = game.lock().unwrap();
+        let author_color = game.get_side_of_player(msg.author.id).unwrap();
+        let other_player = game.mention_side(author_color.get_opposite());
+
+        match game.decide_action(msg.author.id, TeamAction::OfferDraw, ChessGame::offer_draw)? {
+            None => make_vote_reply(msg.author.id, game.id, TeamAction::OfferDraw),
+            Some(result) => match result.map_err(|_| CommandError::FailedToDraw)? {
+                GameResult::DrawAgreed => Reply::Board(
+                    data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
+                    format!("{} and {} agreed to a draw.", msg.author.id.mention(), other_player),
+                ),
+                _ => Reply::Message(format!("{}, {} wants a draw. Type {}game draw to accept", other_player, msg.author.id.mention(), data.prefix)),
+            },
+        }
+    };
+
+    reply.send(ctx, msg.channel_id).await?;
 
     Ok(())
 }
 
 #[command]
 #[description = "Resign the game."]
-async fn resign(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
-    let mut data = ctx.data.write().await;
-    let data = data.get_mut::<BotData>().unwrap();
-    let mut game_manager = data.game_manager.write().await;
+#[usage = "[#game|@opponent]"]
+async fn resign(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let selector = args.single::<GameSelector>().ok();
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game = data.game_manager.read().await.find_game(msg.author.id, selector)?;
+
+    let reply = {
+        let mut game = game.lock().unwrap();
+
+        match game.decide_action(msg.author.id, TeamAction::Resign, ChessGame::resign)? {
+            None => make_vote_reply(msg.author.id, game.id, TeamAction::Resign),
+            Some(result) => {
+                result.map_err(|_| GeneralError::FailedToResign)?;
+                Reply::Board(data.visualizer.visualize(&game.chess_game.state.board).unwrap(), format!("{} resigned. ", msg.author.id.mention()))
+            }
+        }
+    };
 
-    let game = game_manager.get_game(msg.author.id).ok_or(CommandError::NotInGame)?;
+    reply.send(ctx, msg.channel_id).await?;
 
-    let author_color = game.get_side_of_player(msg.author.id).unwrap();
+    Ok(())
+}
+
+#[command]
+#[description = "Claim the win when your opponent stopped moving."]
+#[usage = "[#game|@opponent]"]
+async fn claim(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let selector = args.single::<GameSelector>().ok();
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game = data.game_manager.read().await.claim_win(msg.author.id, selector)?;
 
-    game.chess_game.resign(author_color).map_err(|_| GeneralError::FailedToResign)?;
-    send_board(
-        ctx,
-        msg.channel_id,
-        &data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
-        format!("{} resigned. ", msg.author.id.mention()),
-    )
-    .await?;
+    let image = data.visualizer.visualize(&game.lock().unwrap().chess_game.state.board).unwrap();
+
+    send_board(ctx, msg.channel_id, &image, format!("{} claimed the win, their opponent abandoned the game.", msg.author.id.mention())).await?;
 
     Ok(())
 }
 
 #[command]
 #[aliases("move")]
-#[description = "Make a move on the board."]
+#[description = "Make a move on the board, or propose one in a team game."]
+#[usage = "<move> [#game|@opponent]"]
 #[min_args(1)]
 pub async fn make_move(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let m = match args.single::<NewMove>() {
@@ -211,89 +395,215 @@ pub async fn make_move(ctx: &Context, msg: &Message, mut args: Args) -> CommandR
             return Ok(());
         }
     };
+    let selector = args.single::<GameSelector>().ok();
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game = data.game_manager.read().await.find_game(msg.author.id, selector)?;
+
+    // The author is answered directly if the move is not played yet, otherwise the new board is posted
+    let board = {
+        let mut game = game.lock().unwrap();
+
+        if game.get_side_of_player(msg.author.id) != Some(game.chess_game.state.current_turn) {
+            Err(String::from("Not your move."))
+        } else if game.consultation.is_some() && !game.propose_move(msg.author.id, m)? {
+            let consultation = game.consultation.as_ref().unwrap();
+            let proposal = consultation.proposals.last().unwrap();
+
+            Err(format!(
+                "You proposed {} in game #{}, {} of your team voted for it.",
+                proposal.proposed_move,
+                game.id,
+                consultation.get_votes(proposal.proposed_move)
+            ))
+        } else {
+            if game.consultation.is_none() {
+                game.chess_game.make_move(m).map_err(GeneralError::FailedToMove)?;
+            }
 
-    let mut data = ctx.data.write().await;
-    let data = data.get_mut::<BotData>().unwrap();
-    let mut game_manager = data.game_manager.write().await;
+            game.update_clock();
 
-    let game = game_manager.get_game(msg.author.id).ok_or(CommandError::NotInGame)?;
+            let turn = game.chess_game.state.current_turn;
+            let mut header = format!("Your move {}", game.mention_side(turn));
 
-    if game.get_player_id_by_side(game.chess_game.state.current_turn) != msg.author.id {
-        msg.reply(&ctx, "Not your move.").await?;
-        return Ok(());
-    }
+            if let TimeControl::Correspondence { days_per_move } = game.options.time_control {
+                header.push_str(&format!(", you have {} days.", days_per_move));
+            }
 
-    game.chess_game.make_move(m).map_err(GeneralError::FailedToMove)?;
-    send_board(
-        &ctx,
-        msg.channel_id,
-        &data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
-        format!("Your move {}", game.get_player_id_by_side(game.chess_game.state.current_turn).mention()),
-    )
-    .await?;
+            if let Some(clock) = game.clock.filter(|clock| clock.is_running()) {
+                header.push_str(&format!(", you have {} left.", format_clock_time(clock.get_remaining_time(turn))));
+            }
+
+            Ok((data.visualizer.visualize(&game.chess_game.state.board).unwrap(), header))
+        }
+    };
+
+    match board {
+        Ok((image, header)) => send_board(ctx, msg.channel_id, &image, header).await?,
+        Err(answer) => msg.reply(&ctx, answer).await?,
+    };
 
     Ok(())
 }
 
 #[command]
 #[description = "Re-send the current board."]
+#[usage = "[#game|@player]"]
 async fn board(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let mut data = ctx.data.write().await;
-    let data = data.get_mut::<BotData>().unwrap();
-    let mut game_manager = data.game_manager.write().await;
-
-    let user = if args.is_empty() { msg.author.id } else { args.single::<UserId>()? };
-
-    let game = match game_manager.get_game(user) {
-        Some(game) => game,
-        None => {
-            if user == msg.author.id {
-                msg.reply(&ctx, "You are not in a game.").await?;
-            } else {
-                msg.reply(&ctx, "This player is not in a game.").await?;
-            }
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game = find_viewed_game(&*data.game_manager.read().await, msg.author.id, args.single::<GameSelector>().ok())?;
 
-            return Ok(());
-        }
-    };
+    let image = data.visualizer.visualize(&game.lock().unwrap().chess_game.state.board).unwrap();
 
-    send_board(ctx, msg.channel_id, &data.visualizer.visualize(&game.chess_game.state.board).unwrap(), String::from("")).await?;
+    send_board(ctx, msg.channel_id, &image, String::from("")).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Show which side controls each square of the current board."]
+#[usage = "[#game|@player]"]
+#[aliases("control")]
+async fn heatmap(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game = find_viewed_game(&*data.game_manager.read().await, msg.author.id, args.single::<GameSelector>().ok())?;
+
+    let image = data.visualizer.visualize_heatmap(&game.lock().unwrap().chess_game.state.board).unwrap();
+
+    send_board(ctx, msg.channel_id, &image, String::from("Green squares are controlled by white, red squares by black.")).await?;
 
     Ok(())
 }
 
 #[command]
 #[description = "Send a takeback request"]
-async fn takeback(ctx: &Context, msg: &Message) -> CommandResult {
-    let mut data = ctx.data.write().await;
-    let data = data.get_mut::<BotData>().unwrap();
-    let mut game_manager = data.game_manager.write().await;
+#[usage = "[#game|@opponent]"]
+async fn takeback(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let selector = args.single::<GameSelector>().ok();
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game = data.game_manager.read().await.find_game(msg.author.id, selector)?;
+
+    let reply = {
+        let mut game = game.lock().unwrap();
+        let author_color = game.get_side_of_player(msg.author.id).unwrap();
+        let other_player = game.mention_side(author_color.get_opposite());
+
+        match game.decide_action(msg.author.id, TeamAction::OfferTakeback, ChessGame::offer_takeback)? {
+            None => make_vote_reply(msg.author.id, game.id, TeamAction::OfferTakeback),
+            Some(result) => {
+                if result.map_err(|_| CommandError::FailedToTakeback)? {
+                    Reply::Board(
+                        data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
+                        format!("Takeback accepted. Your move {}.", game.mention_side(game.chess_game.state.current_turn)),
+                    )
+                } else {
+                    Reply::Message(format!("{}, {} wants a takeback. Type {}game takeback to accept", other_player, msg.author.id.mention(), data.prefix))
+                }
+            }
+        }
+    };
 
-    let game = game_manager.get_game(msg.author.id).ok_or(CommandError::NotInGame)?;
+    reply.send(ctx, msg.channel_id).await?;
 
-    let author_color = game.get_side_of_player(msg.author.id).unwrap();
-    let other_player = game.get_player_id_by_side(author_color.get_opposite());
+    Ok(())
+}
 
-    let result = game.chess_game.offer_takeback(author_color).map_err(|_| CommandError::FailedToTakeback)?;
+#[command]
+#[description = "Watch a game, the board is posted in this channel after every move."]
+#[usage = "[#game|@player]"]
+async fn watch(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+
+    let game = {
+        let game_manager = data.game_manager.read().await;
+
+        let id = find_viewed_game(&game_manager, msg.author.id, args.single::<GameSelector>().ok())?.lock().unwrap().id;
+        let watcher = GameWatcher::new(UserInfo::from(&msg.author), msg.channel_id, ctx.http.clone(), data.visualizer.clone());
+        game_manager.add_watcher(id, watcher)?
+    };
+
+    let (image, header) = {
+        let game = game.lock().unwrap();
 
-    if result {
-        send_board(
-            ctx,
-            msg.channel_id,
-            &data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
-            format!("Takeback accepted. Your move {}.", game.get_player_id_by_side(game.chess_game.state.current_turn).mention()),
+        (
+            data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
+            format!("You are now watching game #{}, {} spectating.", game.id, game.get_spectators().len()),
         )
-        .await?;
+    };
+
+    send_board(ctx, msg.channel_id, &image, header).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Stop watching a game in this channel."]
+#[usage = "[#game|@player]"]
+async fn unwatch(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+
+    let id = {
+        let game_manager = data.game_manager.read().await;
+        let id = find_viewed_game(&game_manager, msg.author.id, args.single::<GameSelector>().ok())?.lock().unwrap().id;
+
+        if !game_manager.remove_watcher(id, msg.author.id, msg.channel_id) {
+            return Err(CommandError::NotWatching.into());
+        }
+
+        id
+    };
+
+    msg.reply(&ctx, format!("You are no longer watching game #{}.", id)).await?;
+
+    Ok(())
+}
+
+/// Any game can be looked at, a mention selects the game of that player instead of the author's one.
+fn find_viewed_game(game_manager: &GameManager, author: UserId, selector: Option<GameSelector>) -> std::result::Result<SharedGame, GameLookupError> {
+    let game = match selector {
+        Some(GameSelector::Id(id)) => game_manager.get_game(id).ok_or(GameLookupError::NoSuchGame(id)),
+        Some(GameSelector::User(player)) => game_manager.find_game(player, None),
+        None => game_manager.find_game(author, None),
+    }?;
+
+    let (id, watchable) = {
+        let game = game.lock().unwrap();
+        (game.id, game.can_be_watched_by(author))
+    };
+
+    if watchable {
+        Ok(game)
     } else {
-        msg.channel_id
-            .say(
-                &ctx,
-                format!("{}, {} wants a takeback. Type {}game takeback to accept", other_player.mention(), msg.author.id.mention(), data.prefix),
-            )
-            .await?;
+        Err(GameLookupError::PrivateGame(id))
     }
+}
 
-    Ok(())
+/// An answer built while the game manager or a game is locked, it is only sent once they were released.
+pub enum Reply {
+    Message(String),
+    /// A board with its header.
+    Board(Vec<u8>, String),
+}
+
+impl Reply {
+    pub async fn send(self, ctx: &Context, channel: ChannelId) -> Result<Message> {
+        match self {
+            Reply::Message(content) => channel.say(ctx, content).await,
+            Reply::Board(image, header) => send_board(ctx, channel, &image, header).await,
+        }
+    }
+}
+
+/// Answer to a team member whose vote did not decide for the team yet.
+fn make_vote_reply(player: UserId, game: GameId, action: TeamAction) -> Reply {
+    Reply::Message(format!("{}, you voted to {} in game #{}, your team has not decided yet.", player.mention(), action, game))
 }
 
 pub async fn send_board(ctx: &Context, channel: ChannelId, vec: &[u8], header: String) -> Result<Message> {
@@ -307,3 +617,69 @@ pub async fn send_board(ctx: &Context, channel: ChannelId, vec: &[u8], header: S
         })
         .await
 }
+
+#[command]
+#[description = "List the past games of a player. Optionally filtered by opponent, result and the days the games ended on."]
+#[usage = "[@player] [@opponent] [win|loss|draw] [since:YYYY-MM-DD] [until:YYYY-MM-DD]"]
+async fn history(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut players = Vec::new();
+    let mut query = ArchiveQuery::new(msg.author.id);
+
+    while !args.is_empty() {
+        if let Ok(user) = args.single::<UserId>() {
+            players.push(user);
+            continue;
+        }
+
+        let filter = args.single::<String>()?;
+        let invalid = || CommandError::InvalidHistoryFilter(filter.clone());
+
+        if let Some(date) = filter.strip_prefix("since:") {
+            query.since = Some(parse_date(date).ok_or_else(invalid)?);
+        } else if let Some(date) = filter.strip_prefix("until:") {
+            // The whole day is included
+            query.until = Some(parse_date(date).ok_or_else(invalid)? + Duration::from_secs(24 * 60 * 60 - 1));
+        } else {
+            query.outcome = Some(filter.parse::<Outcome>().map_err(|_| invalid())?);
+        }
+    }
+
+    query.player = players.first().copied().unwrap_or(msg.author.id);
+    query.opponent = players.get(1).copied();
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+
+    let message = {
+        let game_manager = data.game_manager.read().await;
+        let games: Vec<&ArchivedGame> = game_manager.get_archive().query(&query).into_iter().filter(|game| game.can_be_viewed_by(Some(msg.author.id))).collect();
+
+        if games.is_empty() {
+            None
+        } else {
+            let mut message = format!("Showing {} of {} games of {}:\n", games.len().min(HISTORY_LENGTH), games.len(), query.player.mention());
+
+            for game in games.iter().take(HISTORY_LENGTH) {
+                message.push_str(&format!(
+                    "`#{}` {} vs {}: **{}** {} {} moves, <t:{}:d>\n",
+                    game.id,
+                    game.white_player.username,
+                    game.black_player.username,
+                    game.result.get_score(),
+                    game.result.pretty_message().trim(),
+                    game.get_moves().len().div_ceil(2),
+                    game.end_time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+                ));
+            }
+
+            Some(message)
+        }
+    };
+
+    match message {
+        Some(message) => msg.channel_id.say(&ctx, message).await?,
+        None => msg.reply(&ctx, "No games found.").await?,
+    };
+
+    Ok(())
+}
@@ -1,15 +1,19 @@
 use crate::chess::moves::MoveFailureReason;
 
 pub mod admin;
+pub mod arena;
 pub mod game;
+pub mod rating;
+pub mod seek;
+pub mod simul;
+pub mod stats;
+pub mod tournament;
 pub mod util;
 
 #[derive(Error, Debug)]
 pub enum GeneralError {
-    #[error("Failed to create a game, maybe you're already in one?")]
+    #[error("Failed to create a game, a player cannot play against themselves.")]
     FailedToCreateGame,
-    #[error("This player is not in a game.")]
-    PlayerNotInGame,
     #[error("Failed to move: {0}")]
     FailedToMove(MoveFailureReason),
     #[error("Failed to resign.")]
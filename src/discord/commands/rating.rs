@@ -0,0 +1,41 @@
+use serenity::framework::standard::{
+    macros::{command, group},
+    Args, CommandResult,
+};
+use serenity::model::channel::Message;
+use serenity::model::id::UserId;
+use serenity::prelude::Context;
+
+use crate::discord::bot::BotData;
+use crate::system::rating::Rating;
+
+#[group]
+#[description = "Rating commands."]
+#[commands(rating)]
+pub struct Ratings;
+
+#[command]
+#[description = "Show the ratings of a player in every pool they have played rated games in."]
+#[usage = "[@user]"]
+async fn rating(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user = if args.is_empty() { msg.author.clone() } else { args.single::<UserId>()?.to_user(&ctx).await? };
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game_manager = data.game_manager.read().await;
+
+    let mut message = format!("Ratings of {}:\n", user.name);
+
+    match game_manager.get_ratings().get_player(user.id) {
+        Some(ratings) if !ratings.ratings.is_empty() => {
+            for rating in ratings.ratings.iter() {
+                message.push_str(&format!("{}: {} ± {:.0} ({} games)\n", rating.pool, rating.rating, rating.rating.deviation, rating.rating.games));
+            }
+        }
+        _ => message.push_str(&format!("No rated games yet, every pool starts at {}.", Rating::default())),
+    }
+
+    msg.channel_id.say(&ctx, message).await?;
+
+    Ok(())
+}
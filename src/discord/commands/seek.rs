@@ -0,0 +1,166 @@
+use serenity::framework::standard::{
+    macros::{command, group},
+    Args, CommandResult,
+};
+use serenity::model::channel::Message;
+use serenity::model::misc::Mentionable;
+use serenity::prelude::Context;
+
+use crate::discord::bot::BotData;
+use crate::discord::commands::game::send_board;
+use crate::http::http_server::UserInfo;
+use crate::system::game::{ColorPreference, GameAnnouncer, GameOptions, SeekOutcome, SharedGame};
+use crate::system::matchmaking::{RatingRange, Seek, SeekId};
+
+#[derive(Error, Debug)]
+enum SeekCommandError {
+    #[error("Unknown option '{0}'.")]
+    InvalidSeekOption(String),
+    #[error("You have no open seeks.")]
+    NoSeeks,
+}
+
+#[group]
+#[prefixes("seek")]
+#[description = "Matchmaking commands, a seek is paired with the first compatible one."]
+#[commands(post, list, join, cancel)]
+#[default_command(post)]
+#[only_in(guilds)]
+pub struct Matchmaking;
+
+#[command]
+#[description = "Look for an opponent. Rating ranges look like 1200-1800."]
+#[usage = "[5+3|3d|unlimited] [rated|casual] [standard] [white|black|random] [min-max]"]
+async fn post(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut options = GameOptions::default();
+    let mut color = ColorPreference::Random;
+    let mut rating_range = RatingRange::default();
+
+    while !args.is_empty() {
+        let option = args.single::<String>()?;
+
+        if let Ok(preference) = option.parse::<ColorPreference>() {
+            color = preference;
+        } else if let Ok(range) = option.parse::<RatingRange>() {
+            rating_range = range;
+        } else if option.to_lowercase() == "private" || !options.parse_option(&option) {
+            return Err(SeekCommandError::InvalidSeekOption(option).into());
+        }
+    }
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+
+    let seek = Seek::new(UserInfo::from(&msg.author), options, color, rating_range, Some(GameAnnouncer::new(ctx.http.clone(), msg.channel_id)));
+    let outcome = data.game_manager.write().await.post_seek(seek);
+
+    match outcome {
+        SeekOutcome::Posted(id) => {
+            msg.channel_id
+                .say(
+                    &ctx,
+                    format!("{} is looking for a {} game, seek #{}.\nType {}seek join {} to play.", msg.author, options, id, data.prefix, id),
+                )
+                .await?;
+        }
+        SeekOutcome::Paired(game) => {
+            let (image, header) = describe_start(data, &game);
+            send_board(ctx, msg.channel_id, &image, header).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[command]
+#[description = "List the open seeks."]
+async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let mut game_manager = data.game_manager.write().await;
+
+    let seeks = game_manager.get_seeks();
+
+    if seeks.is_empty() {
+        msg.channel_id.say(&ctx, "There are no open seeks.").await?;
+        return Ok(());
+    }
+
+    let mut message = String::from("Open seeks:\n");
+
+    for seek in seeks.iter() {
+        message.push_str(&format!("#{}: {} ({}), {} game, {}", seek.id, seek.user.username, seek.rating, seek.options, seek.color));
+
+        if !seek.rating_range.is_any() {
+            message.push_str(&format!(", opponents rated {}", seek.rating_range));
+        }
+
+        message.push('\n');
+    }
+
+    msg.channel_id.say(&ctx, message).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Take an open seek."]
+#[usage = "<seek>"]
+#[min_args(1)]
+async fn join(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = args.single::<SeekId>()?;
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+
+    let game = data
+        .game_manager
+        .write()
+        .await
+        .accept_seek(id, UserInfo::from(&msg.author), Some(GameAnnouncer::new(ctx.http.clone(), msg.channel_id)))?;
+
+    let (image, header) = describe_start(data, &game);
+    send_board(ctx, msg.channel_id, &image, header).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Cancel one of your seeks, or all of them."]
+#[usage = "[seek]"]
+async fn cancel(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = if args.is_empty() { None } else { Some(args.single::<SeekId>()?) };
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let mut game_manager = data.game_manager.write().await;
+
+    match id {
+        Some(id) => game_manager.cancel_seek(id, msg.author.id)?,
+        None => {
+            if game_manager.cancel_seeks_of(msg.author.id) == 0 {
+                return Err(SeekCommandError::NoSeeks.into());
+            }
+        }
+    }
+
+    msg.channel_id.say(&ctx, "Your seek was cancelled.").await?;
+
+    Ok(())
+}
+
+/// The board and header announcing a game paired from a seek.
+fn describe_start(data: &BotData, game: &SharedGame) -> (Vec<u8>, String) {
+    let game = game.lock().unwrap();
+
+    (
+        data.visualizer.visualize(&game.chess_game.state.board).unwrap(),
+        format!(
+            "{} (white) vs {} (black), game #{} has started! \nYou can play at {}",
+            game.white_player.id.mention(),
+            game.black_player.id.mention(),
+            game.id,
+            data.play_url
+        ),
+    )
+}
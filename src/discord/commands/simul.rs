@@ -0,0 +1,202 @@
+use serenity::framework::standard::{
+    macros::{command, group},
+    Args, CommandResult,
+};
+use serenity::model::channel::Message;
+use serenity::prelude::Context;
+
+use crate::discord::bot::BotData;
+use crate::http::http_server::UserInfo;
+use crate::system::game::{ColorPreference, GameAnnouncer, GameOptions};
+use crate::system::simul::{SimulError, SimulId, SimulState};
+
+#[group]
+#[prefixes("simul")]
+#[description = "Simultaneous exhibitions, one host plays a game against every opponent at once."]
+#[commands(create, join, leave, start, show, list)]
+#[only_in(guilds)]
+pub struct Simuls;
+
+#[command]
+#[description = "Host a simul. The colour is the one you play on every board, random alternates it. Everything after the game options is its name."]
+#[usage = "[white|black|random] [5+3|3d|unlimited] [rated|casual] [standard] [name]"]
+async fn create(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut options = GameOptions::default();
+    let mut color = ColorPreference::White;
+
+    while let Ok(option) = args.parse::<String>() {
+        if let Ok(preference) = option.parse::<ColorPreference>() {
+            color = preference;
+        } else if option.to_lowercase() == "private" || !options.parse_option(&option) {
+            break;
+        }
+
+        args.advance();
+    }
+
+    let name = match args.remains() {
+        Some(name) => name.to_string(),
+        None => format!("{}'s simul", msg.author.name),
+    };
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let mut game_manager = data.game_manager.write().await;
+
+    let simul = game_manager.create_simul(name, UserInfo::from(&msg.author), color, options, Some(GameAnnouncer::new(ctx.http.clone(), msg.channel_id)));
+
+    let colors = match simul.host_color {
+        ColorPreference::Random => String::from("alternating colours"),
+        color => format!("playing {}", color),
+    };
+
+    msg.channel_id
+        .say(
+            &ctx,
+            format!(
+                "{} is hosting simul #{} ({}) with {} games, {}.\nType {prefix}simul join {id} to join.",
+                msg.author,
+                simul.id,
+                simul.name,
+                simul.options,
+                colors,
+                prefix = data.prefix,
+                id = simul.id
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Join a simul before it starts."]
+#[usage = "<simul>"]
+#[min_args(1)]
+async fn join(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = args.single::<SimulId>()?;
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let mut game_manager = data.game_manager.write().await;
+
+    let simul = game_manager.join_simul(id, UserInfo::from(&msg.author))?;
+
+    msg.channel_id
+        .say(&ctx, format!("{} joined simul #{}, {} opponents are registered.", msg.author, simul.id, simul.boards.len()))
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Leave a simul before it starts."]
+#[usage = "<simul>"]
+#[min_args(1)]
+async fn leave(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = args.single::<SimulId>()?;
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let mut game_manager = data.game_manager.write().await;
+
+    let simul = game_manager.leave_simul(id, msg.author.id)?;
+
+    msg.channel_id.say(&ctx, format!("{} left simul #{}.", msg.author, simul.id)).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Start your simul, the boards are posted in the channel it was created in."]
+#[usage = "<simul>"]
+#[min_args(1)]
+async fn start(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = args.single::<SimulId>()?;
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let mut game_manager = data.game_manager.write().await;
+
+    let simul = game_manager.start_simul(id, msg.author.id)?;
+
+    msg.reply(&ctx, format!("Simul #{} has started on {} boards, you can play at {}", simul.id, simul.boards.len(), data.play_url))
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Show the boards of a simul and whose turn it is on each of them."]
+#[usage = "<simul>"]
+#[min_args(1)]
+async fn show(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = args.single::<SimulId>()?;
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game_manager = data.game_manager.read().await;
+
+    let (simul, games) = game_manager.get_simul_games(id).ok_or(SimulError::NoSuchSimul(id))?;
+    let mut message = format!("Simul #{} ({}) hosted by {}, {} games:\n", simul.id, simul.name, simul.host.username, simul.options);
+
+    for (index, (board, game)) in simul.boards.iter().zip(games.iter()).enumerate() {
+        let game = game.as_ref().map(|game| game.lock().unwrap());
+        let status = match (board.result, game) {
+            (Some(result), _) => String::from(result.get_score()),
+            (None, Some(game)) if game.chess_game.state.current_turn == board.host_color => format!("game #{}, host to move", game.id),
+            (None, Some(game)) => format!("game #{}, {} to move", game.id, board.opponent.username),
+            (None, None) => String::from("not started"),
+        };
+
+        message.push_str(&format!("Board {}: {} as {:?}, {}\n", index + 1, board.opponent.username, board.host_color.get_opposite(), status));
+    }
+
+    if simul.boards.is_empty() {
+        message.push_str("Nobody joined yet.");
+    } else if simul.state != SimulState::Registration {
+        message.push_str(&format!("Host score: {}/{}", simul.get_host_score(), simul.boards.len()));
+    }
+
+    msg.channel_id.say(&ctx, message).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "List the simuls that have not finished yet."]
+async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game_manager = data.game_manager.read().await;
+
+    let lines: Vec<String> = game_manager
+        .get_simuls()
+        .iter()
+        .filter(|simul| simul.state != SimulState::Finished)
+        .map(|simul| {
+            let state = match simul.state {
+                SimulState::Running => "running",
+                _ => "registration open",
+            };
+
+            format!(
+                "#{}: {} hosted by {} ({} games, {} opponents, {})",
+                simul.id,
+                simul.name,
+                simul.host.username,
+                simul.options,
+                simul.boards.len(),
+                state
+            )
+        })
+        .collect();
+
+    if lines.is_empty() {
+        msg.channel_id.say(&ctx, "There are no simuls right now.").await?;
+    } else {
+        msg.channel_id.say(&ctx, format!("Simuls:\n{}", lines.join("\n"))).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use serenity::framework::standard::{
+    macros::{command, group},
+    Args, CommandResult,
+};
+use serenity::model::channel::Message;
+use serenity::model::id::UserId;
+use serenity::prelude::Context;
+
+use crate::discord::bot::BotData;
+use crate::system::game::{TimeCategory, Variant};
+use crate::system::rating::RatingPool;
+use crate::system::stats::{get_activity_leaderboard, PlayerStats, Score};
+
+#[derive(Error, Debug)]
+enum StatsCommandError {
+    #[error("Unknown leaderboard '{0}'. Use activity or a time category.")]
+    InvalidLeaderboard(String),
+}
+
+const LEADERBOARD_LENGTH: usize = 10;
+
+#[group]
+#[description = "Statistics commands."]
+#[commands(stats, leaderboard)]
+pub struct Statistics;
+
+fn format_score(score: &Score) -> String {
+    format!("+{} -{} ={}", score.wins, score.losses, score.draws)
+}
+
+#[command]
+#[description = "Show statistics about the concluded games of a player."]
+#[usage = "[@user]"]
+async fn stats(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user = if args.is_empty() { msg.author.clone() } else { args.single::<UserId>()?.to_user(&ctx).await? };
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game_manager = data.game_manager.read().await;
+
+    let stats = PlayerStats::compute(user.id, game_manager.get_archive());
+
+    if stats.total.get_games() == 0 {
+        msg.channel_id.say(&ctx, format!("{} has not finished any games yet.", user.name)).await?;
+        return Ok(());
+    }
+
+    let mut message = format!(
+        "Statistics of {}:\nTotal: {} ({} games)\nAs white: {}\nAs black: {}\n",
+        user.name,
+        format_score(&stats.total),
+        stats.total.get_games(),
+        format_score(&stats.as_white),
+        format_score(&stats.as_black)
+    );
+
+    message.push_str("Endings: ");
+    message.push_str(
+        &stats
+            .terminations
+            .iter()
+            .map(|termination| format!("{} {}", termination.termination.replace('_', " "), termination.score.get_games()))
+            .collect::<Vec<String>>()
+            .join(", "),
+    );
+    message.push('\n');
+
+    if !stats.favourite_openings.is_empty() {
+        message.push_str("Favourite openings:\n");
+
+        for opening in stats.favourite_openings.iter() {
+            message.push_str(&format!("  {}: {}\n", opening.moves, format_score(&opening.score)));
+        }
+    }
+
+    message.push_str(&format!(
+        "Longest win streak: {}, longest losing streak: {}\nAverage game: {:.0} moves, {} minutes",
+        stats.longest_win_streak,
+        stats.longest_loss_streak,
+        stats.average_plies / 2.0,
+        stats.average_duration / 60
+    ));
+
+    msg.channel_id.say(&ctx, message).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Show the highest rated players of a time category, or the most active players."]
+#[usage = "[activity|bullet|blitz|rapid|classical|correspondence|unlimited] [standard]"]
+async fn leaderboard(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut pool = RatingPool::default();
+    let mut activity = false;
+
+    while !args.is_empty() {
+        let option = args.single::<String>()?;
+
+        if option.to_lowercase() == "activity" {
+            activity = true;
+        } else if let Ok(category) = option.parse::<TimeCategory>() {
+            pool.category = category;
+        } else if let Ok(variant) = option.parse::<Variant>() {
+            pool.variant = variant;
+        } else {
+            return Err(StatsCommandError::InvalidLeaderboard(option).into());
+        }
+    }
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game_manager = data.game_manager.read().await;
+
+    let lines: Vec<String> = if activity {
+        get_activity_leaderboard(game_manager.get_archive())
+            .iter()
+            .take(LEADERBOARD_LENGTH)
+            .enumerate()
+            .map(|(index, ranking)| format!("{}. {}: {} games", index + 1, ranking.player.username, ranking.games))
+            .collect()
+    } else {
+        game_manager
+            .get_rating_leaderboard(pool)
+            .iter()
+            .take(LEADERBOARD_LENGTH)
+            .enumerate()
+            .map(|(index, ranking)| format!("{}. {}: {}", index + 1, ranking.player.username, ranking.rating))
+            .collect()
+    };
+
+    let title = if activity {
+        String::from("Most active players")
+    } else {
+        format!("Highest rated {} players", pool)
+    };
+
+    let message = if lines.is_empty() {
+        format!("{}: nobody yet.", title)
+    } else {
+        format!("{}:\n{}", title, lines.join("\n"))
+    };
+
+    msg.channel_id.say(&ctx, message).await?;
+
+    Ok(())
+}
@@ -0,0 +1,232 @@
+use serenity::framework::standard::{
+    macros::{command, group},
+    Args, CommandResult,
+};
+use serenity::model::channel::Message;
+use serenity::prelude::Context;
+
+use crate::discord::bot::BotData;
+use crate::http::http_server::UserInfo;
+use crate::system::game::{GameAnnouncer, GameOptions};
+use crate::system::tournament::{Tournament, TournamentError, TournamentFormat, TournamentId, TournamentState};
+
+#[derive(Error, Debug)]
+enum TournamentCommandError {
+    #[error("Unknown format '{0}'. Use roundrobin or swiss.")]
+    InvalidFormat(String),
+}
+
+const DEFAULT_SWISS_ROUNDS: u32 = 5;
+
+#[group]
+#[prefixes("tournament")]
+#[description = "Round-robin and Swiss tournaments."]
+#[commands(create, join, leave, start, standings, list)]
+#[only_in(guilds)]
+pub struct Tournaments;
+
+#[command]
+#[description = "Create a tournament, everything after the game options is its name."]
+#[usage = "<roundrobin|swiss> [rounds] [5+3|3d|unlimited] [rated|casual] [standard] [name]"]
+#[min_args(1)]
+async fn create(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let format = args.single::<String>()?;
+    let format = match format.to_lowercase().as_str() {
+        "roundrobin" | "round-robin" | "rr" => TournamentFormat::RoundRobin,
+        "swiss" => TournamentFormat::Swiss {
+            rounds: args.single::<u32>().unwrap_or(DEFAULT_SWISS_ROUNDS),
+        },
+        _ => return Err(TournamentCommandError::InvalidFormat(format).into()),
+    };
+
+    let mut options = GameOptions::default();
+
+    while let Ok(option) = args.parse::<String>() {
+        if option.to_lowercase() == "private" || !options.parse_option(&option) {
+            break;
+        }
+
+        args.advance();
+    }
+
+    let name = match args.remains() {
+        Some(name) => name.to_string(),
+        None => format!("{}'s tournament", msg.author.name),
+    };
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let mut game_manager = data.game_manager.write().await;
+
+    let tournament = game_manager.create_tournament(name, msg.author.id, format, options, Some(GameAnnouncer::new(ctx.http.clone(), msg.channel_id)));
+
+    msg.channel_id
+        .say(
+            &ctx,
+            format!(
+                "{} created the {} tournament #{} ({}) with {} games.\nType {prefix}tournament join {id} to join.",
+                msg.author,
+                tournament.format,
+                tournament.id,
+                tournament.name,
+                tournament.options,
+                prefix = data.prefix,
+                id = tournament.id
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Join a tournament before it starts."]
+#[usage = "<tournament>"]
+#[min_args(1)]
+async fn join(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = args.single::<TournamentId>()?;
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let mut game_manager = data.game_manager.write().await;
+
+    let tournament = game_manager.join_tournament(id, UserInfo::from(&msg.author))?;
+
+    msg.channel_id
+        .say(
+            &ctx,
+            format!("{} joined tournament #{}, {} players are registered.", msg.author, tournament.id, tournament.players.len()),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Leave a tournament before it starts."]
+#[usage = "<tournament>"]
+#[min_args(1)]
+async fn leave(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = args.single::<TournamentId>()?;
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let mut game_manager = data.game_manager.write().await;
+
+    let tournament = game_manager.leave_tournament(id, msg.author.id)?;
+
+    msg.channel_id.say(&ctx, format!("{} left tournament #{}.", msg.author, tournament.id)).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Start your tournament, the pairings of each round are posted in the channel it was created in."]
+#[usage = "<tournament>"]
+#[min_args(1)]
+async fn start(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = args.single::<TournamentId>()?;
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let mut game_manager = data.game_manager.write().await;
+
+    let tournament = game_manager.start_tournament(id, msg.author.id)?;
+
+    msg.channel_id
+        .say(
+            &ctx,
+            format!(
+                "Tournament #{} has started with {} players, {} rounds will be played.",
+                tournament.id,
+                tournament.players.len(),
+                tournament.get_total_rounds()
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+fn format_standings(tournament: &Tournament) -> String {
+    let mut message = format!("Tournament #{} ({}), {}", tournament.id, tournament.name, tournament.format);
+
+    match tournament.state {
+        TournamentState::Registration => {
+            message.push_str(", registration is open.\nPlayers: ");
+            message.push_str(&tournament.players.iter().map(|player| player.user.username.clone()).collect::<Vec<String>>().join(", "));
+            return message;
+        }
+        TournamentState::Running => message.push_str(&format!(", round {} of {}.\n", tournament.rounds.len(), tournament.get_total_rounds())),
+        TournamentState::Finished => message.push_str(", finished.\n"),
+    }
+
+    if tournament.state == TournamentState::Running {
+        let name = |id| tournament.get_player(id).map(|player| player.user.username.clone()).unwrap_or_default();
+
+        for (board, pairing) in tournament.get_current_round().unwrap_or_default().iter().enumerate() {
+            let result = pairing.result.map(|result| result.get_score()).unwrap_or("*");
+
+            match pairing.black {
+                Some(black) => message.push_str(&format!("Board {}: {} - {} {}\n", board + 1, name(pairing.white), name(black), result)),
+                None => message.push_str(&format!("{} has a bye.\n", name(pairing.white))),
+            }
+        }
+    }
+
+    message.push_str("Standings:\n");
+
+    for (place, standing) in tournament.get_standings().iter().enumerate() {
+        message.push_str(&format!(
+            "{}. {}: {} (Buchholz {}, SB {})\n",
+            place + 1,
+            standing.player.username,
+            standing.points,
+            standing.buchholz,
+            standing.sonneborn_berger
+        ));
+    }
+
+    message
+}
+
+#[command]
+#[description = "Show the current pairings and the standings of a tournament."]
+#[usage = "<tournament>"]
+#[min_args(1)]
+async fn standings(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = args.single::<TournamentId>()?;
+
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game_manager = data.game_manager.read().await;
+
+    let tournament = game_manager.get_tournament(id).ok_or(TournamentError::NoSuchTournament(id))?;
+
+    msg.channel_id.say(&ctx, format_standings(tournament)).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "List the tournaments that have not finished yet."]
+async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+    let data = ctx.data.read().await;
+    let data = data.get::<BotData>().unwrap();
+    let game_manager = data.game_manager.read().await;
+
+    let lines: Vec<String> = game_manager
+        .get_tournaments()
+        .iter()
+        .filter(|tournament| tournament.state != TournamentState::Finished)
+        .map(|tournament| format!("#{}: {} ({}, {} players)", tournament.id, tournament.name, tournament.format, tournament.players.len()))
+        .collect();
+
+    if lines.is_empty() {
+        msg.channel_id.say(&ctx, "There are no tournaments right now.").await?;
+    } else {
+        msg.channel_id.say(&ctx, format!("Tournaments:\n{}", lines.join("\n"))).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,302 @@
+use actix::{Actor, ActorContext, ActorFuture, AsyncContext, Running, StreamHandler, WrapFuture};
+use actix_web_actors::ws;
+use actix_web_actors::ws::{CloseCode, CloseReason};
+use serde::Serialize;
+use serde_json::Value;
+use serenity::model::id::UserId;
+use tokio::sync::RwLock;
+
+use crate::chess::analysis::{AnalysisBoard, AnalysisNode, NodeId};
+use crate::chess::board::Color;
+use crate::chess::game::GameResult;
+use crate::chess::moves::NewMove;
+use crate::http::http_server::UserInfo;
+use crate::system::game::{GameId, GameLookupError, GameManager};
+
+use super::proto::{make_pieces_info, make_rejected, OutgoingPacket, PieceInfo, ProcessingError};
+use super::web_socket::{CLIENT_TIMEOUT, HEARTBEAT_INTERVAL};
+
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+#[derive(Serialize)]
+pub struct AnalysisState {
+    pub current: NodeId,
+    pub ply: u32,
+    pub current_turn: Color,
+    pub result: Option<GameResult>,
+    pub winner: Option<Color>,
+    pub pieces: Vec<PieceInfo>,
+    pub highlighted_squares: Vec<String>,
+    pub mainline: Vec<NodeId>,
+    pub nodes: Vec<AnalysisNodeInfo>,
+}
+
+#[derive(Serialize)]
+pub struct AnalysisNodeInfo {
+    pub id: NodeId,
+    pub parent: Option<NodeId>,
+    pub children: Vec<NodeId>,
+    pub ply: u32,
+    #[serde(rename = "move")]
+    pub last_move: Option<String>,
+    pub comment: String,
+    pub nags: Vec<u8>,
+}
+
+impl From<&AnalysisNode> for AnalysisNodeInfo {
+    fn from(node: &AnalysisNode) -> Self {
+        AnalysisNodeInfo {
+            id: node.id,
+            parent: node.parent,
+            children: node.children.clone(),
+            ply: node.ply,
+            last_move: node.last_move.filter(|_| node.parent.is_some()).map(|m| m.to_string()),
+            comment: node.comment.clone(),
+            nags: node.nags.clone(),
+        }
+    }
+}
+
+pub fn make_analysis_state(analysis: &AnalysisBoard) -> String {
+    let node = analysis.get_current_node();
+    let board = &node.state.board;
+
+    let mut nodes: Vec<AnalysisNodeInfo> = analysis.get_nodes().map(AnalysisNodeInfo::from).collect();
+    nodes.sort_by_key(|node| node.id);
+
+    let state = AnalysisState {
+        current: node.id,
+        ply: node.ply,
+        current_turn: node.state.current_turn,
+        result: node.result,
+        winner: node.result.and_then(|result| result.get_winner()),
+        pieces: make_pieces_info(board, if node.result.is_none() { Some(node.state.current_turn) } else { None }),
+        highlighted_squares: board.highlighted_squares.iter().map(|square| square.to_string()).collect(),
+        mainline: analysis.get_mainline(),
+        nodes,
+    };
+
+    OutgoingPacket::Analysis(state).to_json()
+}
+
+/// A websocket session driving a private analysis board, it never affects any real game.
+pub struct AnalysisWebSocketSession {
+    pub game_manager: Arc<RwLock<GameManager>>,
+    pub info: Option<UserInfo>,
+    pub heartbeat: Instant,
+    pub analysis: AnalysisBoard,
+}
+
+impl AnalysisWebSocketSession {
+    pub fn new(info: Option<UserInfo>, game_manager: Arc<RwLock<GameManager>>) -> Self {
+        Self {
+            game_manager,
+            info,
+            heartbeat: Instant::now(),
+            analysis: AnalysisBoard::new(),
+        }
+    }
+
+    pub fn do_heartbeat(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            if Instant::now().duration_since(act.heartbeat) > CLIENT_TIMEOUT {
+                ctx.stop();
+                return;
+            }
+
+            ctx.ping(b"");
+        });
+    }
+
+    /// Loads the given concluded game, or by default the user's last one.
+    /// The session processes nothing else until the game is loaded, so the following packets apply to it.
+    fn load_game(&mut self, game_id: Option<GameId>, ctx: &mut ws::WebsocketContext<Self>) {
+        let user = self.info.as_ref().unwrap().id;
+        let game_manager = self.game_manager.clone();
+
+        let future = async move {
+            let game_manager = game_manager.read().await;
+            AnalysisWebSocketSession::find_game(&game_manager, user, game_id)
+        };
+
+        ctx.wait(future.into_actor(self).map(|result, act, ctx| match result {
+            Ok(analysis) => {
+                act.analysis = analysis;
+                ctx.text(make_analysis_state(&act.analysis));
+            }
+            Err(error) => ctx.text(make_rejected("load_game", error)),
+        }));
+    }
+
+    /// Running games cannot be loaded, the board must not help a player during a game.
+    fn find_game(game_manager: &GameManager, user: UserId, game_id: Option<GameId>) -> Result<AnalysisBoard, String> {
+        let archived = match game_id {
+            Some(id) if game_manager.get_game(id).is_some() => return Err(format!("Game #{} is still being played.", id)),
+            Some(id) => {
+                let archived = game_manager.get_archive().get(id).ok_or_else(|| format!("There is no game #{}.", id))?;
+
+                if !archived.can_be_viewed_by(Some(user)) {
+                    return Err(GameLookupError::PrivateGame(id).to_string());
+                }
+
+                archived
+            }
+            None => game_manager.get_archive().get_latest_for(user).ok_or_else(|| String::from("You have no finished game to analyse."))?,
+        };
+
+        Ok(AnalysisBoard::from_game(&archived.to_chess_game().map_err(|e| e.to_string())?))
+    }
+
+    pub fn handle_packet(&mut self, value: &Value) -> Result<Option<String>, ProcessingError> {
+        let packet_type = value.get("type").and_then(|v| v.as_str()).ok_or(ProcessingError::InvalidProtocol)?;
+
+        let result = match packet_type {
+            "get_state" => Ok(()),
+            "new" => {
+                self.analysis = AnalysisBoard::new();
+                Ok(())
+            }
+            "make_move" => {
+                let new_move = value
+                    .get("move")
+                    .and_then(|v| v.as_str())
+                    .and_then(|v| NewMove::from_str(v).ok())
+                    .ok_or(ProcessingError::InvalidProtocol)?;
+
+                self.analysis.make_move(new_move).map(|_| ()).map_err(|e| e.to_string())
+            }
+            "next" => self.analysis.go_to_next().map_err(|e| e.to_string()),
+            "previous" => self.analysis.go_to_previous().map_err(|e| e.to_string()),
+            "start" => {
+                self.analysis.go_to_start();
+                Ok(())
+            }
+            "end" => {
+                self.analysis.go_to_end();
+                Ok(())
+            }
+            "jump_to_ply" => {
+                let ply = value.get("ply").and_then(|v| v.as_u64()).ok_or(ProcessingError::InvalidProtocol)?;
+                self.analysis.jump_to_ply(ply as u32).map_err(|e| e.to_string())
+            }
+            "go_to" => {
+                let node = value.get("node").and_then(|v| v.as_u64()).ok_or(ProcessingError::InvalidProtocol)?;
+                self.analysis.go_to(node as NodeId).map_err(|e| e.to_string())
+            }
+            "promote_variation" => self.analysis.promote_variation().map_err(|e| e.to_string()),
+            "delete_variation" => self.analysis.delete_variation().map_err(|e| e.to_string()),
+            "set_comment" => {
+                let comment = value.get("comment").and_then(|v| v.as_str()).ok_or(ProcessingError::InvalidProtocol)?;
+                self.analysis.set_comment(String::from(comment));
+                Ok(())
+            }
+            "add_nag" | "remove_nag" => {
+                let nag = value.get("nag").and_then(|v| v.as_u64()).filter(|&nag| nag <= 255).ok_or(ProcessingError::InvalidProtocol)? as u8;
+
+                if packet_type == "add_nag" {
+                    self.analysis.add_nag(nag);
+                } else {
+                    self.analysis.remove_nag(nag);
+                }
+
+                Ok(())
+            }
+            _ => return Err(ProcessingError::InvalidProtocol),
+        };
+
+        match result {
+            Ok(()) => Ok(Some(make_analysis_state(&self.analysis))),
+            Err(error) => Ok(Some(make_rejected(packet_type, error))),
+        }
+    }
+
+    fn do_handle_packet(&mut self, text: String, ctx: &mut <AnalysisWebSocketSession as Actor>::Context) {
+        let value: Value = match serde_json::from_str(&text) {
+            Ok(value) => value,
+            Err(_) => {
+                ctx.close(Some(CloseReason::from(CloseCode::Unsupported)));
+                return;
+            }
+        };
+
+        // Loading a game needs the game manager, it is the only packet not answered right away
+        if value.get("type").and_then(|v| v.as_str()) == Some("load_game") {
+            self.load_game(value.get("game_id").and_then(|v| v.as_u64()), ctx);
+            return;
+        }
+
+        match self.handle_packet(&value) {
+            Ok(str) => {
+                if let Some(str) = str {
+                    ctx.text(str);
+                }
+            }
+            Err(e) => match e {
+                ProcessingError::InvalidProtocol => {
+                    ctx.close(Some(CloseReason::from(CloseCode::Unsupported)));
+                }
+                ProcessingError::OldState => {}
+                ProcessingError::NoOutput => {}
+            },
+        }
+    }
+}
+
+impl Actor for AnalysisWebSocketSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.do_heartbeat(ctx);
+
+        if self.info.is_none() {
+            ctx.close(Some(CloseReason::from(CloseCode::from(4000))));
+        }
+    }
+
+    fn stopping(&mut self, _: &mut Self::Context) -> Running {
+        Running::Stop
+    }
+}
+
+/// WebSocket message handler
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for AnalysisWebSocketSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        let msg = match msg {
+            Err(_) => {
+                ctx.stop();
+                return;
+            }
+            Ok(msg) => msg,
+        };
+
+        match msg {
+            ws::Message::Ping(msg) => {
+                self.heartbeat = Instant::now();
+                ctx.pong(&msg);
+            }
+            ws::Message::Pong(_) => {
+                self.heartbeat = Instant::now();
+            }
+            ws::Message::Text(text) => {
+                if self.info.is_none() {
+                    ctx.close(Some(CloseReason::from(CloseCode::from(4000))));
+                    return;
+                }
+
+                self.do_handle_packet(text, ctx);
+            }
+            ws::Message::Binary(_) => {
+                ctx.close(Some(CloseReason::from(CloseCode::Unsupported)));
+            }
+            ws::Message::Close(_) => {
+                ctx.stop();
+            }
+            ws::Message::Continuation(_) => {
+                ctx.stop();
+            }
+            ws::Message::Nop => (),
+        }
+    }
+}
@@ -6,6 +6,7 @@ use std::collections::HashMap;
 use rand::distributions::Alphanumeric;
 use rand::Rng;
 
+#[derive(Default)]
 pub struct AuthenticationManager {
     map: HashMap<String, UserInfo>,
 }
@@ -46,9 +47,3 @@ impl AuthenticationManager {
         }
     }
 }
-
-impl Default for AuthenticationManager {
-    fn default() -> Self {
-        Self { map: HashMap::new() }
-    }
-}
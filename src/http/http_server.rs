@@ -3,7 +3,7 @@ use std::sync::Arc;
 use actix_cors::Cors;
 use actix_session::{CookieSession, Session};
 use actix_web::cookie::SameSite;
-use actix_web::{get, http::header, web, App, HttpRequest, HttpResponse, HttpServer};
+use actix_web::{get, http::header, post, web, App, HttpRequest, HttpResponse, HttpServer};
 use actix_web_actors::ws;
 use oauth2::basic::BasicClient;
 use oauth2::http::{self, HeaderMap, Method};
@@ -15,21 +15,33 @@ use serde::{Deserialize, Serialize};
 use serenity::model::id::UserId;
 use tokio::sync::RwLock;
 
+use super::analysis_socket::AnalysisWebSocketSession;
 use super::auth_manager::AuthenticationManager;
-use super::web_socket::WebSocketSession;
+use super::proto::ChallengeInfo;
+use super::web_socket::{SocketRegistry, WebSocketSession};
+use crate::chess::board::Color;
+use crate::chess::game::GameResult;
 use crate::config::{HttpConfig, OAuth2Config};
-use crate::system::game::GameManager;
+use crate::system::archive::{ArchiveQuery, ArchivedGame, Outcome};
+use crate::system::challenge::{Challenge, DEFAULT_CHALLENGE_EXPIRY};
+use crate::system::game::{ColorPreference, GameId, GameManager, GameOptions, TimeControl, Variant};
+use crate::system::handicap::Odds;
+use crate::system::stats::PlayerStats;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub struct AppState {
     pub oauth2_client: BasicClient,
     pub auth_url: Url,
     pub frontend_url: String,
     pub game_manager: Arc<RwLock<GameManager>>,
+    pub sockets: SocketRegistry,
     pub auth_manager: Arc<RwLock<AuthenticationManager>>,
 }
 
 pub async fn start_server(http_config: HttpConfig, oauth2_config: OAuth2Config, game_manager: Arc<RwLock<GameManager>>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let auth_manager = Arc::new(RwLock::new(AuthenticationManager::new()));
+    let sockets = game_manager.read().await.get_sockets();
     let frontend_address = http_config.frontend_address.clone();
 
     HttpServer::new(move || {
@@ -49,12 +61,13 @@ pub async fn start_server(http_config: HttpConfig, oauth2_config: OAuth2Config,
                 auth_url,
                 frontend_url: frontend_address.clone(),
                 game_manager: game_manager.clone(),
+                sockets: sockets.clone(),
                 auth_manager: auth_manager.clone(),
             })
             .wrap(
                 Cors::new()
                     .allowed_origin(&frontend_address)
-                    .allowed_methods(vec!["GET"])
+                    .allowed_methods(vec!["GET", "POST"])
                     .allowed_headers(vec![header::AUTHORIZATION, header::ACCEPT])
                     .allowed_header(header::CONTENT_TYPE)
                     .supports_credentials()
@@ -68,6 +81,14 @@ pub async fn start_server(http_config: HttpConfig, oauth2_config: OAuth2Config,
             .service(info)
             .service(get_token)
             .service(socket)
+            .service(analysis_socket)
+            .service(game_history)
+            .service(player_stats)
+            .service(game_pgn)
+            .service(challenges)
+            .service(create_challenge)
+            .service(accept_challenge)
+            .service(decline_challenge)
     })
     .bind(http_config.address.clone())?
     .run()
@@ -184,8 +205,236 @@ async fn socket(query: web::Query<WebSocketQuery>, req: HttpRequest, stream: web
     let auth_manager = data.auth_manager.read().await;
 
     ws::start(
-        WebSocketSession::new(auth_manager.get_for_token(query.token.clone()).ok().cloned(), data.game_manager.clone()),
+        WebSocketSession::new(auth_manager.get_for_token(query.token.clone()).ok().cloned(), data.game_manager.clone(), data.sockets.clone()),
+        &req,
+        stream,
+    )
+}
+
+#[get("/analysis_socket")]
+async fn analysis_socket(query: web::Query<WebSocketQuery>, req: HttpRequest, stream: web::Payload, data: web::Data<AppState>) -> Result<HttpResponse, actix_web::error::Error> {
+    let auth_manager = data.auth_manager.read().await;
+
+    ws::start(
+        AnalysisWebSocketSession::new(auth_manager.get_for_token(query.token.clone()).ok().cloned(), data.game_manager.clone()),
         &req,
         stream,
     )
 }
+
+#[derive(Deserialize)]
+pub struct GameHistoryQuery {
+    opponent: Option<u64>,
+    since: Option<u64>,
+    until: Option<u64>,
+    result: Option<Outcome>,
+}
+
+#[derive(Serialize)]
+pub struct ArchivedGameInfo {
+    pub id: GameId,
+    pub white_player: UserInfo,
+    pub black_player: UserInfo,
+    pub result: GameResult,
+    pub winner: Option<Color>,
+    pub termination: String,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub moves: Vec<String>,
+}
+
+impl From<&ArchivedGame> for ArchivedGameInfo {
+    fn from(game: &ArchivedGame) -> Self {
+        ArchivedGameInfo {
+            id: game.id,
+            white_player: game.white_player.clone(),
+            black_player: game.black_player.clone(),
+            result: game.result,
+            winner: game.result.get_winner(),
+            termination: game.result.pretty_message().trim().to_string(),
+            start_time: to_timestamp(game.start_time),
+            end_time: to_timestamp(game.end_time),
+            moves: game.get_moves().iter().map(|m| m.to_string()).collect(),
+        }
+    }
+}
+
+fn to_timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Concluded games of a user, most recent first. Times are unix timestamps in seconds.
+/// Private games are left out unless the logged in user played in them.
+#[get("/users/{id}/games")]
+async fn game_history(session: Session, path: web::Path<u64>, query: web::Query<GameHistoryQuery>, data: web::Data<AppState>) -> HttpResponse {
+    let user = session.get::<UserInfo>("user").unwrap().map(|user| user.id);
+    let mut archive_query = ArchiveQuery::new(UserId(path.into_inner()));
+    archive_query.opponent = query.opponent.map(UserId);
+    archive_query.since = query.since.map(|since| UNIX_EPOCH + Duration::from_secs(since));
+    archive_query.until = query.until.map(|until| UNIX_EPOCH + Duration::from_secs(until));
+    archive_query.outcome = query.result;
+
+    let game_manager = data.game_manager.read().await;
+    let games: Vec<ArchivedGameInfo> = game_manager
+        .get_archive()
+        .query(&archive_query)
+        .into_iter()
+        .filter(|game| game.can_be_viewed_by(user))
+        .map(ArchivedGameInfo::from)
+        .collect();
+
+    HttpResponse::Ok().json(games)
+}
+
+/// Statistics about the concluded games of a user.
+#[get("/users/{id}/stats")]
+async fn player_stats(path: web::Path<u64>, data: web::Data<AppState>) -> HttpResponse {
+    let game_manager = data.game_manager.read().await;
+
+    HttpResponse::Ok().json(PlayerStats::compute(UserId(path.into_inner()), game_manager.get_archive()))
+}
+
+/// The game in Portable Game Notation, ongoing games are exported up to the current position.
+#[get("/games/{id}/pgn")]
+async fn game_pgn(session: Session, path: web::Path<GameId>, data: web::Data<AppState>) -> HttpResponse {
+    let id = path.into_inner();
+    let user = session.get::<UserInfo>("user").unwrap().map(|user| user.id);
+
+    let game_manager = data.game_manager.read().await;
+    let game = match game_manager.get_game(id) {
+        Some(game) => ArchivedGame::from_game(&game.lock().unwrap()),
+        None => match game_manager.get_archive().get(id) {
+            Some(game) => game.clone(),
+            None => return HttpResponse::NotFound().finish(),
+        },
+    };
+
+    if !game.can_be_viewed_by(user) {
+        return HttpResponse::Forbidden().finish();
+    }
+
+    match game.to_pgn() {
+        Ok(pgn) => HttpResponse::Ok().content_type("application/x-chess-pgn").body(pgn),
+        Err(why) => HttpResponse::InternalServerError().body(why.to_string()),
+    }
+}
+
+#[derive(Serialize)]
+pub struct ApiError {
+    error: String,
+}
+
+impl ApiError {
+    fn response(error: impl ToString) -> HttpResponse {
+        HttpResponse::BadRequest().json(ApiError { error: error.to_string() })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ChallengeRequest {
+    invitee: u64,
+    #[serde(default)]
+    color: ColorPreference,
+    #[serde(default)]
+    rated: bool,
+    #[serde(default)]
+    private: bool,
+    /// Written like on Discord, `5+3`, `3d` or `unlimited`.
+    time_control: Option<String>,
+    #[serde(default)]
+    variant: Variant,
+    /// Odds given by the inviter, written like on Discord, `knight-odds` or `time-odds:1+0`.
+    odds: Option<String>,
+    /// Seconds until the challenge expires.
+    expiry: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct ChallengeAccepted {
+    game: GameId,
+}
+
+/// Open challenges the logged in user sent or received.
+#[get("/challenges")]
+async fn challenges(session: Session, data: web::Data<AppState>) -> HttpResponse {
+    let user_info = match session.get::<UserInfo>("user").unwrap() {
+        Some(user_info) => user_info,
+        None => return HttpResponse::Unauthorized().finish(),
+    };
+
+    let mut game_manager = data.game_manager.write().await;
+    let challenges: Vec<ChallengeInfo> = game_manager.get_challenges_of(user_info.id).into_iter().map(ChallengeInfo::from).collect();
+
+    HttpResponse::Ok().json(challenges)
+}
+
+#[post("/challenges")]
+async fn create_challenge(session: Session, request: web::Json<ChallengeRequest>, data: web::Data<AppState>) -> HttpResponse {
+    let user_info = match session.get::<UserInfo>("user").unwrap() {
+        Some(user_info) => user_info,
+        None => return HttpResponse::Unauthorized().finish(),
+    };
+
+    let time_control = match &request.time_control {
+        Some(time_control) => match time_control.parse::<TimeControl>() {
+            Ok(time_control) => time_control,
+            Err(_) => return ApiError::response(format!("Invalid time control '{}'.", time_control)),
+        },
+        None => TimeControl::default(),
+    };
+
+    let options = GameOptions {
+        private: request.private,
+        rated: request.rated,
+        time_control,
+        variant: request.variant,
+    };
+
+    let odds = match &request.odds {
+        Some(odds) => match odds.parse::<Odds>() {
+            Ok(odds) => Some(odds),
+            Err(_) => return ApiError::response(format!("Invalid odds '{}'.", odds)),
+        },
+        None => None,
+    };
+
+    let expiry = request.expiry.map(Duration::from_secs).unwrap_or(DEFAULT_CHALLENGE_EXPIRY);
+    let challenge = Challenge::new(user_info, UserId(request.invitee), options, request.color, odds, expiry);
+
+    let mut game_manager = data.game_manager.write().await;
+
+    match game_manager.create_challenge(challenge) {
+        Ok(challenge) => HttpResponse::Ok().json(ChallengeInfo::from(challenge)),
+        Err(why) => ApiError::response(why),
+    }
+}
+
+#[post("/challenges/{inviter}/accept")]
+async fn accept_challenge(session: Session, path: web::Path<u64>, data: web::Data<AppState>) -> HttpResponse {
+    let user_info = match session.get::<UserInfo>("user").unwrap() {
+        Some(user_info) => user_info,
+        None => return HttpResponse::Unauthorized().finish(),
+    };
+
+    let mut game_manager = data.game_manager.write().await;
+
+    match game_manager.accept_challenge(user_info, UserId(path.into_inner()), None) {
+        Ok(game) => HttpResponse::Ok().json(ChallengeAccepted { game: game.lock().unwrap().id }),
+        Err(why) => ApiError::response(why),
+    }
+}
+
+#[post("/challenges/{inviter}/decline")]
+async fn decline_challenge(session: Session, path: web::Path<u64>, data: web::Data<AppState>) -> HttpResponse {
+    let user_info = match session.get::<UserInfo>("user").unwrap() {
+        Some(user_info) => user_info,
+        None => return HttpResponse::Unauthorized().finish(),
+    };
+
+    let mut game_manager = data.game_manager.write().await;
+
+    match game_manager.remove_challenge(user_info.id, UserId(path.into_inner())) {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(why) => ApiError::response(why),
+    }
+}
@@ -1,3 +1,4 @@
+pub mod analysis_socket;
 pub mod auth_manager;
 pub mod http_server;
 pub mod proto;
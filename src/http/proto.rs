@@ -1,27 +1,58 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use serenity::async_trait;
-use tokio::sync::RwLockWriteGuard;
+use tokio::sync::{RwLockReadGuard, RwLockWriteGuard};
 
-use crate::chess::board::Color;
+use crate::chess::board::{Board, Color};
 use crate::chess::pieces::Type;
 use crate::http::http_server::UserInfo;
-use crate::system::game::{Game, GameManager};
+use crate::system::challenge::Challenge;
+use crate::system::clock::Clock;
+use crate::system::consultation::{ConsultationError, MoveSelection, TeamAction};
+use crate::system::game::{ColorPreference, Game, GameId, GameManager, GameOptions, GameSelector, RematchOutcome, SharedGame, TimeCategory, TimeControl, Variant};
+use crate::system::handicap::{Handicap, Odds};
+use crate::system::matchmaking::{RatingRange, Seek, SeekId};
+use crate::system::rating::Rating;
+use crate::system::rematch::{MatchScore, Rematch, REMATCH_WINDOW};
+use crate::system::simul::{Simul, SimulId, SimulState};
+use crate::system::tournament::{Standing, Tournament, TournamentFormat, TournamentGame, TournamentId, TournamentState};
 
 use crate::chess::game::{Game as ChessGame, GameResult};
-use crate::chess::moves::NewMove;
+use crate::chess::moves::{MoveFailureReason, NewMove};
+use crate::chess::pgn::write_fen;
 use ProcessingError::*;
 
+use super::analysis_socket::AnalysisState;
+
 use std::str::FromStr;
+use std::time::UNIX_EPOCH;
+
+/// Who a game state is made for, the players of a side see their legal moves and their team's proposals.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Audience {
+    Player(Color),
+    Spectator,
+}
+
+impl Audience {
+    pub fn of(game: &Game, user: &UserInfo) -> Self {
+        match game.get_side_of_player(user.id) {
+            Some(side) => Audience::Player(side),
+            None => Audience::Spectator,
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct State {
     pub user: UserInfo,
     pub game: Option<GameState>,
+    pub games: Vec<GameId>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct GameState {
+    pub id: GameId,
     pub white: PublicUserInfo,
     pub black: PublicUserInfo,
     pub current_turn: Color,
@@ -31,6 +62,39 @@ pub struct GameState {
     pub highlighted_squares: Vec<String>,
     pub draw_offers: Vec<String>,
     pub takeback_offers: Vec<String>,
+    pub spectators: Vec<PublicUserInfo>,
+    pub rated: bool,
+    pub time_control: TimeControl,
+    pub category: TimeCategory,
+    pub variant: Variant,
+    pub tournament: Option<TournamentGame>,
+    /// Unix time in seconds the side to move has to move by in correspondence games.
+    pub move_deadline: Option<u64>,
+    pub clock: Option<ClockInfo>,
+    /// Score of the series of rematches before this game.
+    pub match_score: Option<MatchScoreInfo>,
+    /// Whether the waiting player may claim the win because the side to move stopped playing.
+    pub claimable: bool,
+    pub handicap: Option<Handicap>,
+    pub teams: Option<TeamsInfo>,
+}
+
+/// The teams of a team game, the first member of each is its captain.
+#[derive(Serialize, Deserialize)]
+pub struct TeamsInfo {
+    pub white: Vec<PublicUserInfo>,
+    pub black: Vec<PublicUserInfo>,
+    pub selection: MoveSelection,
+    /// Only sent to the members of the side to move.
+    pub proposals: Vec<ProposalInfo>,
+    /// Unix time in seconds the side to move has to agree on a move by, once one was proposed.
+    pub decision_deadline: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ProposalInfo {
+    pub player: String,
+    pub proposed_move: String,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -42,12 +106,282 @@ pub struct PieceInfo {
     pub valid_moves: Vec<String>,
 }
 
+/// Everything sent to a session, its type tells the client what it is.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutgoingPacket {
+    State(Box<State>),
+    Lobby(Lobby),
+    Rematch(RematchInfo),
+    Tournament(TournamentInfo),
+    Simul(SimulInfo),
+    Analysis(AnalysisState),
+    /// A request of the session could not be carried out.
+    Rejected {
+        /// Type of the rejected packet.
+        request: String,
+        error: String,
+        /// Only set for rejected moves.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<MoveFailureReason>,
+    },
+}
+
+impl OutgoingPacket {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+#[derive(Serialize)]
+pub struct Lobby {
+    pub seeks: Vec<SeekInfo>,
+}
+
+#[derive(Serialize)]
+pub struct SeekInfo {
+    pub id: SeekId,
+    pub user: PublicUserInfo,
+    pub rated: bool,
+    pub time_control: String,
+    pub category: TimeCategory,
+    pub variant: Variant,
+    pub color: ColorPreference,
+    pub rating_range: RatingRange,
+}
+
+impl From<&Seek> for SeekInfo {
+    fn from(seek: &Seek) -> Self {
+        SeekInfo {
+            id: seek.id,
+            user: PublicUserInfo::with_rating(&seek.user, &seek.rating),
+            rated: seek.options.rated,
+            time_control: seek.options.time_control.to_string(),
+            category: seek.options.time_control.get_category(),
+            variant: seek.options.variant,
+            color: seek.color,
+            rating_range: seek.rating_range,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MatchScoreInfo {
+    pub white: f64,
+    pub black: f64,
+    pub games: u32,
+}
+
+impl MatchScoreInfo {
+    fn new(score: &MatchScore, white: &UserInfo, black: &UserInfo) -> Self {
+        MatchScoreInfo {
+            white: score.get_points(white.id),
+            black: score.get_points(black.id),
+            games: score.games,
+        }
+    }
+}
+
+/// Time both sides of a live game had left when the state was sent.
+#[derive(Serialize, Deserialize)]
+pub struct ClockInfo {
+    /// In milliseconds.
+    pub white: u64,
+    pub black: u64,
+    /// Whether the clock of the side to move is running.
+    pub running: bool,
+}
+
+impl ClockInfo {
+    fn new(clock: &Clock) -> Self {
+        ClockInfo {
+            white: clock.get_remaining_time(Color::White).as_millis() as u64,
+            black: clock.get_remaining_time(Color::Black).as_millis() as u64,
+            running: clock.is_running(),
+        }
+    }
+}
+
+/// Sent to both players of a concluded game while a rematch is offered.
+#[derive(Serialize)]
+pub struct RematchInfo {
+    pub game: GameId,
+    pub white: PublicUserInfo,
+    pub black: PublicUserInfo,
+    pub offered_by: Vec<String>,
+    /// Score including the concluded game.
+    pub score: MatchScoreInfo,
+    /// Unix time in seconds.
+    pub expires_at: u64,
+}
+
+impl From<&Rematch> for RematchInfo {
+    fn from(rematch: &Rematch) -> Self {
+        RematchInfo {
+            game: rematch.game,
+            white: PublicUserInfo::from(&rematch.white_player),
+            black: PublicUserInfo::from(&rematch.black_player),
+            offered_by: rematch.offers.iter().map(|id| id.to_string()).collect(),
+            score: MatchScoreInfo::new(&rematch.score, &rematch.white_player, &rematch.black_player),
+            expires_at: (rematch.end_time + REMATCH_WINDOW).duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ChallengeInfo {
+    pub inviter: PublicUserInfo,
+    pub invitee: String,
+    pub rated: bool,
+    pub private: bool,
+    pub time_control: String,
+    pub category: TimeCategory,
+    pub variant: Variant,
+    /// Colour the inviter plays.
+    pub color: ColorPreference,
+    /// Odds given by the inviter.
+    pub odds: Option<Odds>,
+    /// Unix time in seconds.
+    pub expires_at: u64,
+}
+
+impl From<&Challenge> for ChallengeInfo {
+    fn from(challenge: &Challenge) -> Self {
+        ChallengeInfo {
+            inviter: PublicUserInfo::from(&challenge.inviter),
+            invitee: challenge.invitee.to_string(),
+            rated: challenge.options.rated,
+            private: challenge.options.private,
+            time_control: challenge.options.time_control.to_string(),
+            category: challenge.options.time_control.get_category(),
+            variant: challenge.options.variant,
+            color: challenge.color,
+            odds: challenge.odds,
+            expires_at: challenge.get_expiry_time().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TournamentInfo {
+    pub id: TournamentId,
+    pub name: String,
+    pub format: TournamentFormat,
+    pub state: TournamentState,
+    pub round: usize,
+    pub total_rounds: usize,
+    pub players: Vec<PublicUserInfo>,
+    pub pairings: Vec<PairingInfo>,
+    pub standings: Vec<Standing>,
+}
+
+#[derive(Serialize)]
+pub struct PairingInfo {
+    pub white: PublicUserInfo,
+    pub black: Option<PublicUserInfo>,
+    pub game: Option<GameId>,
+    pub result: Option<GameResult>,
+}
+
+impl From<&Tournament> for TournamentInfo {
+    fn from(tournament: &Tournament) -> Self {
+        let user = |id| PublicUserInfo::from(&tournament.get_player(id).unwrap().user);
+
+        TournamentInfo {
+            id: tournament.id,
+            name: tournament.name.clone(),
+            format: tournament.format,
+            state: tournament.state,
+            round: tournament.rounds.len(),
+            total_rounds: if tournament.state == TournamentState::Registration { 0 } else { tournament.get_total_rounds() },
+            players: tournament.players.iter().map(|player| PublicUserInfo::with_rating(&player.user, &player.rating)).collect(),
+            pairings: tournament
+                .get_current_round()
+                .unwrap_or_default()
+                .iter()
+                .map(|pairing| PairingInfo {
+                    white: user(pairing.white),
+                    black: pairing.black.map(user),
+                    game: pairing.game,
+                    result: pairing.result,
+                })
+                .collect(),
+            standings: tournament.get_standings(),
+        }
+    }
+}
+
+/// Overview of every board of a simul.
+#[derive(Serialize)]
+pub struct SimulInfo {
+    pub id: SimulId,
+    pub name: String,
+    pub host: PublicUserInfo,
+    pub state: SimulState,
+    pub time_control: String,
+    pub host_score: f64,
+    pub boards: Vec<SimulBoardInfo>,
+}
+
+#[derive(Serialize)]
+pub struct SimulBoardInfo {
+    pub opponent: PublicUserInfo,
+    pub host_color: Color,
+    pub game: Option<GameId>,
+    /// Side to move while the game is running.
+    pub current_turn: Option<Color>,
+    pub result: Option<GameResult>,
+    /// Position in Forsyth-Edwards Notation while the game is running.
+    pub position: Option<String>,
+}
+
+impl SimulInfo {
+    fn new(simul: &Simul, games: &[Option<SharedGame>]) -> Self {
+        SimulInfo {
+            id: simul.id,
+            name: simul.name.clone(),
+            host: PublicUserInfo::from(&simul.host),
+            state: simul.state,
+            time_control: simul.options.time_control.to_string(),
+            host_score: simul.get_host_score(),
+            boards: simul
+                .boards
+                .iter()
+                .zip(games.iter())
+                .map(|(board, game)| {
+                    let game = game.as_ref().map(|game| game.lock().unwrap());
+
+                    SimulBoardInfo {
+                        opponent: PublicUserInfo::from(&board.opponent),
+                        host_color: board.host_color,
+                        game: board.game,
+                        current_turn: game.as_ref().map(|game| game.chess_game.state.current_turn),
+                        result: board.result,
+                        position: game.as_ref().map(|game| write_fen(&game.chess_game.state, game.chess_game.get_ply() / 2 + 1)),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct PublicUserInfo {
     pub id: String,
     pub username: String,
     pub discriminator: String,
     pub avatar: Option<String>,
+    /// Rating in the pool of the game, only set for players.
+    pub rating: Option<RatingInfo>,
+}
+
+impl PublicUserInfo {
+    pub fn with_rating(user: &UserInfo, rating: &Rating) -> Self {
+        PublicUserInfo {
+            rating: Some(RatingInfo::from(rating)),
+            ..PublicUserInfo::from(user)
+        }
+    }
 }
 
 impl From<&UserInfo> for PublicUserInfo {
@@ -57,6 +391,24 @@ impl From<&UserInfo> for PublicUserInfo {
             discriminator: user.discriminator.clone(),
             username: user.username.clone(),
             avatar: user.avatar.clone(),
+            rating: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RatingInfo {
+    pub rating: i32,
+    pub deviation: i32,
+    pub provisional: bool,
+}
+
+impl From<&Rating> for RatingInfo {
+    fn from(rating: &Rating) -> Self {
+        RatingInfo {
+            rating: rating.rating.round() as i32,
+            deviation: rating.deviation.round() as i32,
+            provisional: rating.is_provisional(),
         }
     }
 }
@@ -67,46 +419,86 @@ pub enum ProcessingError {
     OldState,
 }
 
-fn make_game_state(current_player: &UserInfo, game: &Game) -> GameState {
-    let turn = game.chess_game.state.current_turn;
-    let our_turn = game.get_player_id_by_side(turn) == current_player.id;
+pub fn make_pieces_info(board: &Board, show_moves_for: Option<Color>) -> Vec<PieceInfo> {
+    board
+        .state
+        .pieces
+        .values()
+        .map(|piece| {
+            let show_moves = show_moves_for == Some(piece.color);
 
-    GameState {
-        white: PublicUserInfo::from(&game.white_player),
-        black: PublicUserInfo::from(&game.black_player),
-        current_turn: turn,
-        pieces: game
-            .chess_game
-            .state
-            .board
-            .state
-            .pieces
+            PieceInfo {
+                piece_type: piece.piece_type,
+                color: piece.color,
+                position: piece.location.to_string(),
+                possible_valid_moves: if show_moves {
+                    piece.valid_moves.iter().map(|square| square.to_string()).collect()
+                } else {
+                    Vec::new()
+                },
+                valid_moves: if show_moves {
+                    board.get_valid_moves_for_piece(piece).iter().map(|m| m.to.to_string()).collect()
+                } else {
+                    Vec::new()
+                },
+            }
+        })
+        .collect()
+}
+
+fn make_teams_info(audience: Audience, game: &Game) -> Option<TeamsInfo> {
+    let consultation = game.consultation.as_ref()?;
+    let ply = game.chess_game.get_ply();
+
+    // Proposals stay hidden from the opponents and spectators
+    let proposals = if audience == Audience::Player(game.chess_game.state.current_turn) {
+        consultation.get_proposals(ply)
+    } else {
+        &[]
+    };
+
+    Some(TeamsInfo {
+        white: consultation.white_team.iter().map(PublicUserInfo::from).collect(),
+        black: consultation.black_team.iter().map(PublicUserInfo::from).collect(),
+        selection: consultation.selection,
+        proposals: proposals
             .iter()
-            .map(|(_, piece)| {
-                let show_moves = our_turn && piece.color == turn;
-
-                PieceInfo {
-                    piece_type: piece.piece_type,
-                    color: piece.color,
-                    position: piece.location.to_string(),
-                    possible_valid_moves: if show_moves {
-                        piece.valid_moves.iter().map(|square| square.to_string()).collect()
-                    } else {
-                        Vec::new()
-                    },
-                    valid_moves: if show_moves {
-                        game.chess_game.state.board.get_valid_moves_for_piece(&piece).iter().map(|m| m.to.to_string()).collect()
-                    } else {
-                        Vec::new()
-                    },
-                }
+            .map(|proposal| ProposalInfo {
+                player: proposal.player.to_string(),
+                proposed_move: proposal.proposed_move.to_string(),
             })
             .collect(),
+        decision_deadline: consultation.get_window_timestamp(ply),
+    })
+}
+
+fn make_game_state(audience: Audience, game: &Game) -> GameState {
+    let turn = game.chess_game.state.current_turn;
+    let our_turn = audience == Audience::Player(turn);
+
+    GameState {
+        id: game.id,
+        white: PublicUserInfo::with_rating(&game.white_player, &game.white_rating),
+        black: PublicUserInfo::with_rating(&game.black_player, &game.black_rating),
+        current_turn: turn,
+        pieces: make_pieces_info(&game.chess_game.state.board, if our_turn { Some(turn) } else { None }),
         result: game.chess_game.result,
         winner: game.chess_game.result.and_then(|result| result.get_winner()),
         highlighted_squares: game.chess_game.state.board.highlighted_squares.iter().map(|square| square.to_string()).collect(),
         draw_offers: map_colors_to_ids(game, &game.chess_game.state.draw_offers),
         takeback_offers: map_colors_to_ids(game, &game.chess_game.state.takeback_offers),
+        spectators: game.get_spectators().into_iter().map(PublicUserInfo::from).collect(),
+        rated: game.options.rated,
+        time_control: game.options.time_control,
+        category: game.options.time_control.get_category(),
+        variant: game.options.variant,
+        tournament: game.tournament,
+        move_deadline: game.deadline.map(|deadline| deadline.get_timestamp()),
+        clock: game.clock.as_ref().map(ClockInfo::new),
+        match_score: game.score.map(|score| MatchScoreInfo::new(&score, &game.white_player, &game.black_player)),
+        claimable: game.activity.claimable,
+        handicap: game.handicap,
+        teams: make_teams_info(audience, game),
     }
 }
 
@@ -116,6 +508,98 @@ pub trait Handler {
 
     async fn get_game_manager(&mut self) -> RwLockWriteGuard<GameManager>;
 
+    /// Shared access to the game manager, enough for anything that only changes single games.
+    async fn read_game_manager(&mut self) -> RwLockReadGuard<GameManager>;
+
+    /// Starts receiving updates of a game without playing in it, returns false if the session already does.
+    fn watch_game(&mut self, id: GameId) -> bool;
+
+    /// Returns false if the session did not watch the game.
+    fn unwatch_game(&mut self, id: GameId) -> bool;
+
+    fn set_in_lobby(&mut self, in_lobby: bool);
+
+    async fn handle_lobby(&mut self, user: &UserInfo, packet_type: &str, value: &Value) -> Result<Option<String>, ProcessingError> {
+        let mut game_manager = self.get_game_manager().await;
+
+        let result = match packet_type {
+            "join_lobby" => return Ok(Some(make_lobby(game_manager.get_seeks()))),
+            "create_seek" => {
+                let mut options = GameOptions {
+                    rated: value.get("rated").and_then(|v| v.as_bool()).unwrap_or(false),
+                    ..GameOptions::default()
+                };
+
+                if let Some(time_control) = value.get("time_control") {
+                    options.time_control = time_control.as_str().and_then(|v| v.parse().ok()).ok_or(InvalidProtocol)?;
+                }
+
+                if let Some(variant) = value.get("variant") {
+                    options.variant = variant.as_str().and_then(|v| v.parse().ok()).ok_or(InvalidProtocol)?;
+                }
+
+                let color = match value.get("color") {
+                    Some(color) => color.as_str().and_then(|v| v.parse().ok()).ok_or(InvalidProtocol)?,
+                    None => ColorPreference::Random,
+                };
+
+                let rating_range = RatingRange {
+                    min: value.get("min_rating").and_then(|v| v.as_u64()).map(|v| v as u32),
+                    max: value.get("max_rating").and_then(|v| v.as_u64()).map(|v| v as u32),
+                };
+
+                game_manager.post_seek(Seek::new(user.clone(), options, color, rating_range, None));
+                Ok(())
+            }
+            "accept_seek" => {
+                let id = value.get("seek_id").and_then(|v| v.as_u64()).ok_or(InvalidProtocol)?;
+                game_manager.accept_seek(id, user.clone(), None).map(|_| ())
+            }
+            "cancel_seek" => {
+                let id = value.get("seek_id").and_then(|v| v.as_u64()).ok_or(InvalidProtocol)?;
+                game_manager.cancel_seek(id, user.id)
+            }
+            _ => return Err(InvalidProtocol),
+        };
+
+        match result {
+            Ok(()) => Ok(None),
+            Err(e) => Ok(Some(make_rejected(packet_type, e))),
+        }
+    }
+
+    async fn handle_watch(&mut self, user: &UserInfo, value: &Value, watch: bool) -> Result<Option<String>, ProcessingError> {
+        let id = value.get("game_id").and_then(|v| v.as_u64()).ok_or(InvalidProtocol)?;
+
+        if !watch {
+            if self.unwatch_game(id) {
+                self.read_game_manager().await.remove_spectator(id, user.id);
+            }
+
+            return Ok(None);
+        }
+
+        // Watching first, so no update between joining the spectators and the answer is missed
+        if !self.watch_game(id) {
+            return Err(OldState);
+        }
+
+        let state = {
+            let game_manager = self.read_game_manager().await;
+            let games = game_manager.get_game_ids_of(user.id);
+
+            game_manager.add_spectator(id, user.clone()).map(|game| make_state(user, Some(&game.lock().unwrap()), games))
+        };
+
+        match state {
+            Ok(state) => Ok(Some(state)),
+            Err(e) => {
+                self.unwatch_game(id);
+                Ok(Some(make_rejected("watch", e)))
+            }
+        }
+    }
+
     async fn handle(&mut self, text: String) -> Result<Option<String>, ProcessingError> {
         let value: Value = match serde_json::from_str(&text) {
             Ok(val) => val,
@@ -125,24 +609,95 @@ pub trait Handler {
         };
 
         let user = self.fetch_user_info().await;
-        let mut game_manager = self.get_game_manager().await;
-        let game = game_manager.get_game(user.id);
+
+        match value.get("type").and_then(|v| v.as_str()) {
+            Some("watch") => return self.handle_watch(&user, &value, true).await,
+            Some("unwatch") => return self.handle_watch(&user, &value, false).await,
+            Some("join_lobby") => {
+                self.set_in_lobby(true);
+                return self.handle_lobby(&user, "join_lobby", &value).await;
+            }
+            Some("leave_lobby") => {
+                self.set_in_lobby(false);
+                return Ok(None);
+            }
+            Some("get_tournament") => {
+                let id = value.get("tournament_id").and_then(|v| v.as_u64()).ok_or(InvalidProtocol)?;
+                let game_manager = self.read_game_manager().await;
+                let tournament = game_manager.get_tournament(id).ok_or(OldState)?;
+
+                return Ok(Some(OutgoingPacket::Tournament(TournamentInfo::from(tournament)).to_json()));
+            }
+            Some("offer_rematch") => {
+                let id = value.get("game_id").and_then(|v| v.as_u64()).ok_or(InvalidProtocol)?;
+                let mut game_manager = self.get_game_manager().await;
+
+                // Once both players offered, the state of the new game is sent to them
+                return match game_manager.offer_rematch(id, user.id, None) {
+                    Ok(RematchOutcome::Offered(rematch)) => Ok(Some(make_rematch(rematch))),
+                    Ok(RematchOutcome::Started(_)) => Ok(None),
+                    Err(e) => Ok(Some(make_rejected("offer_rematch", e))),
+                };
+            }
+            Some("get_simul") => {
+                let id = value.get("simul_id").and_then(|v| v.as_u64()).ok_or(InvalidProtocol)?;
+                let game_manager = self.read_game_manager().await;
+                let (simul, games) = game_manager.get_simul_games(id).ok_or(OldState)?;
+
+                return Ok(Some(make_simul(simul, &games)));
+            }
+            Some("next_board") => {
+                let id = value.get("simul_id").and_then(|v| v.as_u64()).ok_or(InvalidProtocol)?;
+                let after = value.get("game_id").and_then(|v| v.as_u64());
+                let game_manager = self.read_game_manager().await;
+
+                // Only the host switches between boards, the opponents play a single one
+                if game_manager.get_simul(id).ok_or(OldState)?.host.id != user.id {
+                    return Err(OldState);
+                }
+
+                let next = game_manager.get_next_simul_board(id, after).ok_or(OldState)?;
+                let games = game_manager.get_game_ids_of(user.id);
+
+                let game = game_manager.get_game(next);
+                let game = game.as_ref().map(|game| game.lock().unwrap());
+
+                return Ok(Some(make_state(&user, game.as_deref(), games)));
+            }
+            Some("claim_win") => {
+                let selector = value.get("game_id").and_then(|v| v.as_u64()).map(GameSelector::Id);
+                let game_manager = self.read_game_manager().await;
+
+                return match game_manager.claim_win(user.id, selector) {
+                    Ok(_) => Ok(None),
+                    Err(e) => Ok(Some(make_rejected("claim_win", e))),
+                };
+            }
+            Some(packet_type @ "create_seek") | Some(packet_type @ "accept_seek") | Some(packet_type @ "cancel_seek") => return self.handle_lobby(&user, packet_type, &value).await,
+            _ => {}
+        }
+
+        let game_manager = self.read_game_manager().await;
+
+        // Packets without a game id keep addressing the player's only game
+        let selector = value.get("game_id").and_then(|v| v.as_u64()).map(GameSelector::Id);
+        let games = game_manager.get_game_ids_of(user.id);
+        let game = game_manager.find_game(user.id, selector).ok();
+        let mut game = game.as_ref().map(|game| game.lock().unwrap());
 
         let packet_type = value.get("type").and_then(|v| v.as_str());
         if let Some(packet_type) = packet_type {
             match packet_type {
-                "get_state" => return Ok(Some(make_state(&user, &game))),
-                "make_move" => {
-                    handle_make_move(&user, &value, game)?;
-                }
+                "get_state" => return Ok(Some(make_state(&user, game.as_deref(), games))),
+                "make_move" => return handle_make_move(&user, &value, game.as_deref_mut()),
                 "offer_draw" => {
-                    handle_simple_function(&user, game, ChessGame::offer_draw)?;
+                    handle_simple_function(&user, game.as_deref_mut(), TeamAction::OfferDraw, ChessGame::offer_draw)?;
                 }
                 "offer_takeback" => {
-                    handle_simple_function(&user, game, ChessGame::offer_takeback)?;
+                    handle_simple_function(&user, game.as_deref_mut(), TeamAction::OfferTakeback, ChessGame::offer_takeback)?;
                 }
                 "resign" => {
-                    handle_simple_function(&user, game, ChessGame::resign)?;
+                    handle_simple_function(&user, game.as_deref_mut(), TeamAction::Resign, ChessGame::resign)?;
                 }
                 _ => return Err(InvalidProtocol),
             };
@@ -154,22 +709,75 @@ pub trait Handler {
     }
 }
 
-pub fn make_state(user: &UserInfo, game: &Option<&mut Game>) -> String {
+pub fn make_state(user: &UserInfo, game: Option<&Game>, games: Vec<GameId>) -> String {
     let state = State {
         user: user.clone(),
-        game: game.as_ref().map(|game| make_game_state(&user, game)),
+        game: game.map(|game| make_game_state(Audience::of(game, user), game)),
+        games,
+    };
+
+    OutgoingPacket::State(Box::new(state)).to_json()
+}
+
+/// The game as the audience sees it, serialized once for every session of that audience.
+pub fn make_audience_state(game: &Game, audience: Audience) -> String {
+    serde_json::to_string(&make_game_state(audience, game)).unwrap()
+}
+
+/// A state like `make_state` around a game made by `make_audience_state`, which is not serialized again.
+pub fn make_shared_state(user: &UserInfo, game: &str, games: &[GameId]) -> String {
+    format!(
+        "{{\"type\":\"state\",\"user\":{},\"game\":{},\"games\":{}}}",
+        serde_json::to_string(user).unwrap(),
+        game,
+        serde_json::to_string(games).unwrap()
+    )
+}
+
+pub fn make_simul(simul: &Simul, games: &[Option<SharedGame>]) -> String {
+    OutgoingPacket::Simul(SimulInfo::new(simul, games)).to_json()
+}
+
+pub fn make_rematch(rematch: &Rematch) -> String {
+    OutgoingPacket::Rematch(RematchInfo::from(rematch)).to_json()
+}
+
+pub fn make_lobby(seeks: &[Seek]) -> String {
+    let lobby = Lobby {
+        seeks: seeks.iter().map(SeekInfo::from).collect(),
     };
 
-    serde_json::to_string_pretty(&state).unwrap()
+    OutgoingPacket::Lobby(lobby).to_json()
 }
 
-fn map_colors_to_ids(game: &Game, colors: &Vec<Color>) -> Vec<String> {
+/// Answers a packet of the given type that could not be carried out.
+pub fn make_rejected<E: ToString>(request: &str, error: E) -> String {
+    let rejected = OutgoingPacket::Rejected {
+        request: String::from(request),
+        error: error.to_string(),
+        reason: None,
+    };
+
+    rejected.to_json()
+}
+
+pub fn make_move_rejected(reason: MoveFailureReason) -> String {
+    let rejected = OutgoingPacket::Rejected {
+        request: String::from("make_move"),
+        error: reason.to_string(),
+        reason: Some(reason),
+    };
+
+    rejected.to_json()
+}
+
+fn map_colors_to_ids(game: &Game, colors: &[Color]) -> Vec<String> {
     colors.iter().map(|color| game.get_player_id_by_side(*color).to_string()).collect()
 }
 
-fn handle_make_move(user: &UserInfo, value: &Value, game: Option<&mut Game>) -> Result<(), ProcessingError> {
+fn handle_make_move(user: &UserInfo, value: &Value, game: Option<&mut Game>) -> Result<Option<String>, ProcessingError> {
     let game = game.ok_or(OldState)?;
-    if game.get_player_id_by_side(game.chess_game.state.current_turn) != user.id {
+    if game.get_side_of_player(user.id) != Some(game.chess_game.state.current_turn) {
         return Err(OldState);
     }
 
@@ -179,18 +787,28 @@ fn handle_make_move(user: &UserInfo, value: &Value, game: Option<&mut Game>) ->
         .ok_or(ProcessingError::InvalidProtocol)
         .and_then(|v| NewMove::from_str(v).map_err(|_| ProcessingError::InvalidProtocol))?;
 
-    let _ = game.chess_game.make_move(new_move);
+    // Team members propose moves, the team's decision is played
+    if game.consultation.is_some() {
+        return match game.propose_move(user.id, new_move) {
+            Ok(_) => Ok(None),
+            Err(ConsultationError::Move(reason)) => Ok(Some(make_move_rejected(reason))),
+            Err(e) => Ok(Some(make_rejected("make_move", e))),
+        };
+    }
 
-    Ok(())
+    match game.chess_game.make_move(new_move) {
+        Ok(_) => Ok(None),
+        Err(reason) => Ok(Some(make_move_rejected(reason))),
+    }
 }
 
-fn handle_simple_function<'a, F, R>(user: &UserInfo, game: Option<&'a mut Game>, function: F) -> Result<(), ProcessingError>
+/// Team members only vote for the action, it is done once their team decided on it.
+fn handle_simple_function<F, R>(user: &UserInfo, game: Option<&mut Game>, action: TeamAction, function: F) -> Result<(), ProcessingError>
 where
-    F: FnOnce(&'a mut ChessGame, Color) -> R,
+    F: FnOnce(&mut ChessGame, Color) -> R,
 {
     let game = game.ok_or(OldState)?;
-    let color = game.get_side_of_player(user.id).ok_or(OldState)?;
-    function(&mut game.chess_game, color);
+    game.decide_action(user.id, action, function).map_err(|_| OldState)?;
 
     Ok(())
 }
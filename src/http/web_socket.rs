@@ -1,19 +1,27 @@
-use actix::{Actor, ActorContext, AsyncContext, Handler as ActixHandler, Message, Running, StreamHandler};
+use actix::{Actor, ActorContext, ActorFuture, Addr, AsyncContext, Handler as ActixHandler, Message, Running, StreamHandler, WrapFuture};
 use actix_web_actors::ws;
 use actix_web_actors::ws::{CloseCode, CloseReason};
 use serenity::async_trait;
-use tokio::sync::{RwLock, RwLockWriteGuard};
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use crate::http::http_server::UserInfo;
-use crate::system::game::GameManager;
+use crate::system::events::{GameEvent, GameEventSubscriber};
+use crate::system::game::{Game, GameId, GameManager};
+use crate::system::simul::SimulId;
 
 use super::proto::{Handler, ProcessingError};
 
-use crate::http::proto::make_state;
+use crate::chess::board::Color;
+use crate::http::proto::{make_audience_state, make_shared_state, make_simul, Audience};
 use serenity::model::id::UserId;
-use std::sync::Arc;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
+#[derive(Default)]
 pub struct UnauthorizedWebSocketSession {}
 
 impl Actor for UnauthorizedWebSocketSession {
@@ -28,27 +36,214 @@ impl Actor for UnauthorizedWebSocketSession {
     }
 }
 
-impl Default for UnauthorizedWebSocketSession {
-    fn default() -> Self {
-        Self {}
+/// What every session is interested in.
+#[derive(Default)]
+struct Subscriptions {
+    /// The sessions of each user, they receive the states of the user's games.
+    users: HashMap<UserId, Vec<Addr<WebSocketSession>>>,
+    /// Sessions watching a game they do not play in, along with their users.
+    games: HashMap<GameId, Vec<(UserId, Addr<WebSocketSession>)>>,
+    /// The running games of each user, kept up to date by the game manager.
+    player_games: HashMap<UserId, Vec<GameId>>,
+    /// Sessions receiving the open seeks.
+    lobby: Vec<Addr<WebSocketSession>>,
+}
+
+/// A session receiving a state, along with its user.
+type Recipient<'a> = (UserId, &'a Addr<WebSocketSession>);
+
+fn remove_socket<K: Eq + Hash>(sockets: &mut HashMap<K, Vec<Addr<WebSocketSession>>>, key: K, socket: &Addr<WebSocketSession>) {
+    if let Entry::Occupied(mut entry) = sockets.entry(key) {
+        entry.get_mut().retain(|other| other != socket);
+
+        if entry.get().is_empty() {
+            entry.remove();
+        }
+    }
+}
+
+/// The connected sessions by what they subscribed to, shared by the game manager and the event subscribers.
+#[derive(Clone, Default)]
+pub struct SocketRegistry {
+    subscriptions: Arc<Mutex<Subscriptions>>,
+}
+
+impl SocketRegistry {
+    pub fn register(&self, user: UserId, socket: Addr<WebSocketSession>) {
+        self.subscriptions.lock().unwrap().users.entry(user).or_default().push(socket);
+    }
+
+    /// Removes the session along with everything it subscribed to.
+    pub fn unregister(&self, user: UserId, socket: &Addr<WebSocketSession>) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+
+        remove_socket(&mut subscriptions.users, user, socket);
+        subscriptions.games.retain(|_, sockets| {
+            sockets.retain(|(_, other)| other != socket);
+            !sockets.is_empty()
+        });
+        subscriptions.lobby.retain(|other| other != socket);
+    }
+
+    pub fn watch(&self, game: GameId, user: UserId, socket: Addr<WebSocketSession>) {
+        self.subscriptions.lock().unwrap().games.entry(game).or_default().push((user, socket));
+    }
+
+    pub fn unwatch(&self, game: GameId, socket: &Addr<WebSocketSession>) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+
+        if let Entry::Occupied(mut entry) = subscriptions.games.entry(game) {
+            entry.get_mut().retain(|(_, other)| other != socket);
+
+            if entry.get().is_empty() {
+                entry.remove();
+            }
+        }
+    }
+
+    /// Called by the game manager whenever a user's games change, the states sent to the user list them.
+    pub fn set_player_games(&self, user: UserId, games: Vec<GameId>) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+
+        if games.is_empty() {
+            subscriptions.player_games.remove(&user);
+        } else {
+            subscriptions.player_games.insert(user, games);
+        }
+    }
+
+    pub fn set_in_lobby(&self, socket: Addr<WebSocketSession>, in_lobby: bool) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        subscriptions.lobby.retain(|other| *other != socket);
+
+        if in_lobby {
+            subscriptions.lobby.push(socket);
+        }
+    }
+
+    pub fn send_to_lobby<M>(&self, message: M)
+    where
+        M: Message<Result = ()> + Clone + Send + 'static,
+        WebSocketSession: ActixHandler<M>,
+    {
+        for socket in self.subscriptions.lock().unwrap().lobby.iter() {
+            socket.do_send(message.clone());
+        }
+    }
+
+    /// Sends the message to every session of the users.
+    pub fn send_to_users<M>(&self, users: &[UserId], message: M)
+    where
+        M: Message<Result = ()> + Clone + Send + 'static,
+        WebSocketSession: ActixHandler<M>,
+    {
+        let subscriptions = self.subscriptions.lock().unwrap();
+
+        for socket in users.iter().filter_map(|user| subscriptions.users.get(user)).flatten() {
+            socket.do_send(message.clone());
+        }
+    }
+
+    /// Sends the game to its players and spectators, the state of each audience is only made if somebody receives it.
+    pub fn send_state(&self, game: &Game) {
+        let subscriptions = self.subscriptions.lock().unwrap();
+        let mut audiences: Vec<(Audience, Vec<Recipient>)> = Vec::new();
+
+        for &side in [Color::White, Color::Black].iter() {
+            let sockets = game
+                .get_players_by_side(side)
+                .iter()
+                .filter_map(|player| subscriptions.users.get(&player.id).map(|sockets| (player.id, sockets)))
+                .flat_map(|(user, sockets)| sockets.iter().map(move |socket| (user, socket)))
+                .collect();
+            audiences.push((Audience::Player(side), sockets));
+        }
+
+        // Players watching their own game already got it
+        let spectators = subscriptions
+            .games
+            .get(&game.id)
+            .into_iter()
+            .flatten()
+            .filter(|(_, socket)| !audiences.iter().any(|(_, sockets)| sockets.iter().any(|(_, other)| *other == socket)))
+            .map(|(user, socket)| (*user, socket))
+            .collect();
+        audiences.push((Audience::Spectator, spectators));
+
+        for (audience, sockets) in audiences.into_iter().filter(|(_, sockets)| !sockets.is_empty()) {
+            let state: Arc<str> = Arc::from(make_audience_state(game, audience));
+
+            for (user, socket) in sockets {
+                socket.do_send(UpdateGameStateMessage {
+                    game_id: game.id,
+                    audience,
+                    state: state.clone(),
+                    games: subscriptions.player_games.get(&user).cloned().unwrap_or_default(),
+                });
+            }
+        }
     }
 }
 
-const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
-const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Sends the new state of a game to the sessions of its players and spectators.
+pub struct GameStateNotifier {
+    sockets: SocketRegistry,
+}
+
+impl GameStateNotifier {
+    pub fn new(sockets: SocketRegistry) -> Self {
+        Self { sockets }
+    }
+}
+
+impl GameEventSubscriber for GameStateNotifier {
+    fn notify(&self, game: &Game, _: &[GameEvent]) {
+        self.sockets.send_state(game);
+    }
+}
+
+/// Sends the overview of a simul to its host whenever one of its games changes.
+pub struct SimulNotifier {
+    sockets: SocketRegistry,
+}
+
+impl SimulNotifier {
+    pub fn new(sockets: SocketRegistry) -> Self {
+        Self { sockets }
+    }
+}
+
+impl GameEventSubscriber for SimulNotifier {
+    fn notify(&self, game: &Game, _: &[GameEvent]) {
+        if let Some(simul) = game.simul {
+            self.sockets
+                .send_to_users(&[game.get_player_id_by_side(simul.host_color)], UpdateSimulMessage { simul_id: simul.simul });
+        }
+    }
+}
+
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub struct WebSocketSession {
     pub game_manager: Arc<RwLock<GameManager>>,
+    pub sockets: SocketRegistry,
     pub info: Option<UserInfo>,
     pub heartbeat: Instant,
+    pub watching: HashSet<GameId>,
+    /// Whether the session receives the open seeks whenever they change.
+    pub in_lobby: bool,
 }
 
 impl WebSocketSession {
-    pub fn new(info: Option<UserInfo>, game_manager: Arc<RwLock<GameManager>>) -> Self {
+    pub fn new(info: Option<UserInfo>, game_manager: Arc<RwLock<GameManager>>, sockets: SocketRegistry) -> Self {
         Self {
             game_manager,
+            sockets,
             info,
             heartbeat: Instant::now(),
+            watching: HashSet::new(),
+            in_lobby: false,
         }
     }
 
@@ -63,42 +258,99 @@ impl WebSocketSession {
         });
     }
 
-    pub async fn handle_packet(&mut self, text: String) -> Result<Option<String>, ProcessingError> {
-        <Self as Handler>::handle(self, text).await
-    }
-
-    fn block_for_manager(&self) -> RwLockWriteGuard<'_, GameManager> {
-        futures::executor::block_on(self.game_manager.write())
+    /// Sends the output of the future, the session processes nothing else until it completed so its messages stay in order.
+    fn send_in_order<F>(&mut self, ctx: &mut ws::WebsocketContext<Self>, future: F)
+    where
+        F: Future<Output = Option<String>> + 'static,
+    {
+        ctx.wait(future.into_actor(self).map(|text, _, ctx| {
+            if let Some(text) = text {
+                ctx.text(text);
+            }
+        }));
     }
 
     fn do_handle_packet(&mut self, text: String, ctx: &mut <WebSocketSession as Actor>::Context) {
-        match futures::executor::block_on(self.handle_packet(text)) {
-            Ok(str) => {
-                if let Some(str) = str {
-                    ctx.text(str);
-                }
-            }
-            Err(e) => match e {
-                ProcessingError::InvalidProtocol => {
+        let mut handler = PacketHandler {
+            game_manager: self.game_manager.clone(),
+            sockets: self.sockets.clone(),
+            address: ctx.address(),
+            info: self.info.clone().unwrap(),
+            watching: self.watching.clone(),
+            in_lobby: self.in_lobby,
+        };
+
+        // The session processes nothing else before it took over the subscriptions of the handler
+        let future = async move {
+            let result = handler.handle(text).await;
+            (handler, result)
+        };
+
+        ctx.wait(future.into_actor(self).map(|(handler, result), act, ctx| {
+            act.watching = handler.watching;
+            act.in_lobby = handler.in_lobby;
+
+            match result {
+                Ok(Some(text)) => ctx.text(text),
+                Ok(None) => {}
+                Err(ProcessingError::InvalidProtocol) => {
                     ctx.close(Some(CloseReason::from(CloseCode::Unsupported)));
                 }
-                ProcessingError::OldState => {}
-                ProcessingError::NoOutput => {}
-            },
-        }
+                Err(ProcessingError::OldState) => {}
+                Err(ProcessingError::NoOutput) => {}
+            }
+        }));
     }
 }
 
+/// Handles a packet of a session with a copy of its state, which the session takes over afterwards.
+struct PacketHandler {
+    game_manager: Arc<RwLock<GameManager>>,
+    sockets: SocketRegistry,
+    address: Addr<WebSocketSession>,
+    info: UserInfo,
+    watching: HashSet<GameId>,
+    in_lobby: bool,
+}
+
 #[async_trait]
-impl Handler for WebSocketSession {
+impl Handler for PacketHandler {
     async fn fetch_user_info(&mut self) -> UserInfo {
-        self.info.as_ref().unwrap().clone()
+        self.info.clone()
     }
 
     #[allow(clippy::needless_lifetimes)] // clippy bug ?
     async fn get_game_manager<'a>(&'a mut self) -> RwLockWriteGuard<'a, GameManager> {
         self.game_manager.write().await
     }
+
+    #[allow(clippy::needless_lifetimes)]
+    async fn read_game_manager<'a>(&'a mut self) -> RwLockReadGuard<'a, GameManager> {
+        self.game_manager.read().await
+    }
+
+    fn watch_game(&mut self, id: GameId) -> bool {
+        if !self.watching.insert(id) {
+            return false;
+        }
+
+        self.sockets.watch(id, self.info.id, self.address.clone());
+        true
+    }
+
+    fn unwatch_game(&mut self, id: GameId) -> bool {
+        if !self.watching.remove(&id) {
+            return false;
+        }
+
+        self.sockets.unwatch(id, &self.address);
+        true
+    }
+
+    fn set_in_lobby(&mut self, in_lobby: bool) {
+        self.in_lobby = in_lobby;
+        self.sockets.set_in_lobby(self.address.clone(), in_lobby);
+    }
 }
 
 impl Actor for WebSocketSession {
@@ -112,11 +364,28 @@ impl Actor for WebSocketSession {
             return;
         };
 
-        self.block_for_manager().register_socket(ctx.address());
+        self.sockets.register(self.info.as_ref().unwrap().id, ctx.address());
     }
 
     fn stopping(&mut self, ctx: &mut Self::Context) -> Running {
-        self.block_for_manager().unregister_socket(ctx.address());
+        let info = match &self.info {
+            Some(info) => info,
+            None => return Running::Stop,
+        };
+
+        self.sockets.unregister(info.id, &ctx.address());
+
+        let game_manager = self.game_manager.clone();
+        let spectator = info.id;
+        let watching = std::mem::take(&mut self.watching);
+
+        actix::spawn(async move {
+            let game_manager = game_manager.read().await;
+
+            for id in watching {
+                game_manager.remove_spectator(id, spectator);
+            }
+        });
 
         Running::Stop
     }
@@ -165,7 +434,12 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WebSocketSession
 
 #[derive(Clone)]
 pub struct UpdateGameStateMessage {
-    pub viewer_list: Vec<UserId>,
+    pub game_id: GameId,
+    pub audience: Audience,
+    /// Made by `make_audience_state`, shared by every session of the audience.
+    pub state: Arc<str>,
+    /// The running games of the session's user.
+    pub games: Vec<GameId>,
 }
 
 impl Message for UpdateGameStateMessage {
@@ -176,25 +450,252 @@ impl ActixHandler<UpdateGameStateMessage> for WebSocketSession {
     type Result = ();
 
     fn handle(&mut self, msg: UpdateGameStateMessage, ctx: &mut Self::Context) -> Self::Result {
-        match &self.info {
-            Some(info) => {
-                if !msg.viewer_list.contains(&info.id) {
-                    return;
+        let info = match &self.info {
+            Some(info) => info.clone(),
+            None => return,
+        };
+
+        // The session may have stopped watching after the state was sent
+        if msg.audience == Audience::Spectator && !self.watching.contains(&msg.game_id) {
+            return;
+        }
+
+        ctx.text(make_shared_state(&info, &msg.state, &msg.games));
+    }
+}
+
+#[derive(Clone)]
+pub struct UpdateLobbyMessage {
+    /// Made by `make_lobby`, shared by every session in the lobby.
+    pub lobby: Arc<str>,
+}
+
+impl Message for UpdateLobbyMessage {
+    type Result = ();
+}
+
+impl ActixHandler<UpdateLobbyMessage> for WebSocketSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: UpdateLobbyMessage, ctx: &mut Self::Context) -> Self::Result {
+        if self.in_lobby {
+            ctx.text(&*msg.lobby);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct UpdateRematchMessage {
+    /// Made by `make_rematch`, shared by the sessions of both players.
+    pub rematch: Arc<str>,
+}
+
+impl Message for UpdateRematchMessage {
+    type Result = ();
+}
+
+impl ActixHandler<UpdateRematchMessage> for WebSocketSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: UpdateRematchMessage, ctx: &mut Self::Context) -> Self::Result {
+        ctx.text(&*msg.rematch);
+    }
+}
+
+#[derive(Clone)]
+pub struct UpdateSimulMessage {
+    pub simul_id: SimulId,
+}
+
+impl Message for UpdateSimulMessage {
+    type Result = ();
+}
+
+impl ActixHandler<UpdateSimulMessage> for WebSocketSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: UpdateSimulMessage, ctx: &mut Self::Context) -> Self::Result {
+        let game_manager = self.game_manager.clone();
+
+        self.send_in_order(ctx, async move {
+            let game_manager = game_manager.read().await;
+            game_manager.get_simul_games(msg.simul_id).map(|(simul, games)| make_simul(simul, &games))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::{test, web, App, HttpRequest, HttpResponse};
+    use futures::{Sink, SinkExt, Stream, StreamExt};
+    use serde_json::{json, Value};
+
+    use crate::system::game::GameOptions;
+
+    const GAMES: u64 = 4;
+    /// User id of the first spectator, the players have the ones below.
+    const SPECTATORS: u64 = 1000;
+    /// No move repeats the squares of another, so the highlighted squares tell how far a state is.
+    const MOVES: [&str; 12] = ["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6", "d2d3", "f8c5", "c2c3", "d7d6", "b1d2", "c8e6"];
+
+    fn player(id: u64) -> UserInfo {
+        UserInfo {
+            id: UserId(id),
+            username: format!("player{}", id),
+            discriminator: String::from("0001"),
+            avatar: None,
+        }
+    }
+
+    async fn socket(
+        req: HttpRequest,
+        path: web::Path<u64>,
+        stream: web::Payload,
+        game_manager: web::Data<Arc<RwLock<GameManager>>>,
+        sockets: web::Data<SocketRegistry>,
+    ) -> Result<HttpResponse, actix_web::Error> {
+        ws::start(
+            WebSocketSession::new(Some(player(path.into_inner())), game_manager.get_ref().clone(), sockets.get_ref().clone()),
+            &req,
+            stream,
+        )
+    }
+
+    /// One player's connection, it remembers the last move it was shown.
+    struct Client<S> {
+        socket: S,
+        game: GameId,
+        last_ply: Option<usize>,
+    }
+
+    impl<S> Client<S>
+    where
+        S: Stream<Item = Result<ws::Frame, ws::ProtocolError>> + Sink<ws::Message> + Unpin,
+    {
+        async fn send(&mut self, packet: Value) {
+            assert!(self.socket.send(ws::Message::Text(packet.to_string())).await.is_ok(), "failed to send a packet");
+        }
+
+        /// Reads the next state, it may not be older than the one before.
+        async fn receive(&mut self) {
+            let text = loop {
+                match self.socket.next().await {
+                    Some(Ok(ws::Frame::Text(text))) => break text,
+                    Some(Ok(_)) => {}
+                    _ => panic!("the connection of game #{} ended", self.game),
                 }
+            };
+
+            let packet: Value = serde_json::from_slice(&text).unwrap();
+            assert_eq!(packet["type"], json!("state"), "unexpected packet {}", packet);
+
+            let state = &packet["game"];
+            assert_eq!(state["id"], json!(self.game), "unexpected packet {}", packet);
+
+            let squares: Vec<String> = state["highlighted_squares"].as_array().unwrap().iter().map(|square| square.as_str().unwrap().to_lowercase()).collect();
+            let shown = MOVES.iter().position(|&m| m == squares.concat());
+
+            assert!(shown >= self.last_ply, "game #{} went back from {:?} to {:?}", self.game, self.last_ply, shown);
+            self.last_ply = shown;
+        }
+
+        async fn wait_for(&mut self, ply: usize) {
+            while self.last_ply < Some(ply) {
+                self.receive().await;
             }
-            None => {
-                return;
+        }
+    }
+
+    async fn play<S>(mut white: Client<S>, mut black: Client<S>, mut spectator: Client<S>)
+    where
+        S: Stream<Item = Result<ws::Frame, ws::ProtocolError>> + Sink<ws::Message> + Unpin,
+    {
+        for (ply, m) in MOVES.iter().enumerate() {
+            let mover = if ply.is_multiple_of(2) { &mut white } else { &mut black };
+
+            // The other player's notifications pile up until the end
+            if let Some(previous) = ply.checked_sub(1) {
+                mover.wait_for(previous).await;
             }
+
+            mover.send(json!({ "type": "make_move", "game_id": mover.game, "move": m })).await;
         }
 
-        match &self.info {
-            Some(info) => {
-                let mut game_manager = self.block_for_manager();
-                ctx.text(make_state(&info, &game_manager.get_game(info.id)));
+        white.wait_for(MOVES.len() - 1).await;
+        black.wait_for(MOVES.len() - 1).await;
+        spectator.wait_for(MOVES.len() - 1).await;
+    }
+
+    #[test]
+    fn concurrent_moves_are_delivered_in_order() {
+        actix_web::rt::System::new("test").block_on(async {
+            let game_manager = Arc::new(RwLock::new(GameManager::new()));
+            let events = game_manager.write().await.manage_games(game_manager.clone());
+            let sockets = game_manager.read().await.get_sockets();
+
+            game_manager.write().await.subscribe(Box::new(GameStateNotifier::new(sockets.clone())));
+
+            actix_web::rt::spawn(GameManager::dispatch_events(game_manager.clone(), events));
+
+            let ids: Vec<GameId> = {
+                let mut manager = game_manager.write().await;
+
+                (0..GAMES)
+                    .map(|index| {
+                        manager
+                            .create_game(player(2 * index + 1), player(2 * index + 2), None, GameOptions::default())
+                            .unwrap()
+                            .lock()
+                            .unwrap()
+                            .id
+                    })
+                    .collect()
+            };
+
+            let data = game_manager.clone();
+            let mut server = test::start(move || App::new().data(data.clone()).data(sockets.clone()).route("/socket/{player}", web::get().to(socket)));
+
+            let mut games = Vec::new();
+
+            for (index, &id) in ids.iter().enumerate() {
+                let mut clients = Vec::new();
+
+                for player in [2 * index + 1, 2 * index + 2].iter() {
+                    let mut client = Client {
+                        socket: server.ws_at(&format!("/socket/{}", player)).await.unwrap(),
+                        game: id,
+                        last_ply: None,
+                    };
+
+                    // The answer shows the session is registered for notifications
+                    client.send(json!({ "type": "get_state", "game_id": id })).await;
+                    client.receive().await;
+                    clients.push(client);
+                }
+
+                let mut spectator = Client {
+                    socket: server.ws_at(&format!("/socket/{}", SPECTATORS + index as u64)).await.unwrap(),
+                    game: id,
+                    last_ply: None,
+                };
+
+                spectator.send(json!({ "type": "watch", "game_id": id })).await;
+                spectator.receive().await;
+
+                let black = clients.pop().unwrap();
+                let white = clients.pop().unwrap();
+                games.push(play(white, black, spectator));
             }
-            None => {
-                ctx.close(Some(CloseReason::from(CloseCode::from(4000))));
+
+            let finished = actix_web::rt::time::timeout(Duration::from_secs(30), futures::future::join_all(games)).await;
+            assert!(finished.is_ok(), "the games stalled");
+
+            for id in ids {
+                let game = game_manager.read().await.get_game(id).unwrap();
+                assert_eq!(game.lock().unwrap().chess_game.get_ply() as usize, MOVES.len());
             }
-        }
+        });
     }
 }
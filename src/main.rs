@@ -12,6 +12,7 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use image::{ImageFormat, Rgba};
+use serenity::http::Http;
 use tokio::sync::RwLock;
 
 use crate::chess::board::Color;
@@ -19,7 +20,9 @@ use crate::chess::pieces::Type;
 use crate::config::load_config;
 use crate::discord::bot::{start_bot, BotData};
 use crate::http::http_server::start_server;
-use crate::system::game::GameManager;
+use crate::http::web_socket::{GameStateNotifier, SimulNotifier};
+use crate::system::game::{GameManager, ResultAnnouncer, WatcherNotifier};
+use crate::system::storage::{open_storage, GameSaver, StorageWriter};
 use crate::util::board_visualizer::{BoardVisualizer, Config};
 
 #[tokio::main]
@@ -27,13 +30,37 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let config = load_config().expect("Failed to load config");
 
     let local = tokio::task::LocalSet::new();
-    let _ = actix_web::rt::System::run_in_tokio("server", &local);
+    drop(actix_web::rt::System::run_in_tokio("server", &local));
 
     let game_manager = Arc::new(RwLock::new(GameManager::new()));
-    game_manager.write().await.manage_games(game_manager.clone());
+    let events = game_manager.write().await.manage_games(game_manager.clone());
+
+    let storage = open_storage(&config.storage).expect("Failed to open game storage");
+    let writer = StorageWriter::new(storage.clone());
+    let http = Arc::new(Http::new_with_token(&config.discord.token));
+
+    {
+        let mut manager = game_manager.write().await;
+        let sockets = manager.get_sockets();
+
+        manager.set_abandonment_config(config.abandonment);
+        manager.subscribe(Box::new(GameSaver::new(writer.clone())));
+        manager.subscribe(Box::new(GameStateNotifier::new(sockets.clone())));
+        manager.subscribe(Box::new(SimulNotifier::new(sockets)));
+        manager.subscribe(Box::new(ResultAnnouncer::new(game_manager.clone())));
+        manager.subscribe(Box::new(WatcherNotifier));
+        let skipped = manager.load_games(storage, writer, http).expect("Failed to load stored games");
+
+        for record in skipped.iter() {
+            eprintln!("Skipped stored {}", record);
+        }
+    }
+
+    tokio::spawn(GameManager::dispatch_events(game_manager.clone(), events));
+    tokio::spawn(GameManager::run_ticks(game_manager.clone()));
 
     let data = BotData {
-        visualizer: setup_visualizer(),
+        visualizer: Arc::new(setup_visualizer()),
         game_manager: game_manager.clone(),
         prefix: config.discord.prefix.clone(),
         play_url: config.http.frontend_address.clone(),
@@ -75,6 +102,8 @@ fn setup_visualizer() -> BoardVisualizer {
         dark_tile_color_highlighted: Rgba([0x52, 0x55, 0x5b, 0xFF]),
         text_on_light_color: Rgba([0xFF, 0xFF, 0xFF, 0xFF]),
         text_on_dark_color: Rgba([0xFF, 0xFF, 0xFF, 0xFF]),
+        white_control_color: Rgba([0x43, 0xB5, 0x81, 0x90]),
+        black_control_color: Rgba([0xF0, 0x47, 0x47, 0x90]),
         text_font: include_bytes!("res/DejaVuSans.ttf") as &[u8],
         text_font_size: 20,
         pieces_image: include_bytes!("res/pieces.png") as &[u8],
@@ -0,0 +1,176 @@
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::AbandonmentConfig;
+
+use super::game::{Game, GameId, GameLookupError, TimeControl};
+
+#[derive(Error, Debug)]
+pub enum AbandonmentError {
+    #[error(transparent)]
+    Lookup(#[from] GameLookupError),
+    #[error("It is your turn in game #{0}.")]
+    YourTurn(GameId),
+    #[error("Your opponent has not abandoned game #{0}.")]
+    NotAbandoned(GameId),
+}
+
+/// When the side to move got its turn.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct Activity {
+    pub time: SystemTime,
+    /// Ply the time was recorded for, a different ply means a move was made or taken back since.
+    pub ply: usize,
+    /// Whether the waiting player was told they can claim the win.
+    pub claimable: bool,
+}
+
+impl Activity {
+    pub fn new(ply: usize) -> Self {
+        Self {
+            time: SystemTime::now(),
+            ply,
+            claimable: false,
+        }
+    }
+
+    pub fn get_inactive_time(&self) -> Duration {
+        self.time.elapsed().unwrap_or_default()
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Inactivity {
+    Active,
+    /// The side to move never made its first move.
+    Abort,
+    /// The waiting player may claim the win.
+    Claimable,
+    Forfeit,
+}
+
+fn minutes(minutes: u64) -> Duration {
+    Duration::from_secs(minutes * 60)
+}
+
+pub fn get_inactivity(config: &AbandonmentConfig, game: &Game) -> Inactivity {
+    if game.chess_game.result.is_some() || matches!(game.options.time_control, TimeControl::Correspondence { .. }) {
+        return Inactivity::Active;
+    }
+
+    // The host of a simul moves on many boards at once, only the opponents can abandon their games
+    if game.simul.is_some_and(|simul| simul.host_color == game.chess_game.state.current_turn) {
+        return Inactivity::Active;
+    }
+
+    let inactive = game.activity.get_inactive_time();
+
+    if game.chess_game.get_ply() < 2 {
+        return if inactive >= minutes(config.abort_after) { Inactivity::Abort } else { Inactivity::Active };
+    }
+
+    if inactive >= minutes(config.forfeit_after) {
+        Inactivity::Forfeit
+    } else if inactive >= minutes(config.claim_after) {
+        Inactivity::Claimable
+    } else {
+        Inactivity::Active
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use serenity::model::id::UserId;
+
+    use crate::chess::board::Color;
+    use crate::chess::game::Game as ChessGame;
+    use crate::chess::moves::NewMove;
+    use crate::http::http_server::UserInfo;
+    use crate::system::simul::SimulGame;
+
+    use super::*;
+
+    fn player(id: u64) -> UserInfo {
+        UserInfo {
+            id: UserId(id),
+            username: format!("player{}", id),
+            discriminator: String::from("0001"),
+            avatar: None,
+        }
+    }
+
+    /// A game after the given moves in which the side to move has not moved for the given number of minutes.
+    fn game_after(moves: &[&str], inactive: u64) -> Game {
+        let mut game = Game::new(1, player(1), player(2), ChessGame::new());
+
+        for m in moves {
+            game.chess_game.make_move(NewMove::from_str(m).unwrap()).unwrap();
+        }
+
+        game.update_activity();
+        game.activity.time = SystemTime::now() - minutes(inactive);
+        game
+    }
+
+    fn inactivity(game: &Game) -> Inactivity {
+        get_inactivity(&AbandonmentConfig::default(), game)
+    }
+
+    #[test]
+    fn games_without_first_moves_are_aborted() {
+        assert_eq!(inactivity(&game_after(&[], 9)), Inactivity::Active);
+        assert_eq!(inactivity(&game_after(&[], 10)), Inactivity::Abort);
+        assert_eq!(inactivity(&game_after(&["E2E4"], 10)), Inactivity::Abort, "black never made its first move");
+    }
+
+    #[test]
+    fn inactive_players_can_be_claimed_against_and_then_forfeit() {
+        let moves = ["E2E4", "E7E5"];
+
+        assert_eq!(inactivity(&game_after(&moves, 14)), Inactivity::Active);
+        assert_eq!(inactivity(&game_after(&moves, 15)), Inactivity::Claimable);
+        assert_eq!(inactivity(&game_after(&moves, 60)), Inactivity::Forfeit);
+    }
+
+    #[test]
+    fn moves_restart_the_inactivity() {
+        let mut game = game_after(&["E2E4", "E7E5"], 60);
+
+        game.chess_game.make_move(NewMove::from_str("G1F3").unwrap()).unwrap();
+        assert!(game.update_activity());
+        assert_eq!(inactivity(&game), Inactivity::Active);
+    }
+
+    #[test]
+    fn simul_hosts_never_abandon() {
+        let mut game = game_after(&["E2E4", "E7E5"], 60);
+
+        game.simul = Some(SimulGame {
+            simul: 1,
+            board: 1,
+            host_color: Color::White,
+        });
+        assert_eq!(inactivity(&game), Inactivity::Active);
+
+        game.simul = Some(SimulGame {
+            simul: 1,
+            board: 1,
+            host_color: Color::Black,
+        });
+        assert_eq!(inactivity(&game), Inactivity::Forfeit);
+    }
+
+    #[test]
+    fn correspondence_and_finished_games_are_skipped() {
+        let mut game = game_after(&["E2E4", "E7E5"], 60);
+        game.options.time_control = TimeControl::Correspondence { days_per_move: 3 };
+        assert_eq!(inactivity(&game), Inactivity::Active);
+
+        let mut game = game_after(&["E2E4", "E7E5"], 60);
+        game.chess_game.resign(Color::White).unwrap();
+        assert_eq!(inactivity(&game), Inactivity::Active);
+    }
+}
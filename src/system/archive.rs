@@ -0,0 +1,383 @@
+use std::cmp::Reverse;
+use std::convert::TryFrom;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serenity::model::id::UserId;
+
+use crate::chess::board::Color;
+use crate::chess::game::{Game as ChessGame, GameLoadError, GameResult, SerializedGame};
+use crate::chess::moves::HistoryMove;
+use crate::chess::pgn::{get_result_token, write_fen, write_movetext};
+use crate::http::http_server::UserInfo;
+
+use super::consultation::Consultation;
+use super::game::{Game, GameId, GameOptions, TimeControl};
+use super::handicap::Handicap;
+use super::rating::Rating;
+
+type PlayerId = UserId;
+
+/// A concluded game, kept for looking up past games.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ArchivedGame {
+    pub id: GameId,
+    pub white_player: UserInfo,
+    pub black_player: UserInfo,
+    pub result: GameResult,
+    pub start_time: SystemTime,
+    pub end_time: SystemTime,
+    pub options: GameOptions,
+    /// Ratings of the players when the game started.
+    pub white_rating: Rating,
+    pub black_rating: Rating,
+    pub handicap: Option<Handicap>,
+    pub consultation: Option<Consultation>,
+    pub game: SerializedGame,
+}
+
+impl ArchivedGame {
+    pub fn from_game(game: &Game) -> Self {
+        Self {
+            id: game.id,
+            white_player: game.white_player.clone(),
+            black_player: game.black_player.clone(),
+            result: game.chess_game.result.unwrap_or(GameResult::Ongoing),
+            start_time: game.start_time,
+            end_time: SystemTime::now(),
+            options: game.options,
+            white_rating: game.white_rating,
+            black_rating: game.black_rating,
+            handicap: game.handicap,
+            consultation: game.consultation.clone(),
+            game: game.chess_game.to_serialized(),
+        }
+    }
+
+    /// Exports the game in Portable Game Notation, the odds are kept in the `Handicap` tag and the starting position.
+    pub fn to_pgn(&self) -> Result<String, GameLoadError> {
+        let chess_game = self.to_chess_game()?;
+        let mut tags = vec![
+            ("Event", String::from(if self.options.rated { "Rated game" } else { "Casual game" })),
+            ("Site", String::from("Discord")),
+            ("Date", format_pgn_date(self.start_time)),
+            ("Round", String::from("-")),
+            ("White", self.get_names_by_side(Color::White)),
+            ("Black", self.get_names_by_side(Color::Black)),
+            ("Result", String::from(get_result_token(Some(self.result)))),
+        ];
+
+        if self.options.rated {
+            tags.push(("WhiteElo", format!("{:.0}", self.white_rating.rating)));
+            tags.push(("BlackElo", format!("{:.0}", self.black_rating.rating)));
+        }
+
+        match self.handicap {
+            Some(handicap) => {
+                tags.push(("WhiteTimeControl", format_pgn_time_control(handicap.get_time_control(Color::White, self.options.time_control))));
+                tags.push(("BlackTimeControl", format_pgn_time_control(handicap.get_time_control(Color::Black, self.options.time_control))));
+                tags.push(("Handicap", handicap.to_string()));
+            }
+            None => tags.push(("TimeControl", format_pgn_time_control(self.options.time_control))),
+        }
+
+        if self.handicap.is_some_and(|handicap| handicap.odds.is_material()) {
+            tags.push(("SetUp", String::from("1")));
+            tags.push(("FEN", write_fen(&self.game.start_position, 1)));
+        }
+
+        let mut pgn: String = tags
+            .iter()
+            .map(|(name, value)| format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\"")))
+            .collect();
+        pgn.push('\n');
+        pgn.push_str(&write_movetext(&chess_game));
+        pgn.push('\n');
+
+        Ok(pgn)
+    }
+
+    /// In team games every member of a team plays its side.
+    pub fn get_side_of_player(&self, player_id: PlayerId) -> Option<Color> {
+        if let Some(consultation) = &self.consultation {
+            return consultation.get_side_of_player(player_id);
+        }
+
+        if self.white_player.id == player_id {
+            Some(Color::White)
+        } else if self.black_player.id == player_id {
+            Some(Color::Black)
+        } else {
+            None
+        }
+    }
+
+    /// Private games are only shown to their players, anonymous users are given `None`.
+    pub fn can_be_viewed_by(&self, user: Option<PlayerId>) -> bool {
+        !self.options.private || user.is_some_and(|user| self.get_side_of_player(user).is_some())
+    }
+
+    /// The player, or the team member, as they appeared in the game.
+    pub fn get_player(&self, player_id: PlayerId) -> Option<&UserInfo> {
+        match &self.consultation {
+            Some(consultation) => consultation.white_team.iter().chain(consultation.black_team.iter()).find(|member| member.id == player_id),
+            None => self.get_side_of_player(player_id).map(|side| self.get_player_by_side(side)),
+        }
+    }
+
+    pub fn get_player_by_side(&self, side: Color) -> &UserInfo {
+        match side {
+            Color::White => &self.white_player,
+            Color::Black => &self.black_player,
+        }
+    }
+
+    /// The player's name, or the names of the whole team in team games.
+    pub fn get_names_by_side(&self, side: Color) -> String {
+        match &self.consultation {
+            Some(consultation) => consultation.get_team(side).iter().map(|member| member.username.clone()).collect::<Vec<String>>().join(", "),
+            None => self.get_player_by_side(side).username.clone(),
+        }
+    }
+
+    pub fn get_outcome_for(&self, player_id: PlayerId) -> Option<Outcome> {
+        let side = self.get_side_of_player(player_id)?;
+
+        Some(match self.result.get_winner() {
+            Some(winner) if winner == side => Outcome::Win,
+            Some(_) => Outcome::Loss,
+            None => Outcome::Draw,
+        })
+    }
+
+    pub fn get_moves(&self) -> &[HistoryMove] {
+        &self.game.moves
+    }
+
+    pub fn to_chess_game(&self) -> Result<ChessGame, GameLoadError> {
+        ChessGame::from_serialized(self.game.clone())
+    }
+
+    /// Rebuilds the finished game, it is not managed and will not be saved.
+    pub fn to_game(&self) -> Result<Game, GameLoadError> {
+        let mut game = Game::new(self.id, self.white_player.clone(), self.black_player.clone(), self.to_chess_game()?);
+        game.start_time = self.start_time;
+        game.options = self.options;
+        game.white_rating = self.white_rating;
+        game.black_rating = self.black_rating;
+        game.handicap = self.handicap;
+        game.consultation = self.consultation.clone();
+
+        Ok(game)
+    }
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Copy, Clone, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+impl FromStr for Outcome {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "win" | "won" => Ok(Outcome::Win),
+            "loss" | "lost" => Ok(Outcome::Loss),
+            "draw" | "drawn" => Ok(Outcome::Draw),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Filter for the games of a single player, the outcome is seen from that player's side.
+pub struct ArchiveQuery {
+    pub player: PlayerId,
+    pub opponent: Option<PlayerId>,
+    pub since: Option<SystemTime>,
+    pub until: Option<SystemTime>,
+    pub outcome: Option<Outcome>,
+}
+
+impl ArchiveQuery {
+    pub fn new(player: PlayerId) -> Self {
+        Self {
+            player,
+            opponent: None,
+            since: None,
+            until: None,
+            outcome: None,
+        }
+    }
+
+    pub fn matches(&self, game: &ArchivedGame) -> bool {
+        let side = match game.get_side_of_player(self.player) {
+            Some(side) => side,
+            None => return false,
+        };
+
+        self.opponent.is_none_or(|opponent| game.get_side_of_player(opponent) == Some(side.get_opposite()))
+            && self.since.is_none_or(|since| game.end_time >= since)
+            && self.until.is_none_or(|until| game.end_time <= until)
+            && self.outcome.is_none_or(|outcome| game.get_outcome_for(self.player) == Some(outcome))
+    }
+}
+
+/// Start of a day in UTC written as YYYY-MM-DD, used to limit queries to a time span.
+pub fn parse_date(s: &str) -> Option<SystemTime> {
+    let mut parts = s.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+
+    let is_leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        2 if is_leap_year => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return None,
+    };
+
+    if !(1..=days_in_month).contains(&day) {
+        return None;
+    }
+
+    // Days since 1970-01-01, counting years from March so leap days come last
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = u64::try_from(era * 146_097 + day_of_era - 719_468).ok()?;
+
+    Some(UNIX_EPOCH + Duration::from_secs(days * 24 * 60 * 60))
+}
+
+#[derive(Default)]
+pub struct GameArchive {
+    games: Vec<ArchivedGame>,
+}
+
+impl GameArchive {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, game: ArchivedGame) {
+        self.games.push(game);
+    }
+
+    pub fn get(&self, id: GameId) -> Option<&ArchivedGame> {
+        self.games.iter().find(|game| game.id == id)
+    }
+
+    pub fn get_games(&self) -> &[ArchivedGame] {
+        &self.games
+    }
+
+    /// The user as they appeared in their most recently archived game.
+    pub fn get_user_info(&self, user: PlayerId) -> Option<&UserInfo> {
+        self.games.iter().rev().find_map(|game| game.get_player(user))
+    }
+
+    /// Matching games, the most recently finished first.
+    pub fn query(&self, query: &ArchiveQuery) -> Vec<&ArchivedGame> {
+        let mut games: Vec<&ArchivedGame> = self.games.iter().filter(|game| query.matches(game)).collect();
+        games.sort_by_key(|game| Reverse(game.end_time));
+        games
+    }
+
+    pub fn get_latest_for(&self, player: PlayerId) -> Option<&ArchivedGame> {
+        self.query(&ArchiveQuery::new(player)).into_iter().next()
+    }
+}
+
+/// Dates are written like `2020.09.27`, in UTC.
+fn format_pgn_date(time: SystemTime) -> String {
+    let days = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64 / (24 * 60 * 60);
+
+    // Converts days since 1970-01-01 to a civil date
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+fn format_pgn_time_control(time_control: TimeControl) -> String {
+    match time_control {
+        TimeControl::Unlimited => String::from("-"),
+        TimeControl::Live { initial, increment } => format!("{}+{}", initial, increment),
+        TimeControl::Correspondence { days_per_move } => format!("1/{}", days_per_move as u64 * 24 * 60 * 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::consultation::{Consultation, MoveSelection, DEFAULT_VOTING_WINDOW};
+
+    fn player(id: u64) -> UserInfo {
+        UserInfo {
+            id: UserId(id),
+            username: format!("player{}", id),
+            discriminator: String::from("0001"),
+            avatar: None,
+        }
+    }
+
+    #[test]
+    fn team_members_find_their_games() {
+        let (white_team, black_team) = (vec![player(1), player(2)], vec![player(3), player(4)]);
+
+        let mut game = Game::new(1, player(1), player(3), ChessGame::new());
+        game.consultation = Some(Consultation::new(white_team, black_team, MoveSelection::Vote, DEFAULT_VOTING_WINDOW).unwrap());
+        game.chess_game.resign(Color::Black).unwrap();
+
+        let mut archive = GameArchive::new();
+        archive.add(ArchivedGame::from_game(&game));
+
+        let mut query = ArchiveQuery::new(UserId(2));
+        assert_eq!(archive.query(&query).len(), 1);
+
+        query.opponent = Some(UserId(4));
+        query.outcome = Some(Outcome::Win);
+        assert_eq!(archive.query(&query).len(), 1);
+
+        query.opponent = Some(UserId(1));
+        assert!(archive.query(&query).is_empty());
+
+        assert_eq!(archive.get_user_info(UserId(2)).unwrap().username, "player2");
+        assert_eq!(archive.get_user_info(UserId(4)).unwrap().username, "player4");
+    }
+
+    fn days(days: u64) -> Option<SystemTime> {
+        Some(UNIX_EPOCH + Duration::from_secs(days * 24 * 60 * 60))
+    }
+
+    #[test]
+    fn dates_count_days_since_the_epoch() {
+        assert_eq!(parse_date("1970-01-01"), days(0));
+        assert_eq!(parse_date("2000-03-01"), days(11_017));
+        assert_eq!(parse_date("2024-02-29"), days(19_782));
+        assert_eq!(parse_date("2024-12-31"), days(20_088));
+    }
+
+    #[test]
+    fn invalid_dates_are_refused() {
+        assert_eq!(parse_date("2023-02-29"), None);
+        assert_eq!(parse_date("2024-13-01"), None);
+        assert_eq!(parse_date("2024-04-31"), None);
+        assert_eq!(parse_date("1969-12-31"), None);
+        assert_eq!(parse_date("yesterday"), None);
+    }
+}
@@ -0,0 +1,317 @@
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{ChannelId, MessageId, UserId};
+
+use crate::chess::board::Color;
+use crate::chess::game::GameResult;
+use crate::http::http_server::UserInfo;
+
+use super::game::{GameAnnouncer, GameId, GameOptions};
+
+type PlayerId = UserId;
+pub type ArenaId = u64;
+
+pub const WIN_POINTS: u32 = 2;
+pub const DRAW_POINTS: u32 = 1;
+/// Wins in a row after which the points of every game are doubled, until the player stops winning.
+pub const STREAK_LENGTH: u32 = 2;
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Copy, Clone, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ArenaState {
+    Registration,
+    Running,
+    Finished,
+}
+
+#[derive(Error, Debug)]
+pub enum ArenaError {
+    #[error("There is no arena #{0}.")]
+    NoSuchArena(ArenaId),
+    #[error("The arena has already finished.")]
+    ArenaFinished,
+    #[error("The arena has already started.")]
+    AlreadyStarted,
+    #[error("You already joined this arena.")]
+    AlreadyJoined,
+    #[error("You are not playing in this arena.")]
+    NotJoined,
+    #[error("Only the organizer can start the arena.")]
+    NotOrganizer,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ArenaPlayer {
+    pub user: UserInfo,
+    pub score: u32,
+    /// Points of every finished game, oldest first.
+    pub results: Vec<u32>,
+    pub win_streak: u32,
+    /// Games played as white minus games played as black.
+    pub color_balance: i32,
+    pub game: Option<GameId>,
+    pub last_opponent: Option<PlayerId>,
+    /// Withdrawn players keep their score but are not paired anymore.
+    pub withdrawn: bool,
+}
+
+impl ArenaPlayer {
+    pub fn new(user: UserInfo) -> Self {
+        Self {
+            user,
+            score: 0,
+            results: Vec::new(),
+            win_streak: 0,
+            color_balance: 0,
+            game: None,
+            last_opponent: None,
+            withdrawn: false,
+        }
+    }
+
+    pub fn is_on_fire(&self) -> bool {
+        self.win_streak >= STREAK_LENGTH
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        !self.withdrawn && self.game.is_none()
+    }
+
+    fn add_result(&mut self, result: GameResult, side: Color) {
+        // Aborted games were never played, they neither score nor break a streak
+        if result == GameResult::Aborted {
+            return;
+        }
+
+        let multiplier = if self.is_on_fire() { 2 } else { 1 };
+
+        let points = match result.get_winner() {
+            Some(winner) if winner == side => {
+                self.win_streak += 1;
+                WIN_POINTS * multiplier
+            }
+            Some(_) => {
+                self.win_streak = 0;
+                0
+            }
+            None => {
+                self.win_streak = 0;
+                DRAW_POINTS * multiplier
+            }
+        };
+
+        self.score += points;
+        self.results.push(points);
+    }
+}
+
+/// A time-boxed tournament where players are paired again as soon as their game is over.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Arena {
+    pub id: ArenaId,
+    pub name: String,
+    pub organizer: PlayerId,
+    pub options: GameOptions,
+    pub duration: Duration,
+    pub end_time: Option<SystemTime>,
+    pub state: ArenaState,
+    pub players: Vec<ArenaPlayer>,
+    pub announce_channel: Option<ChannelId>,
+    /// The message in the announce channel that is edited whenever the scores change.
+    pub scoreboard: Option<MessageId>,
+    #[serde(skip)]
+    pub announcer: Option<GameAnnouncer>,
+}
+
+impl Arena {
+    pub fn new(id: ArenaId, name: String, organizer: PlayerId, duration: Duration, options: GameOptions, announcer: Option<GameAnnouncer>) -> Self {
+        Self {
+            id,
+            name,
+            organizer,
+            options,
+            duration,
+            end_time: None,
+            state: ArenaState::Registration,
+            players: Vec::new(),
+            announce_channel: announcer.as_ref().map(|announcer| announcer.id),
+            scoreboard: None,
+            announcer,
+        }
+    }
+
+    pub fn get_player(&self, player: PlayerId) -> Option<&ArenaPlayer> {
+        self.players.iter().find(|other| other.user.id == player)
+    }
+
+    fn get_player_mut(&mut self, player: PlayerId) -> Option<&mut ArenaPlayer> {
+        self.players.iter_mut().find(|other| other.user.id == player)
+    }
+
+    pub fn start(&mut self) {
+        self.state = ArenaState::Running;
+        self.end_time = Some(SystemTime::now() + self.duration);
+    }
+
+    /// Whether the time of the arena is up, a running arena is closed on the next tick.
+    pub fn is_over(&self) -> bool {
+        self.end_time.is_some_and(|end_time| SystemTime::now() >= end_time)
+    }
+
+    pub fn get_remaining_time(&self) -> Duration {
+        match self.end_time {
+            Some(end_time) => end_time.duration_since(SystemTime::now()).unwrap_or_default(),
+            None => self.duration,
+        }
+    }
+
+    /// Remaining time rounded up to whole minutes.
+    pub fn get_remaining_minutes(&self) -> u64 {
+        self.get_remaining_time().as_secs().div_ceil(60)
+    }
+
+    /// Scores a finished arena game, returns false if the game is not played in this arena.
+    pub fn record_result(&mut self, game: GameId, white: PlayerId, black: PlayerId, result: GameResult) -> bool {
+        if [white, black].iter().any(|&id| self.get_player(id).is_none_or(|player| player.game != Some(game))) {
+            return false;
+        }
+
+        for &(id, opponent, side) in [(white, black, Color::White), (black, white, Color::Black)].iter() {
+            let player = self.get_player_mut(id).unwrap();
+
+            player.add_result(result, side);
+            player.game = None;
+            player.last_opponent = Some(opponent);
+        }
+
+        true
+    }
+
+    pub fn start_game(&mut self, white: PlayerId, black: PlayerId, game: GameId) {
+        for (id, balance) in [(white, 1), (black, -1)].iter() {
+            let player = self.get_player_mut(*id).unwrap();
+            player.game = Some(game);
+            player.color_balance += balance;
+        }
+    }
+
+    /// Pairs the waiting players with the closest score, avoiding an immediate rematch unless there is nobody else.
+    /// The player who had white less often gets white.
+    pub fn pair_waiting(&self) -> Vec<(PlayerId, PlayerId)> {
+        let mut waiting: Vec<&ArenaPlayer> = self.players.iter().filter(|player| player.is_waiting()).collect();
+        waiting.sort_by_key(|player| std::cmp::Reverse(player.score));
+
+        let mut pairings = Vec::new();
+
+        while waiting.len() >= 2 {
+            let player = waiting.remove(0);
+            let index = waiting
+                .iter()
+                .position(|other| player.last_opponent != Some(other.user.id) && other.last_opponent != Some(player.user.id));
+
+            let opponent = match index {
+                Some(index) => waiting.remove(index),
+                None if waiting.len() == 1 => waiting.remove(0),
+                None => continue,
+            };
+
+            if player.color_balance <= opponent.color_balance {
+                pairings.push((player.user.id, opponent.user.id));
+            } else {
+                pairings.push((opponent.user.id, player.user.id));
+            }
+        }
+
+        pairings
+    }
+
+    /// Players by score, the one who needed fewer games first.
+    pub fn get_ranking(&self) -> Vec<&ArenaPlayer> {
+        let mut ranking: Vec<&ArenaPlayer> = self.players.iter().collect();
+        ranking.sort_by(|a, b| b.score.cmp(&a.score).then(a.results.len().cmp(&b.results.len())));
+
+        ranking
+    }
+
+    pub fn create_scoreboard(&self) -> String {
+        let mut message = format!("Arena #{} ({}), {} games, ", self.id, self.name, self.options);
+
+        match self.state {
+            ArenaState::Registration => message.push_str(&format!("lasts {} minutes once started.\n", self.duration.as_secs() / 60)),
+            ArenaState::Running => message.push_str(&format!("{} minutes left.\n", self.get_remaining_minutes())),
+            ArenaState::Finished => message.push_str("finished.\n"),
+        }
+
+        for (place, player) in self.get_ranking().iter().enumerate() {
+            message.push_str(&format!("{}. {}: {}", place + 1, player.user.username, player.score));
+
+            if !player.results.is_empty() {
+                let results: Vec<String> = player.results.iter().map(|points| points.to_string()).collect();
+                message.push_str(&format!(" ({})", results.join(" ")));
+            }
+
+            if player.is_on_fire() && self.state == ArenaState::Running {
+                message.push_str(" on fire");
+            }
+
+            if player.withdrawn {
+                message.push_str(" withdrawn");
+            }
+
+            message.push('\n');
+        }
+
+        if self.players.is_empty() {
+            message.push_str("Nobody joined yet.");
+        }
+
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arena_with_players() -> Arena {
+        let mut arena = Arena::new(1, "Test".to_string(), UserId(1), Duration::from_secs(600), GameOptions::default(), None);
+
+        for id in 1..=2 {
+            arena.players.push(ArenaPlayer::new(UserInfo {
+                id: UserId(id),
+                username: format!("player{}", id),
+                discriminator: "0000".to_string(),
+                avatar: None,
+            }));
+        }
+
+        arena.start();
+        arena
+    }
+
+    #[test]
+    fn aborted_games_do_not_score() {
+        let mut arena = arena_with_players();
+        let (white, black) = (UserId(1), UserId(2));
+
+        for game in 1..=STREAK_LENGTH as GameId {
+            arena.start_game(white, black, game);
+            assert!(arena.record_result(game, white, black, GameResult::Resignation(Color::Black)));
+        }
+
+        arena.start_game(white, black, 10);
+        assert!(arena.record_result(10, white, black, GameResult::Aborted));
+
+        for id in [white, black].iter() {
+            let player = arena.get_player(*id).unwrap();
+            assert!(player.is_waiting());
+            assert_eq!(player.results.len(), STREAK_LENGTH as usize);
+        }
+
+        let winner = arena.get_player(white).unwrap();
+        assert_eq!(winner.score, STREAK_LENGTH * WIN_POINTS);
+        assert!(winner.is_on_fire());
+        assert_eq!(arena.get_player(black).unwrap().score, 0);
+    }
+}
@@ -0,0 +1,104 @@
+use std::time::{Duration, SystemTime};
+
+use serenity::model::id::UserId;
+
+use crate::chess::board::Color;
+use crate::http::http_server::UserInfo;
+
+use super::game::{ColorPreference, GameOptions};
+use super::handicap::Odds;
+
+type PlayerId = UserId;
+
+pub const DEFAULT_CHALLENGE_EXPIRY: Duration = Duration::from_secs(30);
+pub const MAX_CHALLENGE_EXPIRY: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Error, Debug)]
+pub enum ChallengeError {
+    #[error("You cannot invite yourself")]
+    CannotChallengeSelf,
+    #[error("You already invited this user!")]
+    AlreadyChallenged,
+    #[error("There are no invites from this user.")]
+    NoSuchChallenge,
+    #[error("Invites cannot last longer than a week.")]
+    ExpiryTooLong,
+    #[error("Games with odds cannot be rated.")]
+    RatedOdds,
+    #[error("You have to play {0:?} to give {1}.")]
+    OddsColor(Color, Odds),
+    #[error("Time odds can only be given in live games.")]
+    TimeOddsWithoutClock,
+}
+
+/// An invitation of one player by another to a game with the given settings.
+#[derive(Clone)]
+pub struct Challenge {
+    pub inviter: UserInfo,
+    pub invitee: PlayerId,
+    pub options: GameOptions,
+    /// Colour the inviter wants to play.
+    pub color: ColorPreference,
+    /// Odds given by the inviter.
+    pub odds: Option<Odds>,
+    pub expiry: Duration,
+    pub creation_time: SystemTime,
+}
+
+impl Challenge {
+    pub fn new(inviter: UserInfo, invitee: PlayerId, options: GameOptions, color: ColorPreference, odds: Option<Odds>, expiry: Duration) -> Self {
+        Self {
+            inviter,
+            invitee,
+            options,
+            color,
+            odds,
+            expiry,
+            creation_time: SystemTime::now(),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.creation_time.elapsed().unwrap_or_default() >= self.expiry
+    }
+
+    pub fn get_expiry_time(&self) -> SystemTime {
+        self.creation_time + self.expiry
+    }
+
+    /// The colour the inviter plays, some odds decide it for them.
+    pub fn resolve_inviter_color(&self) -> Color {
+        match self.odds.and_then(|odds| odds.get_required_color()) {
+            Some(color) => color,
+            None => self.color.resolve(ColorPreference::Random),
+        }
+    }
+}
+
+/// Parses how long an invite stays open, written as `45s`, `10m` or `2h`.
+pub fn parse_expiry(s: &str) -> Option<Duration> {
+    let s = s.to_lowercase();
+    let unit = match s.chars().last()? {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        _ => return None,
+    };
+
+    match s[..s.len() - 1].parse::<u64>() {
+        Ok(amount) if amount > 0 => Some(Duration::from_secs(amount * unit)),
+        _ => None,
+    }
+}
+
+pub fn format_expiry(expiry: Duration) -> String {
+    let seconds = expiry.as_secs();
+
+    if seconds.is_multiple_of(60 * 60) {
+        format!("{} hours", seconds / (60 * 60))
+    } else if seconds.is_multiple_of(60) {
+        format!("{} minutes", seconds / 60)
+    } else {
+        format!("{} seconds", seconds)
+    }
+}
@@ -0,0 +1,200 @@
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::chess::board::Color;
+
+use super::game::TimeControl;
+
+/// Time one side of a live game has left.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct SideClock {
+    pub remaining: Duration,
+    /// Added after every move once the clock runs.
+    pub increment: Duration,
+}
+
+impl SideClock {
+    /// Only live time controls have a clock.
+    pub fn new(time_control: TimeControl) -> Option<Self> {
+        match time_control {
+            TimeControl::Live { initial, increment } => Some(Self {
+                remaining: Duration::from_secs(initial),
+                increment: Duration::from_secs(increment),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// The clocks of a live game, they only run once both sides made their first move.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct Clock {
+    pub white: SideClock,
+    pub black: SideClock,
+    /// Side whose clock runs.
+    pub turn: Color,
+    /// When the side to move got its turn, unset while the clock is stopped.
+    pub turn_start: Option<SystemTime>,
+    /// Ply the turn started at, a different ply means a move was made or taken back since.
+    pub ply: usize,
+}
+
+impl Clock {
+    /// Both sides need a live time control, they differ when one side gives time odds.
+    pub fn new(white: TimeControl, black: TimeControl, turn: Color, ply: usize) -> Option<Self> {
+        Some(Self {
+            white: SideClock::new(white)?,
+            black: SideClock::new(black)?,
+            turn,
+            turn_start: None,
+            ply,
+        })
+    }
+
+    pub fn get_side(&self, side: Color) -> &SideClock {
+        match side {
+            Color::White => &self.white,
+            Color::Black => &self.black,
+        }
+    }
+
+    fn get_side_mut(&mut self, side: Color) -> &mut SideClock {
+        match side {
+            Color::White => &mut self.white,
+            Color::Black => &mut self.black,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.turn_start.is_some()
+    }
+
+    /// Time the side has left, including the turn it is taking right now.
+    pub fn get_remaining_time(&self, side: Color) -> Duration {
+        let remaining = self.get_side(side).remaining;
+
+        match self.turn_start {
+            Some(start) if side == self.turn => remaining.saturating_sub(start.elapsed().unwrap_or_default()),
+            _ => remaining,
+        }
+    }
+
+    pub fn is_out_of_time(&self, side: Color) -> bool {
+        self.get_remaining_time(side) == Duration::from_secs(0)
+    }
+
+    /// Charges the side that moved for its turn and starts the turn of the side to move.
+    /// Returns false if the side that moved ran out of time before its move.
+    pub fn switch(&mut self, turn: Color, ply: usize) -> bool {
+        let mut in_time = true;
+
+        // Taking back moves restarts the turn without charging anyone
+        if ply == self.ply + 1 {
+            if let Some(start) = self.turn_start {
+                let spent = start.elapsed().unwrap_or_default();
+                let clock = self.get_side_mut(self.turn);
+
+                in_time = spent < clock.remaining;
+                clock.remaining = clock.remaining.saturating_sub(spent) + clock.increment;
+            }
+        }
+
+        self.turn = turn;
+        self.ply = ply;
+        self.turn_start = if ply >= 2 { Some(SystemTime::now()) } else { None };
+        in_time
+    }
+
+    /// Charges the side to move for its turn so far and stops the clock, done once the game ended.
+    pub fn stop(&mut self) {
+        if let Some(start) = self.turn_start.take() {
+            let spent = start.elapsed().unwrap_or_default();
+            let clock = self.get_side_mut(self.turn);
+
+            clock.remaining = clock.remaining.saturating_sub(spent);
+        }
+    }
+}
+
+/// Formats the time left like a chess clock shows it, `4:05`.
+pub fn format_clock_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::system::handicap::Handicap;
+
+    use super::*;
+
+    const BLITZ: TimeControl = TimeControl::Live { initial: 300, increment: 3 };
+
+    /// A clock in the middle of the game, white has been thinking for the given number of seconds.
+    fn running_clock(thinking: u64) -> Clock {
+        let mut clock = Clock::new(BLITZ, BLITZ, Color::White, 2).unwrap();
+        clock.turn_start = Some(SystemTime::now() - Duration::from_secs(thinking));
+        clock
+    }
+
+    #[test]
+    fn clocks_start_after_the_first_moves() {
+        let mut clock = Clock::new(BLITZ, BLITZ, Color::White, 0).unwrap();
+
+        assert!(clock.switch(Color::Black, 1));
+        assert!(!clock.is_running());
+        assert!(clock.switch(Color::White, 2));
+        assert!(clock.is_running());
+        assert_eq!(clock.get_remaining_time(Color::Black), Duration::from_secs(300), "first moves neither cost time nor add the increment");
+        assert!(Clock::new(TimeControl::Unlimited, TimeControl::Unlimited, Color::White, 0).is_none());
+    }
+
+    #[test]
+    fn time_odds_shorten_the_clock_of_the_giver() {
+        let handicap = Handicap::new("time-odds:1+0".parse().unwrap(), Color::Black);
+        let clock = Clock::new(handicap.get_time_control(Color::White, BLITZ), handicap.get_time_control(Color::Black, BLITZ), Color::White, 0).unwrap();
+
+        assert_eq!(clock.white.remaining, Duration::from_secs(300));
+        assert_eq!(clock.black.remaining, Duration::from_secs(60));
+        assert_eq!(clock.black.increment, Duration::from_secs(0));
+    }
+
+    #[test]
+    fn moves_cost_their_time_and_add_the_increment() {
+        let mut clock = running_clock(10);
+
+        assert!(clock.switch(Color::Black, 3));
+        assert_eq!(clock.get_remaining_time(Color::White).as_secs(), 292);
+        assert_eq!(clock.get_remaining_time(Color::Black).as_secs(), 299);
+    }
+
+    #[test]
+    fn moves_after_the_time_ran_out_lose() {
+        let clock = running_clock(301);
+        assert!(clock.is_out_of_time(Color::White));
+        assert!(!clock.is_out_of_time(Color::Black));
+
+        let mut clock = clock;
+        assert!(!clock.switch(Color::Black, 3));
+    }
+
+    #[test]
+    fn takebacks_cost_no_time() {
+        let mut clock = running_clock(10);
+
+        assert!(clock.switch(Color::Black, 1));
+        assert_eq!(clock.get_remaining_time(Color::White), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn stopped_clocks_keep_the_time_left() {
+        let mut clock = running_clock(60);
+        clock.stop();
+
+        assert!(!clock.is_running());
+        assert_eq!(clock.get_remaining_time(Color::White).as_secs(), 239);
+        assert_eq!(format_clock_time(clock.get_remaining_time(Color::White)), "3:59");
+    }
+}
@@ -0,0 +1,264 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serenity::model::id::UserId;
+
+use crate::chess::board::Color;
+use crate::chess::moves::{HistoryMove, MoveFailureReason};
+use crate::http::http_server::UserInfo;
+
+pub const DEFAULT_VOTING_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Error, Debug)]
+pub enum ConsultationError {
+    #[error("Both teams need at least one player.")]
+    EmptyTeam,
+    #[error("{0} is listed more than once.")]
+    DuplicatePlayer(String),
+    #[error("You have to play in one of the teams.")]
+    NotInTeam,
+    #[error("Team games cannot be rated.")]
+    Rated,
+    #[error("It is not your team's turn.")]
+    NotYourTurn,
+    #[error("{0}")]
+    Move(#[from] MoveFailureReason),
+}
+
+/// How a team settles on the move it plays.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Copy, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveSelection {
+    /// The move with the most votes is played, ties go to the move proposed first.
+    Vote,
+    /// The captain's proposal is played right away, the most voted move if the captain did not propose in time.
+    Captain,
+}
+
+impl Display for MoveSelection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveSelection::Vote => write!(f, "vote"),
+            MoveSelection::Captain => write!(f, "captain"),
+        }
+    }
+}
+
+impl FromStr for MoveSelection {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "vote" => Ok(MoveSelection::Vote),
+            "captain" => Ok(MoveSelection::Captain),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Something a team does as a whole instead of a single member doing it for everyone.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Copy, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TeamAction {
+    Resign,
+    OfferDraw,
+    OfferTakeback,
+}
+
+impl Display for TeamAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TeamAction::Resign => write!(f, "resign"),
+            TeamAction::OfferDraw => write!(f, "offer a draw"),
+            TeamAction::OfferTakeback => write!(f, "offer a takeback"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone)]
+pub struct ActionVote {
+    pub player: UserId,
+    pub action: TeamAction,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone)]
+pub struct Proposal {
+    pub player: UserId,
+    pub proposed_move: HistoryMove,
+    pub time: SystemTime,
+}
+
+/// The teams of a consultation game and the moves proposed for the side to move.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Consultation {
+    /// The first member of a team is its captain, who also represents the team as the player of its side.
+    pub white_team: Vec<UserInfo>,
+    pub black_team: Vec<UserInfo>,
+    pub selection: MoveSelection,
+    /// How long after the first proposal the team's move is chosen.
+    pub window: Duration,
+    pub proposals: Vec<Proposal>,
+    /// Votes of both teams for resigning or making an offer, they count for the same ply as the proposals.
+    #[serde(default)]
+    pub action_votes: Vec<ActionVote>,
+    /// Ply the proposals were made for, a different ply means a move was made or taken back since.
+    pub ply: usize,
+}
+
+impl Consultation {
+    pub fn new(white_team: Vec<UserInfo>, black_team: Vec<UserInfo>, selection: MoveSelection, window: Duration) -> Result<Self, ConsultationError> {
+        if white_team.is_empty() || black_team.is_empty() {
+            return Err(ConsultationError::EmptyTeam);
+        }
+
+        let members: Vec<&UserInfo> = white_team.iter().chain(black_team.iter()).collect();
+
+        for (index, member) in members.iter().enumerate() {
+            if members[..index].iter().any(|other| other.id == member.id) {
+                return Err(ConsultationError::DuplicatePlayer(member.username.clone()));
+            }
+        }
+
+        Ok(Self {
+            white_team,
+            black_team,
+            selection,
+            window,
+            proposals: Vec::new(),
+            action_votes: Vec::new(),
+            ply: 0,
+        })
+    }
+
+    pub fn get_team(&self, side: Color) -> &[UserInfo] {
+        match side {
+            Color::White => &self.white_team,
+            Color::Black => &self.black_team,
+        }
+    }
+
+    pub fn get_side_of_player(&self, player: UserId) -> Option<Color> {
+        if self.white_team.iter().any(|member| member.id == player) {
+            Some(Color::White)
+        } else if self.black_team.iter().any(|member| member.id == player) {
+            Some(Color::Black)
+        } else {
+            None
+        }
+    }
+
+    /// Proposals for the given ply, older ones no longer apply.
+    pub fn get_proposals(&self, ply: usize) -> &[Proposal] {
+        if self.ply == ply {
+            &self.proposals
+        } else {
+            &[]
+        }
+    }
+
+    /// Forgets the proposals and votes once the position changed.
+    fn start_ply(&mut self, ply: usize) {
+        if self.ply != ply {
+            self.proposals.clear();
+            self.action_votes.clear();
+            self.ply = ply;
+        }
+    }
+
+    /// Replaces the player's earlier proposal for the same ply.
+    pub fn propose(&mut self, player: UserId, proposed_move: HistoryMove, ply: usize) {
+        self.start_ply(ply);
+
+        self.proposals.retain(|proposal| proposal.player != player);
+        self.proposals.push(Proposal {
+            player,
+            proposed_move,
+            time: SystemTime::now(),
+        });
+    }
+
+    pub fn get_votes(&self, proposed_move: HistoryMove) -> usize {
+        self.proposals.iter().filter(|proposal| proposal.proposed_move == proposed_move).count()
+    }
+
+    /// Counts the player's vote for the action, returns true once the team decided on it like on a move,
+    /// by its captain or a majority of its members.
+    pub fn vote_action(&mut self, player: UserId, action: TeamAction, side: Color, ply: usize) -> bool {
+        self.start_ply(ply);
+
+        if !self.action_votes.iter().any(|vote| vote.player == player && vote.action == action) {
+            self.action_votes.push(ActionVote { player, action });
+        }
+
+        let team = match side {
+            Color::White => &self.white_team,
+            Color::Black => &self.black_team,
+        };
+        let is_member = |vote: &ActionVote| team.iter().any(|member| member.id == vote.player);
+        let votes = self.action_votes.iter().filter(|vote| vote.action == action && is_member(vote)).count();
+
+        let decided = match self.selection {
+            MoveSelection::Captain => team[0].id == player,
+            MoveSelection::Vote => votes * 2 > team.len(),
+        };
+
+        if decided {
+            self.action_votes.retain(|vote| vote.action != action || !is_member(vote));
+        }
+
+        decided
+    }
+
+    /// The move with the most votes, the earliest proposal wins a tie.
+    fn get_most_voted(&self, ply: usize) -> Option<HistoryMove> {
+        let mut best: Option<(HistoryMove, usize)> = None;
+
+        for proposal in self.get_proposals(ply) {
+            let votes = self.get_votes(proposal.proposed_move);
+
+            if best.is_none_or(|(_, most)| votes > most) {
+                best = Some((proposal.proposed_move, votes));
+            }
+        }
+
+        best.map(|(proposed_move, _)| proposed_move)
+    }
+
+    /// The move the team settled on before the window ended, if any.
+    pub fn get_decision(&self, side: Color, ply: usize) -> Option<HistoryMove> {
+        let proposals = self.get_proposals(ply);
+        let team = self.get_team(side);
+
+        match self.selection {
+            MoveSelection::Captain => proposals.iter().find(|proposal| proposal.player == team[0].id).map(|proposal| proposal.proposed_move),
+            MoveSelection::Vote => {
+                let winner = self.get_most_voted(ply)?;
+
+                if proposals.len() == team.len() || self.get_votes(winner) * 2 > team.len() {
+                    Some(winner)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// When the team's move is chosen, counted from the first proposal.
+    pub fn get_window_end(&self, ply: usize) -> Option<SystemTime> {
+        self.get_proposals(ply).iter().map(|proposal| proposal.time).min().map(|time| time + self.window)
+    }
+
+    /// The move played once the window ended.
+    pub fn get_expired_decision(&self, ply: usize) -> Option<HistoryMove> {
+        match self.get_window_end(ply) {
+            Some(end) if SystemTime::now() >= end => self.get_most_voted(ply),
+            _ => None,
+        }
+    }
+
+    pub fn get_window_timestamp(&self, ply: usize) -> Option<u64> {
+        self.get_window_end(ply).map(|end| end.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs())
+    }
+}
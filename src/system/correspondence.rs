@@ -0,0 +1,43 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+pub const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Players are reminded once this much time is left, or after half of their time if that comes later.
+pub const REMINDER_BEFORE: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// When the side to move in a correspondence game runs out of time.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct MoveDeadline {
+    pub time: SystemTime,
+    /// Ply the deadline was set for, a different ply means a move was made or taken back since.
+    pub ply: usize,
+    pub reminded: bool,
+}
+
+impl MoveDeadline {
+    pub fn new(days_per_move: u32, ply: usize) -> Self {
+        Self {
+            time: SystemTime::now() + DAY * days_per_move,
+            ply,
+            reminded: false,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        SystemTime::now() >= self.time
+    }
+
+    pub fn get_remaining_time(&self) -> Duration {
+        self.time.duration_since(SystemTime::now()).unwrap_or_default()
+    }
+
+    pub fn needs_reminder(&self, days_per_move: u32) -> bool {
+        !self.reminded && self.get_remaining_time() <= REMINDER_BEFORE.min(DAY * days_per_move / 2)
+    }
+
+    pub fn get_timestamp(&self) -> u64 {
+        self.time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+    }
+}
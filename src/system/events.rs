@@ -0,0 +1,68 @@
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::chess::board::Color;
+use crate::chess::game::GameResult;
+use crate::chess::moves::HistoryMove;
+
+use super::game::{Game, GameId};
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Offer {
+    Draw,
+    Takeback,
+}
+
+/// Something that happened in a game.
+#[derive(Copy, Clone)]
+pub enum GameEvent {
+    GameStarted,
+    MoveMade(HistoryMove),
+    /// The game was reverted to the given ply.
+    MovesTakenBack(usize),
+    /// A member of the given team proposed a move without the team deciding on it yet.
+    MoveProposed(Color),
+    OfferMade(Offer, Color),
+    /// An offer by the given side was declined by the opponent making a move.
+    OfferDeclined(Offer, Color),
+    GameEnded(GameResult),
+    ClockFlagged(Color),
+}
+
+impl GameEvent {
+    pub fn is_game_end(&self) -> bool {
+        matches!(self, GameEvent::GameEnded(_))
+    }
+}
+
+/// The events of a single change of a game, in the order they happened.
+pub type EventBatch = (GameId, Vec<GameEvent>);
+pub type GameEventReceiver = UnboundedReceiver<EventBatch>;
+
+pub fn create_event_channel() -> (UnboundedSender<EventBatch>, GameEventReceiver) {
+    unbounded_channel()
+}
+
+/// Publishes the events of one game, they are handed to the subscribers by `GameManager::dispatch_events`.
+#[derive(Clone)]
+pub struct GameEventSender {
+    game: GameId,
+    sender: UnboundedSender<EventBatch>,
+}
+
+impl GameEventSender {
+    pub fn new(game: GameId, sender: UnboundedSender<EventBatch>) -> Self {
+        Self { game, sender }
+    }
+
+    pub fn publish(&self, events: Vec<GameEvent>) {
+        if !events.is_empty() {
+            let _ = self.sender.send((self.game, events));
+        }
+    }
+}
+
+/// Reacts to the events of every game, registered with `GameManager::subscribe`.
+pub trait GameEventSubscriber: Send + Sync {
+    /// Called with a copy of the game as it is after the events, slow work has to be spawned so the next events are not held up.
+    fn notify(&self, game: &Game, events: &[GameEvent]);
+}
@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use serenity::http::Http;
+use serenity::model::channel::{Message, ReactionType};
+use serenity::model::id::{ChannelId, MessageId};
+use tokio::sync::RwLock;
+
+use crate::chess::board::Color;
+use crate::chess::game::GameResult;
+use crate::system::events::{GameEvent, GameEventSubscriber};
+use crate::system::rematch::{REMATCH_EMOJI, REMATCH_WINDOW};
+
+use super::{Game, GameManager};
+
+#[derive(Clone)]
+pub struct GameAnnouncer {
+    pub id: ChannelId,
+    ctx: Arc<Http>,
+}
+
+impl GameAnnouncer {
+    pub fn new(ctx: Arc<Http>, id: ChannelId) -> Self {
+        Self { id, ctx }
+    }
+
+    pub fn create_annoucement(&self, game: &Game, message: &mut String) {
+        if game.chess_game.result == Some(GameResult::Aborted) {
+            message.push_str(&format!(
+                "Game #{} between {} and {} was aborted, the first move was not made in time.",
+                game.id,
+                game.mention_side(Color::White),
+                game.mention_side(Color::Black)
+            ));
+        } else if let Some(result) = game.chess_game.result {
+            if let Some(simul) = game.simul {
+                message.push_str(&format!("Simul #{}, board {}: ", simul.simul, simul.board));
+            }
+
+            message.push_str("The game has concluded.\n");
+            message.push_str(&result.pretty_message());
+            message.push('\n');
+
+            if let Some(winner) = result.get_winner() {
+                message.push_str("Winner: ");
+                message.push_str(&game.mention_side(winner));
+                message.push_str(". Loser: ");
+                message.push_str(&game.mention_side(winner.get_opposite()));
+            } else {
+                message.push_str("The game was drawn. ");
+            }
+
+            if game.can_be_rematched() {
+                let score = game.get_match_score();
+
+                if score.games > 1 {
+                    message.push_str(&format!("\nMatch score: {}", score.describe(&game.white_player, &game.black_player)));
+                }
+
+                message.push_str(&format!("\nReact with {} within {} minutes for a rematch.", REMATCH_EMOJI, REMATCH_WINDOW.as_secs() / 60));
+            }
+        }
+    }
+
+    pub async fn announce(&self, str: String) -> serenity::Result<Message> {
+        self.id
+            .send_message(&self.ctx, |f| {
+                f.content(str);
+                f
+            })
+            .await
+    }
+
+    pub async fn react(&self, message: MessageId, emoji: &str) -> serenity::Result<()> {
+        self.id.create_reaction(&self.ctx, message, ReactionType::Unicode(emoji.to_string())).await
+    }
+
+    pub async fn edit(&self, message: MessageId, str: String) -> serenity::Result<Message> {
+        self.id
+            .edit_message(&self.ctx, message, |f| {
+                f.content(str);
+                f
+            })
+            .await
+    }
+}
+
+/// Announces the result of a game in the channel it was started from, offering a rematch by reacting to it.
+pub struct ResultAnnouncer {
+    manager: Arc<RwLock<GameManager>>,
+}
+
+impl ResultAnnouncer {
+    pub fn new(manager: Arc<RwLock<GameManager>>) -> Self {
+        Self { manager }
+    }
+}
+
+impl GameEventSubscriber for ResultAnnouncer {
+    fn notify(&self, game: &Game, events: &[GameEvent]) {
+        let announcer = match &game.announcer {
+            Some(announcer) if events.iter().any(GameEvent::is_game_end) => announcer.clone(),
+            _ => return,
+        };
+
+        let mut announcement = String::new();
+        announcer.create_annoucement(game, &mut announcement);
+
+        let rematch = game.can_be_rematched();
+        let manager = self.manager.clone();
+        let id = game.id;
+
+        tokio::spawn(async move {
+            let message = announcer.announce(announcement).await;
+
+            // Reacting to the result offers a rematch
+            if let (Ok(message), true) = (message, rematch) {
+                let _ = announcer.react(message.id, REMATCH_EMOJI).await;
+                manager.write().await.set_rematch_message(id, message.id);
+            }
+        });
+    }
+}
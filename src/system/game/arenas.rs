@@ -0,0 +1,257 @@
+use std::time::Duration;
+
+use serenity::model::id::MessageId;
+use serenity::model::misc::Mentionable;
+
+use crate::chess::game::GameResult;
+use crate::http::http_server::UserInfo;
+use crate::system::arena::{Arena, ArenaError, ArenaId, ArenaPlayer, ArenaState};
+
+use super::{Game, GameAnnouncer, GameManager, GameOptions, PlayerId};
+
+impl GameManager {
+    pub fn create_arena(&mut self, name: String, organizer: PlayerId, duration: Duration, options: GameOptions, announcer: Option<GameAnnouncer>) -> &Arena {
+        let arena = Arena::new(self.next_arena_id, name, organizer, duration, options, announcer);
+        self.next_arena_id += 1;
+
+        GameManager::save_arena(&self.storage, &arena);
+        self.arenas.push(arena);
+
+        self.arenas.last().unwrap()
+    }
+
+    pub fn get_arena(&mut self, id: ArenaId) -> Option<&Arena> {
+        self.tick();
+
+        self.arenas.iter().find(|arena| arena.id == id)
+    }
+
+    pub fn get_arenas(&mut self) -> &[Arena] {
+        self.tick();
+
+        &self.arenas
+    }
+
+    fn get_arena_index(&self, id: ArenaId) -> Result<usize, ArenaError> {
+        self.arenas.iter().position(|arena| arena.id == id).ok_or(ArenaError::NoSuchArena(id))
+    }
+
+    /// Adds the player to the arena, or brings them back after withdrawing. Running arenas pair them right away.
+    pub fn join_arena(&mut self, id: ArenaId, user: UserInfo) -> Result<&Arena, ArenaError> {
+        self.tick();
+
+        let index = self.get_arena_index(id)?;
+        let arena = &mut self.arenas[index];
+
+        if arena.state == ArenaState::Finished {
+            return Err(ArenaError::ArenaFinished);
+        }
+
+        match arena.players.iter_mut().find(|player| player.user.id == user.id) {
+            Some(player) if player.withdrawn => player.withdrawn = false,
+            Some(_) => return Err(ArenaError::AlreadyJoined),
+            None => arena.players.push(ArenaPlayer::new(user)),
+        }
+
+        self.pair_arena(index);
+
+        let arena = &self.arenas[index];
+        GameManager::save_arena(&self.storage, arena);
+        GameManager::update_scoreboard(arena);
+        Ok(arena)
+    }
+
+    /// Stops pairing the player, a game they are playing still counts.
+    pub fn leave_arena(&mut self, id: ArenaId, user: PlayerId) -> Result<&Arena, ArenaError> {
+        self.tick();
+
+        let index = self.get_arena_index(id)?;
+        let arena = &mut self.arenas[index];
+
+        if arena.state == ArenaState::Finished {
+            return Err(ArenaError::ArenaFinished);
+        }
+
+        match arena.players.iter().position(|player| player.user.id == user && !player.withdrawn) {
+            Some(position) if arena.state == ArenaState::Registration => {
+                arena.players.remove(position);
+            }
+            Some(position) => arena.players[position].withdrawn = true,
+            None => return Err(ArenaError::NotJoined),
+        }
+
+        GameManager::save_arena(&self.storage, arena);
+        GameManager::update_scoreboard(arena);
+        Ok(arena)
+    }
+
+    /// Starts the clock of the arena, pairs the players and posts the scoreboard.
+    pub fn start_arena(&mut self, id: ArenaId, user: PlayerId) -> Result<&Arena, ArenaError> {
+        let index = self.get_arena_index(id)?;
+        let arena = &mut self.arenas[index];
+
+        if arena.organizer != user {
+            return Err(ArenaError::NotOrganizer);
+        }
+
+        if arena.state != ArenaState::Registration {
+            return Err(ArenaError::AlreadyStarted);
+        }
+
+        arena.start();
+        self.pair_arena(index);
+
+        let arena = &self.arenas[index];
+        GameManager::save_arena(&self.storage, arena);
+
+        if let (Some(announcer), Some(manager)) = (arena.announcer.clone(), self.self_ref.clone()) {
+            let scoreboard = arena.create_scoreboard();
+
+            tokio::spawn(async move {
+                if let Ok(message) = announcer.announce(scoreboard).await {
+                    manager.write().await.set_arena_scoreboard(id, message.id);
+                }
+            });
+        }
+
+        Ok(arena)
+    }
+
+    fn set_arena_scoreboard(&mut self, id: ArenaId, message: MessageId) {
+        if let Some(arena) = self.arenas.iter_mut().find(|arena| arena.id == id) {
+            arena.scoreboard = Some(message);
+
+            GameManager::save_arena(&self.storage, arena);
+            GameManager::update_scoreboard(arena);
+        }
+    }
+
+    /// Creates games for the waiting players of a running arena.
+    fn pair_arena(&mut self, index: usize) {
+        if self.arenas[index].state != ArenaState::Running || self.arenas[index].is_over() {
+            return;
+        }
+
+        let pairings = self.arenas[index].pair_waiting();
+
+        if pairings.is_empty() {
+            return;
+        }
+
+        let (id, options, announcer) = {
+            let arena = &self.arenas[index];
+            (arena.id, arena.options, arena.announcer.clone())
+        };
+
+        let mut message = format!("Arena #{}:\n", id);
+
+        for (white, black) in pairings {
+            let white_player = self.arenas[index].get_player(white).unwrap().user.clone();
+            let black_player = self.arenas[index].get_player(black).unwrap().user.clone();
+
+            let mut game = self.set_up_game(white_player, black_player, announcer.clone(), options).unwrap();
+            let game_id = game.id;
+            game.arena = Some(id);
+            self.start_game(game);
+
+            self.arenas[index].start_game(white, black, game_id);
+
+            message.push_str(&format!("{} - {} (game #{})\n", white.mention(), black.mention(), game_id));
+        }
+
+        let arena = &self.arenas[index];
+
+        if let Some(announcer) = arena.announcer.clone() {
+            tokio::spawn(async move {
+                let _ = announcer.announce(message).await;
+            });
+        }
+
+        GameManager::save_arena(&self.storage, arena);
+    }
+
+    /// Ends the arena, games that are still running are drawn without affecting ratings or the scores.
+    fn close_arena(&mut self, index: usize) {
+        let arena = &mut self.arenas[index];
+        arena.state = ArenaState::Finished;
+
+        for player in arena.players.iter_mut() {
+            player.game = None;
+        }
+
+        let id = arena.id;
+        let mut unfinished = 0;
+
+        for game in self.games.values() {
+            let mut game = game.lock().unwrap();
+
+            if game.arena != Some(id) {
+                continue;
+            }
+
+            game.options.rated = false;
+
+            if game.chess_game.draw().is_ok() {
+                unfinished += 1;
+            }
+        }
+
+        let arena = &self.arenas[index];
+        let mut message = format!("Arena #{} ({}) has finished!\n", arena.id, arena.name);
+
+        for (place, player) in arena.get_ranking().iter().take(3).enumerate() {
+            message.push_str(&format!("{}. {}: {} points\n", place + 1, player.user.id.mention(), player.score));
+        }
+
+        if unfinished > 0 {
+            message.push_str(&format!("{} unfinished games were ended as unrated draws.", unfinished));
+        }
+
+        if let Some(announcer) = arena.announcer.clone() {
+            tokio::spawn(async move {
+                let _ = announcer.announce(message).await;
+            });
+        }
+
+        GameManager::save_arena(&self.storage, arena);
+        GameManager::update_scoreboard(arena);
+    }
+
+    fn update_scoreboard(arena: &Arena) {
+        if let (Some(announcer), Some(scoreboard)) = (arena.announcer.clone(), arena.scoreboard) {
+            let content = arena.create_scoreboard();
+
+            tokio::spawn(async move {
+                let _ = announcer.edit(scoreboard, content).await;
+            });
+        }
+    }
+
+    /// Returns the arena of the game if its scores changed.
+    pub(super) fn record_arena_result(&mut self, game: &Game, result: GameResult) -> Option<ArenaId> {
+        // Games ending after the time is up do not count, like the ones still running when the arena is closed
+        let arena = game
+            .arena
+            .and_then(|id| self.arenas.iter_mut().find(|arena| arena.id == id && arena.state == ArenaState::Running && !arena.is_over()))?;
+
+        Some(arena.id).filter(|_| arena.record_result(game.id, game.white_player.id, game.black_player.id, result))
+    }
+
+    /// Pairs the players of the arenas whose games ended again.
+    pub(super) fn pair_arenas(&mut self, ids: Vec<ArenaId>) {
+        for id in ids {
+            let index = self.arenas.iter().position(|arena| arena.id == id).unwrap();
+
+            self.pair_arena(index);
+            GameManager::update_scoreboard(&self.arenas[index]);
+        }
+    }
+
+    pub(super) fn close_finished_arenas(&mut self) {
+        for index in 0..self.arenas.len() {
+            if self.arenas[index].state == ArenaState::Running && self.arenas[index].is_over() {
+                self.close_arena(index);
+            }
+        }
+    }
+}
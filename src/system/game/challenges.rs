@@ -0,0 +1,94 @@
+use crate::chess::board::Color;
+use crate::http::http_server::UserInfo;
+use crate::system::challenge::{Challenge, ChallengeError, MAX_CHALLENGE_EXPIRY};
+use crate::system::handicap::Handicap;
+
+use super::{ColorPreference, GameAnnouncer, GameManager, PlayerId, SharedGame, TimeControl};
+
+impl GameManager {
+    fn remove_expired_challenges(&mut self) {
+        self.challenges.retain(|x| !x.is_expired());
+    }
+
+    pub fn create_challenge(&mut self, challenge: Challenge) -> Result<&Challenge, ChallengeError> {
+        self.remove_expired_challenges();
+
+        if challenge.inviter.id == challenge.invitee {
+            return Err(ChallengeError::CannotChallengeSelf);
+        }
+
+        if challenge.expiry > MAX_CHALLENGE_EXPIRY {
+            return Err(ChallengeError::ExpiryTooLong);
+        }
+
+        if self.get_challenge(challenge.invitee, challenge.inviter.id).is_some() {
+            return Err(ChallengeError::AlreadyChallenged);
+        }
+
+        if let Some(odds) = challenge.odds {
+            if challenge.options.rated {
+                return Err(ChallengeError::RatedOdds);
+            }
+
+            if !odds.is_material() && !matches!(challenge.options.time_control, TimeControl::Live { .. }) {
+                return Err(ChallengeError::TimeOddsWithoutClock);
+            }
+
+            match odds.get_required_color() {
+                Some(color) if challenge.color != ColorPreference::Random && challenge.color.resolve(ColorPreference::Random) != color => {
+                    return Err(ChallengeError::OddsColor(color, odds));
+                }
+                _ => {}
+            }
+        }
+
+        self.challenges.push(challenge);
+        Ok(self.challenges.last().unwrap())
+    }
+
+    pub fn get_challenge(&self, invitee: PlayerId, inviter: PlayerId) -> Option<&Challenge> {
+        self.challenges
+            .iter()
+            .find(|challenge| challenge.invitee == invitee && challenge.inviter.id == inviter && !challenge.is_expired())
+    }
+
+    /// Open challenges the user either sent or received.
+    pub fn get_challenges_of(&mut self, user: PlayerId) -> Vec<&Challenge> {
+        self.remove_expired_challenges();
+
+        self.challenges.iter().filter(|challenge| challenge.invitee == user || challenge.inviter.id == user).collect()
+    }
+
+    /// Removes the challenge without starting a game, either the invitee declines it or the inviter takes it back.
+    pub fn remove_challenge(&mut self, invitee: PlayerId, inviter: PlayerId) -> Result<Challenge, ChallengeError> {
+        self.remove_expired_challenges();
+
+        let index = self
+            .challenges
+            .iter()
+            .position(|challenge| challenge.invitee == invitee && challenge.inviter.id == inviter)
+            .ok_or(ChallengeError::NoSuchChallenge)?;
+
+        Ok(self.challenges.remove(index))
+    }
+
+    /// Starts the game of a challenge, the inviter gets the colour they asked for and gives the odds, if any.
+    pub fn accept_challenge(&mut self, invitee: UserInfo, inviter: PlayerId, announcer: Option<GameAnnouncer>) -> Result<SharedGame, ChallengeError> {
+        let challenge = self.remove_challenge(invitee.id, inviter)?;
+        let inviter_color = challenge.resolve_inviter_color();
+
+        let (white_player, black_player) = match inviter_color {
+            Color::White => (challenge.inviter, invitee),
+            Color::Black => (invitee, challenge.inviter),
+        };
+
+        // Challenging yourself is rejected when the challenge is created
+        let mut game = self.set_up_game(white_player, black_player, announcer, challenge.options).unwrap();
+
+        if let Some(odds) = challenge.odds {
+            game.set_handicap(Handicap::new(odds, inviter_color));
+        }
+
+        Ok(self.start_game(game))
+    }
+}
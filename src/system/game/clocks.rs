@@ -0,0 +1,19 @@
+use super::GameManager;
+
+impl GameManager {
+    /// Ends live games whose side to move ran out of time.
+    pub(super) fn check_clocks(&self) {
+        for game in self.games.values() {
+            let mut game = game.lock().unwrap();
+
+            // A move may have been made since the last events were handled
+            game.update_clock();
+
+            let side = game.chess_game.state.current_turn;
+
+            if game.clock.is_some_and(|clock| clock.is_running() && clock.is_out_of_time(side)) {
+                let _ = game.chess_game.flag(side);
+            }
+        }
+    }
+}
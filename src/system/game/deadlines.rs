@@ -0,0 +1,49 @@
+use super::{GameManager, PlayerId, TimeControl};
+
+impl GameManager {
+    /// Ends correspondence games whose side to move missed the deadline and reminds players whose deadline is close.
+    pub(super) fn check_deadlines(&self) {
+        for game in self.games.values() {
+            let mut game = game.lock().unwrap();
+            let game = &mut *game;
+            let (deadline, days_per_move) = match (game.deadline.as_mut(), game.options.time_control) {
+                (Some(deadline), TimeControl::Correspondence { days_per_move }) if game.chess_game.result.is_none() => (deadline, days_per_move),
+                _ => continue,
+            };
+
+            let side = game.chess_game.state.current_turn;
+
+            if deadline.is_expired() {
+                let _ = game.chess_game.flag(side);
+                continue;
+            }
+
+            if !deadline.needs_reminder(days_per_move) {
+                continue;
+            }
+
+            deadline.reminded = true;
+
+            let message = format!(
+                "Reminder: you have {} hours left to make your move in game #{} against {}.",
+                deadline.get_remaining_time().as_secs() / 3600,
+                game.id,
+                game.mention_side(side.get_opposite())
+            );
+
+            GameManager::save_game(&self.storage, game);
+
+            if let Some(http) = self.http.clone() {
+                let players: Vec<PlayerId> = game.get_players_by_side(side).iter().map(|player| player.id).collect();
+
+                tokio::spawn(async move {
+                    for player in players {
+                        if let Ok(channel) = player.create_dm_channel(&http).await {
+                            let _ = channel.say(&http, &message).await;
+                        }
+                    }
+                });
+            }
+        }
+    }
+}
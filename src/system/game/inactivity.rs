@@ -0,0 +1,73 @@
+use crate::system::abandonment::{get_inactivity, AbandonmentError, Inactivity};
+
+use super::{GameManager, GameSelector, PlayerId, SharedGame};
+
+impl GameManager {
+    /// Aborts games that never started, offers the win to players whose opponent stopped moving and forfeits games left for too long.
+    pub(super) fn check_abandonment(&self) {
+        for game in self.games.values() {
+            let mut game = game.lock().unwrap();
+
+            // A move may have been made since the last events were handled
+            game.update_activity();
+
+            let side = game.chess_game.state.current_turn;
+
+            match get_inactivity(&self.abandonment, &game) {
+                Inactivity::Active => {}
+                // Events pair their games themselves, someone who never shows up loses instead
+                Inactivity::Abort if game.tournament.is_none() && game.arena.is_none() && game.simul.is_none() => {
+                    let _ = game.chess_game.abort();
+                }
+                Inactivity::Abort | Inactivity::Forfeit => {
+                    let _ = game.chess_game.abandon(side);
+                }
+                Inactivity::Claimable if !game.activity.claimable => {
+                    game.activity.claimable = true;
+
+                    GameManager::save_game(&self.storage, &game);
+                    GameManager::notify_about(&self.web_sockets, &game);
+
+                    if let Some(announcer) = game.announcer.clone() {
+                        let message = format!(
+                            "{}, {} has not moved in game #{} for {} minutes. You can claim the win now, otherwise the game is forfeited after {} minutes.",
+                            game.mention_side(side.get_opposite()),
+                            game.mention_side(side),
+                            game.id,
+                            self.abandonment.claim_after,
+                            self.abandonment.forfeit_after
+                        );
+
+                        tokio::spawn(async move {
+                            let _ = announcer.announce(message).await;
+                        });
+                    }
+                }
+                Inactivity::Claimable => {}
+            }
+        }
+    }
+
+    /// Lets the waiting player win a game their opponent stopped playing.
+    pub fn claim_win(&self, player: PlayerId, selector: Option<GameSelector>) -> Result<SharedGame, AbandonmentError> {
+        let shared = self.find_game(player, selector)?;
+
+        {
+            let mut game = shared.lock().unwrap();
+            game.update_activity();
+
+            let side = game.chess_game.state.current_turn;
+
+            if game.get_side_of_player(player) == Some(side) {
+                return Err(AbandonmentError::YourTurn(game.id));
+            }
+
+            match get_inactivity(&self.abandonment, &game) {
+                Inactivity::Claimable | Inactivity::Forfeit if game.chess_game.abandon(side).is_ok() => {}
+                _ => return Err(AbandonmentError::NotAbandoned(game.id)),
+            }
+        }
+
+        Ok(shared)
+    }
+}
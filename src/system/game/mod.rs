@@ -0,0 +1,787 @@
+mod announcements;
+mod arenas;
+mod challenges;
+mod clocks;
+mod deadlines;
+mod inactivity;
+mod options;
+mod rematches;
+mod seeks;
+mod simuls;
+mod spectators;
+mod tournaments;
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use serenity::http::Http;
+use serenity::model::id::UserId;
+use serenity::model::misc::Mentionable;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::RwLock;
+
+use crate::chess::board::Board;
+use crate::chess::board::Color;
+use crate::chess::game::{Game as ChessGame, GameResult, GameState};
+use crate::chess::moves::NewMove;
+use crate::config::AbandonmentConfig;
+use crate::http::http_server::UserInfo;
+use crate::http::web_socket::SocketRegistry;
+
+use super::abandonment::Activity;
+use super::archive::{ArchivedGame, GameArchive};
+use super::arena::{Arena, ArenaId};
+use super::challenge::Challenge;
+use super::clock::Clock;
+use super::consultation::{Consultation, ConsultationError, MoveSelection, TeamAction};
+use super::correspondence::MoveDeadline;
+use super::events::{create_event_channel, EventBatch, GameEvent, GameEventReceiver, GameEventSender, GameEventSubscriber};
+use super::handicap::Handicap;
+use super::matchmaking::SeekList;
+use super::rating::{PlayerRatings, Rating, RatingPool, RatingTable};
+use super::rematch::{MatchScore, Rematch};
+use super::simul::{Simul, SimulGame, SimulId};
+use super::stats::{get_rating_leaderboard, RatingRanking};
+use super::storage::{GameStorage, StorageError, StorageWriter};
+use super::tournament::{Tournament, TournamentGame, TournamentId};
+
+pub use announcements::{GameAnnouncer, ResultAnnouncer};
+pub use options::{ColorPreference, GameOptions, TimeCategory, TimeControl, Variant};
+pub use rematches::RematchOutcome;
+pub use seeks::SeekOutcome;
+pub use spectators::{GameWatcher, WatcherNotifier};
+
+type PlayerId = UserId;
+pub type GameId = u64;
+/// Every game has its own lock, so players of different games never wait for each other.
+pub type SharedGame = Arc<Mutex<Game>>;
+
+/// How often time based events, like the end of an arena or a missed correspondence deadline, are checked.
+pub const TICK_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+pub struct Game {
+    pub id: GameId,
+    pub white_player: UserInfo,
+    pub black_player: UserInfo,
+    pub chess_game: ChessGame,
+    pub announcer: Option<GameAnnouncer>,
+    pub start_time: SystemTime,
+    pub options: GameOptions,
+    /// Ratings of the players in the game's pool when it started.
+    pub white_rating: Rating,
+    pub black_rating: Rating,
+    pub tournament: Option<TournamentGame>,
+    pub arena: Option<ArenaId>,
+    pub simul: Option<SimulGame>,
+    /// Only set for correspondence games.
+    pub deadline: Option<MoveDeadline>,
+    /// Only set for live games.
+    pub clock: Option<Clock>,
+    /// Score of the series before this game, set for rematches.
+    pub score: Option<MatchScore>,
+    pub activity: Activity,
+    pub handicap: Option<Handicap>,
+    /// Only set for team games, the players of the game are the captains of the teams.
+    pub consultation: Option<Consultation>,
+    /// Users watching through the websocket, once for every session.
+    pub spectators: Vec<UserInfo>,
+    pub watchers: Vec<GameWatcher>,
+    /// Set once the subscribers were told about the end of the game, only then it is archived.
+    pub concluded: bool,
+}
+
+impl Game {
+    pub fn new(id: GameId, white_player: UserInfo, black_player: UserInfo, chess_game: ChessGame) -> Self {
+        let activity = Activity::new(chess_game.get_ply());
+
+        Self {
+            id,
+            white_player,
+            black_player,
+            chess_game,
+            announcer: None,
+            start_time: SystemTime::now(),
+            options: GameOptions::default(),
+            white_rating: Rating::default(),
+            black_rating: Rating::default(),
+            tournament: None,
+            arena: None,
+            simul: None,
+            deadline: None,
+            clock: None,
+            score: None,
+            activity,
+            handicap: None,
+            consultation: None,
+            spectators: Vec::new(),
+            watchers: Vec::new(),
+            concluded: false,
+        }
+    }
+
+    pub fn get_side_of_player(&self, player_id: PlayerId) -> Option<Color> {
+        if let Some(consultation) = &self.consultation {
+            return consultation.get_side_of_player(player_id);
+        }
+
+        if self.white_player.id == player_id {
+            Some(Color::White)
+        } else if self.black_player.id == player_id {
+            Some(Color::Black)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_player_id_by_side(&self, side: Color) -> PlayerId {
+        match side {
+            Color::White => self.white_player.id,
+            Color::Black => self.black_player.id,
+        }
+    }
+
+    /// Everyone playing the side, the whole team in team games.
+    pub fn get_players_by_side(&self, side: Color) -> Vec<&UserInfo> {
+        match (&self.consultation, side) {
+            (Some(consultation), side) => consultation.get_team(side).iter().collect(),
+            (None, Color::White) => vec![&self.white_player],
+            (None, Color::Black) => vec![&self.black_player],
+        }
+    }
+
+    pub fn get_players(&self) -> Vec<&UserInfo> {
+        let mut players = self.get_players_by_side(Color::White);
+        players.extend(self.get_players_by_side(Color::Black));
+        players
+    }
+
+    pub fn mention_side(&self, side: Color) -> String {
+        self.get_players_by_side(side).iter().map(|player| player.id.mention()).collect::<Vec<String>>().join(", ")
+    }
+
+    pub fn is_player(&self, player_id: PlayerId) -> bool {
+        self.get_side_of_player(player_id).is_some()
+    }
+
+    pub fn can_be_watched_by(&self, user: PlayerId) -> bool {
+        !self.options.private || self.is_player(user)
+    }
+
+    pub fn get_rating_by_side(&self, side: Color) -> &Rating {
+        match side {
+            Color::White => &self.white_rating,
+            Color::Black => &self.black_rating,
+        }
+    }
+
+    /// Tournament, arena and simul games are paired by the event rather than rematched, team games are set up again.
+    pub fn can_be_rematched(&self) -> bool {
+        self.tournament.is_none() && self.arena.is_none() && self.simul.is_none() && self.consultation.is_none() && self.chess_game.result != Some(GameResult::Aborted)
+    }
+
+    /// Score of the series this game belongs to, including the game itself once it concluded.
+    pub fn get_match_score(&self) -> MatchScore {
+        let mut score = self.score.unwrap_or_else(|| MatchScore::new(self.white_player.id, self.black_player.id));
+
+        if let Some(result) = self.chess_game.result {
+            score.add_result(self.white_player.id, result);
+        }
+
+        score
+    }
+
+    /// Gives the side to move a new deadline in correspondence games once the ply changed, returns true if it did.
+    pub fn update_deadline(&mut self) -> bool {
+        let days_per_move = match self.options.time_control {
+            TimeControl::Correspondence { days_per_move } => days_per_move,
+            _ => return false,
+        };
+
+        let ply = self.chess_game.get_ply();
+
+        if self.chess_game.result.is_some() || self.deadline.is_some_and(|deadline| deadline.ply == ply) {
+            return false;
+        }
+
+        self.deadline = Some(MoveDeadline::new(days_per_move, ply));
+        true
+    }
+
+    /// Sets up the clocks of live games with the time odds, if any, only done before the first move.
+    pub fn set_up_clock(&mut self) {
+        let time_control = self.options.time_control;
+        let (white, black) = match self.handicap {
+            Some(handicap) => (handicap.get_time_control(Color::White, time_control), handicap.get_time_control(Color::Black, time_control)),
+            None => (time_control, time_control),
+        };
+
+        self.clock = Clock::new(white, black, self.chess_game.state.current_turn, self.chess_game.get_ply());
+    }
+
+    /// Switches the clocks of live games once the ply changed and stops them once the game ended, returns true if they changed.
+    /// The side that moved loses on time if its time ran out before the move.
+    pub fn update_clock(&mut self) -> bool {
+        let turn = self.chess_game.state.current_turn;
+        let ply = self.chess_game.get_ply();
+        let clock = match self.clock.as_mut() {
+            Some(clock) => clock,
+            None => return false,
+        };
+
+        let switched = clock.ply != ply;
+
+        if switched && !clock.switch(turn, ply) {
+            let _ = self.chess_game.flag(turn.get_opposite());
+        }
+
+        if self.chess_game.result.is_some() && clock.is_running() {
+            clock.stop();
+            return true;
+        }
+
+        switched
+    }
+
+    /// Gives the odds by starting the game over from a handicapped position, only done before the first move.
+    pub fn set_handicap(&mut self, handicap: Handicap) {
+        let mut board = Board::new();
+        board.setup_default_board();
+        handicap.apply(&mut board);
+
+        let events = self.chess_game.events.take();
+        self.chess_game = ChessGame::from_position(GameState::new(board, 0, Color::White), Vec::new());
+        self.chess_game.events = events;
+        self.handicap = Some(handicap);
+        self.set_up_clock();
+    }
+
+    /// Restarts the inactivity of the side to move once the ply changed, returns true if it did.
+    pub fn update_activity(&mut self) -> bool {
+        let ply = self.chess_game.get_ply();
+
+        if self.activity.ply == ply {
+            return false;
+        }
+
+        self.activity = Activity::new(ply);
+        true
+    }
+
+    /// Records the move a team member wants to play, it is made once the team decided on it. Returns true if it was made.
+    pub fn propose_move(&mut self, player: PlayerId, new_move: NewMove) -> Result<bool, ConsultationError> {
+        let side = self.get_side_of_player(player).ok_or(ConsultationError::NotInTeam)?;
+
+        if side != self.chess_game.state.current_turn {
+            return Err(ConsultationError::NotYourTurn);
+        }
+
+        let proposed_move = self.chess_game.check_move(new_move)?;
+        let ply = self.chess_game.get_ply();
+        let consultation = self.consultation.as_mut().ok_or(ConsultationError::NotInTeam)?;
+        consultation.propose(player, proposed_move, ply);
+
+        match consultation.get_decision(side, ply) {
+            Some(decided) => {
+                self.chess_game.make_move(decided.to_new_move())?;
+                Ok(true)
+            }
+            None => {
+                if let Some(events) = &self.chess_game.events {
+                    events.publish(vec![GameEvent::MoveProposed(side)]);
+                }
+
+                Ok(false)
+            }
+        }
+    }
+
+    /// Resigns or makes an offer for the player's side. In team games the team has to decide on it first,
+    /// until it did the vote is counted and nothing is done.
+    pub fn decide_action<F, R>(&mut self, player: PlayerId, action: TeamAction, function: F) -> Result<Option<R>, ConsultationError>
+    where
+        F: FnOnce(&mut ChessGame, Color) -> R,
+    {
+        let side = self.get_side_of_player(player).ok_or(ConsultationError::NotInTeam)?;
+        let ply = self.chess_game.get_ply();
+
+        if let Some(consultation) = &mut self.consultation {
+            if !consultation.vote_action(player, action, side, ply) {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(function(&mut self.chess_game, side)))
+    }
+
+    /// Plays the most voted move of a team whose time to decide ran out, returns true if it did.
+    pub fn decide_move(&mut self) -> bool {
+        let ply = self.chess_game.get_ply();
+        let decided = match &self.consultation {
+            Some(consultation) => consultation.get_expired_decision(ply),
+            None => None,
+        };
+
+        decided.is_some_and(|decided| self.chess_game.make_move(decided.to_new_move()).is_ok())
+    }
+
+    /// Everyone watching the game either on the web or on Discord, each user listed once.
+    pub fn get_spectators(&self) -> Vec<&UserInfo> {
+        let mut spectators: Vec<&UserInfo> = Vec::new();
+
+        for user in self.spectators.iter().chain(self.watchers.iter().map(|watcher| &watcher.user)) {
+            if !spectators.iter().any(|other| other.id == user.id) {
+                spectators.push(user);
+            }
+        }
+
+        spectators
+    }
+}
+
+/// Picks one of a player's games, either by its id (`#12`) or by mentioning the opponent.
+#[derive(Copy, Clone, Debug)]
+pub enum GameSelector {
+    Id(GameId),
+    User(PlayerId),
+}
+
+impl FromStr for GameSelector {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(id) = s.strip_prefix('#') {
+            return id.parse().map(GameSelector::Id).map_err(|_| ());
+        }
+
+        serenity::utils::parse_username(s).map(|id| GameSelector::User(UserId(id))).ok_or(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum GameLookupError {
+    #[error("No ongoing game found.")]
+    NotInGame,
+    #[error("There is no ongoing game #{0}.")]
+    NoSuchGame(GameId),
+    #[error("Game #{0} is not played by this player.")]
+    NotAPlayer(GameId),
+    #[error("There is no ongoing game against this player.")]
+    NoGameAgainst,
+    #[error("Game #{0} is private.")]
+    PrivateGame(GameId),
+    #[error("There are several ongoing games ({}). Select one with #<id> or by mentioning the opponent.", format_game_ids(.0))]
+    AmbiguousGame(Vec<GameId>),
+}
+
+fn format_game_ids(ids: &[GameId]) -> String {
+    ids.iter().map(|id| format!("#{}", id)).collect::<Vec<String>>().join(", ")
+}
+
+#[derive(Default)]
+pub struct GameManager {
+    games: HashMap<GameId, SharedGame>,
+    /// The ongoing games of every player, team members included, in the order they started.
+    player_games: HashMap<PlayerId, Vec<GameId>>,
+    archive: GameArchive,
+    ratings: RatingTable,
+    challenges: Vec<Challenge>,
+    seeks: SeekList,
+    rematches: Vec<Rematch>,
+    tournaments: Vec<Tournament>,
+    next_tournament_id: TournamentId,
+    arenas: Vec<Arena>,
+    next_arena_id: ArenaId,
+    simuls: Vec<Simul>,
+    next_simul_id: SimulId,
+    self_ref: Option<Arc<RwLock<GameManager>>>,
+    web_sockets: SocketRegistry,
+    storage: Option<StorageWriter>,
+    events: Option<UnboundedSender<EventBatch>>,
+    subscribers: Vec<Arc<dyn GameEventSubscriber>>,
+    abandonment: AbandonmentConfig,
+    http: Option<Arc<Http>>,
+    next_game_id: GameId,
+}
+
+impl GameManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn remove_concluded_games(&mut self) {
+        let mut ids: Vec<GameId> = self.games.iter().filter(|(_, game)| game.lock().unwrap().concluded).map(|(&id, _)| id).collect();
+        ids.sort_unstable();
+
+        let concluded: Vec<SharedGame> = ids.iter().filter_map(|id| self.games.remove(id)).collect();
+        let mut changed_arenas: Vec<ArenaId> = Vec::new();
+
+        for game in concluded.iter() {
+            let game = game.lock().unwrap();
+            let archived = ArchivedGame::from_game(&game);
+
+            for player in game.get_players() {
+                let games = self.player_games.entry(player.id).or_default();
+                games.retain(|&id| id != game.id);
+
+                self.web_sockets.set_player_games(player.id, games.clone());
+
+                if games.is_empty() {
+                    self.player_games.remove(&player.id);
+                }
+            }
+
+            if game.options.rated && self.ratings.apply_result(game.white_player.id, game.black_player.id, game.options.get_rating_pool(), archived.result) {
+                for player in [game.white_player.id, game.black_player.id].iter() {
+                    GameManager::save_ratings(&self.storage, self.ratings.get_player(*player).unwrap());
+                }
+            }
+
+            if let Some(storage) = &self.storage {
+                storage.archive_game(&archived);
+            }
+
+            self.record_tournament_result(&game, archived.result);
+            self.add_rematch(&game);
+
+            if let Some(arena) = self.record_arena_result(&game, archived.result) {
+                if !changed_arenas.contains(&arena) {
+                    changed_arenas.push(arena);
+                }
+            }
+
+            self.archive.add(archived);
+        }
+
+        for game in concluded.iter() {
+            self.record_simul_result(&game.lock().unwrap());
+        }
+
+        self.pair_arenas(changed_arenas);
+        self.start_finished_tournament_rounds();
+    }
+
+    /// Returns the events of all games, they have to be passed to `dispatch_events`.
+    pub fn manage_games(&mut self, self_ref: Arc<RwLock<GameManager>>) -> GameEventReceiver {
+        let (sender, receiver) = create_event_channel();
+
+        self.self_ref = Some(self_ref);
+        self.events = Some(sender);
+        receiver
+    }
+
+    pub fn set_abandonment_config(&mut self, config: AbandonmentConfig) {
+        self.abandonment = config;
+    }
+
+    pub fn subscribe(&mut self, subscriber: Box<dyn GameEventSubscriber>) {
+        self.subscribers.push(Arc::from(subscriber));
+    }
+
+    fn create_event_sender(&self, id: GameId) -> Option<GameEventSender> {
+        self.events.clone().map(|sender| GameEventSender::new(id, sender))
+    }
+
+    /// Hands the events published by the games to the subscribers, runs for as long as the bot does.
+    pub async fn dispatch_events(manager: Arc<RwLock<GameManager>>, mut receiver: GameEventReceiver) {
+        while let Some((id, events)) = receiver.recv().await {
+            let (game, subscribers) = match manager.read().await.prepare_dispatch(id) {
+                Some(dispatch) => dispatch,
+                None => continue,
+            };
+
+            // The subscribers get a copy of the game, so neither the manager nor the game stay locked while they run
+            for subscriber in subscribers.iter() {
+                subscriber.notify(&game, &events);
+            }
+
+            // Only archiving a concluded game needs the whole manager
+            if events.iter().any(GameEvent::is_game_end) {
+                manager.write().await.conclude_game(id);
+            }
+        }
+    }
+
+    /// Updates the game for its latest change and returns a copy of it together with the subscribers to notify.
+    fn prepare_dispatch(&self, id: GameId) -> Option<(Game, Vec<Arc<dyn GameEventSubscriber>>)> {
+        let mut game = self.games.get(&id)?.lock().unwrap();
+
+        game.update_deadline();
+        game.update_clock();
+        game.update_activity();
+
+        Some((game.clone(), self.subscribers.clone()))
+    }
+
+    /// Archives the game now that the subscribers were told about its end.
+    fn conclude_game(&mut self, id: GameId) {
+        if let Some(game) = self.games.get(&id) {
+            game.lock().unwrap().concluded = true;
+        }
+
+        self.remove_concluded_games();
+    }
+
+    /// Restores the games, the archive and the ratings the storage contains and saves every change through the writer from now on.
+    /// Records that can not be read are skipped and returned with the reason, only failing to read the storage itself is an error.
+    pub fn load_games(&mut self, storage: Arc<dyn GameStorage>, writer: StorageWriter, http: Arc<Http>) -> Result<Vec<String>, StorageError> {
+        let mut skipped = Vec::new();
+
+        for ratings in storage.load_ratings()?.into_records(&mut skipped) {
+            self.ratings.add(ratings);
+        }
+
+        for mut tournament in storage.load_tournaments()?.into_records(&mut skipped) {
+            tournament.announcer = tournament.announce_channel.map(|channel| GameAnnouncer::new(http.clone(), channel));
+
+            self.next_tournament_id = self.next_tournament_id.max(tournament.id + 1);
+            self.tournaments.push(tournament);
+        }
+
+        for mut arena in storage.load_arenas()?.into_records(&mut skipped) {
+            arena.announcer = arena.announce_channel.map(|channel| GameAnnouncer::new(http.clone(), channel));
+
+            self.next_arena_id = self.next_arena_id.max(arena.id + 1);
+            self.arenas.push(arena);
+        }
+
+        for mut simul in storage.load_simuls()?.into_records(&mut skipped) {
+            simul.announcer = simul.announce_channel.map(|channel| GameAnnouncer::new(http.clone(), channel));
+
+            self.next_simul_id = self.next_simul_id.max(simul.id + 1);
+            self.simuls.push(simul);
+        }
+
+        for archived in storage.load_archive()?.into_records(&mut skipped) {
+            self.next_game_id = self.next_game_id.max(archived.id + 1);
+            self.archive.add(archived);
+        }
+
+        for stored in storage.load_games()?.into_records(&mut skipped) {
+            let mut chess_game = match ChessGame::from_serialized(stored.chess_game) {
+                Ok(chess_game) => chess_game,
+                Err(why) => {
+                    skipped.push(format!("game {}: {}", stored.id, why));
+                    continue;
+                }
+            };
+            chess_game.events = self.create_event_sender(stored.id);
+
+            let mut game = Game::new(stored.id, stored.white_player, stored.black_player, chess_game);
+            game.announcer = stored.announce_channel.map(|channel| GameAnnouncer::new(http.clone(), channel));
+            game.start_time = stored.start_time;
+            game.options = stored.options;
+            game.white_rating = stored.white_rating;
+            game.black_rating = stored.black_rating;
+            game.tournament = stored.tournament;
+            game.arena = stored.arena;
+            game.simul = stored.simul;
+            game.deadline = stored.deadline;
+            game.clock = stored.clock;
+            game.score = stored.score;
+            game.activity = stored.activity;
+            game.handicap = stored.handicap;
+            game.consultation = stored.consultation;
+            // Games that ended just before a shutdown are archived right away
+            game.concluded = game.chess_game.result.is_some();
+            game.update_deadline();
+            game.update_clock();
+            game.update_activity();
+
+            self.next_game_id = self.next_game_id.max(stored.id + 1);
+            self.add_game(game);
+        }
+
+        self.storage = Some(writer);
+        self.http = Some(http);
+        self.remove_concluded_games();
+        Ok(skipped)
+    }
+
+    fn save_game(storage: &Option<StorageWriter>, game: &Game) {
+        if let Some(storage) = storage {
+            storage.save_game(game);
+        }
+    }
+
+    fn save_tournament(storage: &Option<StorageWriter>, tournament: &Tournament) {
+        if let Some(storage) = storage {
+            storage.save_tournament(tournament);
+        }
+    }
+
+    fn save_arena(storage: &Option<StorageWriter>, arena: &Arena) {
+        if let Some(storage) = storage {
+            storage.save_arena(arena);
+        }
+    }
+
+    fn save_simul(storage: &Option<StorageWriter>, simul: &Simul) {
+        if let Some(storage) = storage {
+            storage.save_simul(simul);
+        }
+    }
+
+    fn save_ratings(storage: &Option<StorageWriter>, ratings: &PlayerRatings) {
+        if let Some(storage) = storage {
+            storage.save_ratings(ratings);
+        }
+    }
+
+    pub fn create_game(&mut self, white_player: UserInfo, black_player: UserInfo, announcer: Option<GameAnnouncer>, options: GameOptions) -> Option<SharedGame> {
+        let game = self.set_up_game(white_player, black_player, announcer, options)?;
+
+        Some(self.start_game(game))
+    }
+
+    /// Creates a game without starting it, so the caller can finish setting it up first.
+    fn set_up_game(&mut self, white_player: UserInfo, black_player: UserInfo, announcer: Option<GameAnnouncer>, options: GameOptions) -> Option<Game> {
+        if white_player.id == black_player.id {
+            return None;
+        }
+
+        let mut game = Game::new(self.next_game_id, white_player, black_player, ChessGame::new());
+        game.announcer = announcer;
+        game.options = options;
+        game.white_rating = self.ratings.get_rating(game.white_player.id, options.get_rating_pool());
+        game.black_rating = self.ratings.get_rating(game.black_player.id, options.get_rating_pool());
+        game.chess_game.events = self.create_event_sender(game.id);
+        game.update_deadline();
+        game.set_up_clock();
+        self.next_game_id += 1;
+
+        Some(game)
+    }
+
+    fn start_game(&mut self, game: Game) -> SharedGame {
+        if let Some(events) = &game.chess_game.events {
+            events.publish(vec![GameEvent::GameStarted]);
+        }
+
+        self.add_game(game)
+    }
+
+    /// Indexes the game by its id and by its players.
+    fn add_game(&mut self, game: Game) -> SharedGame {
+        for player in game.get_players() {
+            let games = self.player_games.entry(player.id).or_default();
+            games.push(game.id);
+            self.web_sockets.set_player_games(player.id, games.clone());
+        }
+
+        let id = game.id;
+        let game = Arc::new(Mutex::new(game));
+        self.games.insert(id, game.clone());
+
+        game
+    }
+
+    /// Starts a casual game between two teams, the captains are the first members.
+    pub fn create_team_game(
+        &mut self,
+        white_team: Vec<UserInfo>,
+        black_team: Vec<UserInfo>,
+        selection: MoveSelection,
+        window: Duration,
+        announcer: Option<GameAnnouncer>,
+        options: GameOptions,
+    ) -> Result<SharedGame, ConsultationError> {
+        if options.rated {
+            return Err(ConsultationError::Rated);
+        }
+
+        let consultation = Consultation::new(white_team, black_team, selection, window)?;
+        let white_captain = consultation.white_team[0].clone();
+        let black_captain = consultation.black_team[0].clone();
+
+        // The whole teams are indexed, so the game is only started once they are set
+        let mut game = self.set_up_game(white_captain, black_captain, announcer, options).unwrap();
+        game.consultation = Some(consultation);
+
+        Ok(self.start_game(game))
+    }
+
+    pub fn get_game(&self, id: GameId) -> Option<SharedGame> {
+        self.games.get(&id).cloned()
+    }
+
+    pub fn get_game_ids_of(&self, player: PlayerId) -> Vec<GameId> {
+        self.player_games.get(&player).cloned().unwrap_or_default()
+    }
+
+    /// Resolves which of the player's games is meant. Without a selector the player must be in exactly one game.
+    pub fn find_game(&self, player: PlayerId, selector: Option<GameSelector>) -> Result<SharedGame, GameLookupError> {
+        let games = self.player_games.get(&player).map(Vec::as_slice).unwrap_or_default();
+
+        let id = match selector {
+            Some(GameSelector::Id(id)) => {
+                if !self.games.contains_key(&id) {
+                    return Err(GameLookupError::NoSuchGame(id));
+                }
+
+                if !games.contains(&id) {
+                    return Err(GameLookupError::NotAPlayer(id));
+                }
+
+                id
+            }
+            Some(GameSelector::User(opponent)) => {
+                let opponent_games = self.player_games.get(&opponent).map(Vec::as_slice).unwrap_or_default();
+
+                *games.iter().find(|id| player != opponent && opponent_games.contains(id)).ok_or(GameLookupError::NoGameAgainst)?
+            }
+            None => match games {
+                [] => return Err(GameLookupError::NotInGame),
+                [id] => *id,
+                _ => return Err(GameLookupError::AmbiguousGame(games.to_vec())),
+            },
+        };
+
+        Ok(self.games[&id].clone())
+    }
+
+    pub fn get_archive(&self) -> &GameArchive {
+        &self.archive
+    }
+
+    pub fn get_ratings(&self) -> &RatingTable {
+        &self.ratings
+    }
+
+    pub fn get_rating_leaderboard(&self, pool: RatingPool) -> Vec<RatingRanking> {
+        get_rating_leaderboard(&self.ratings, &self.archive, pool)
+    }
+
+    /// Handles everything that happens when time passes rather than when a player acts.
+    pub fn tick(&mut self) {
+        self.remove_concluded_games();
+
+        self.close_finished_arenas();
+
+        for game in self.games.values() {
+            game.lock().unwrap().decide_move();
+        }
+
+        self.check_deadlines();
+        self.check_clocks();
+        self.check_abandonment();
+    }
+
+    /// Calls `tick` every `TICK_INTERVAL`, runs for as long as the bot does.
+    pub async fn run_ticks(manager: Arc<RwLock<GameManager>>) {
+        loop {
+            tokio::time::delay_for(TICK_INTERVAL).await;
+            manager.write().await.tick();
+        }
+    }
+
+    pub fn get_sockets(&self) -> SocketRegistry {
+        self.web_sockets.clone()
+    }
+
+    fn notify_about(sockets: &SocketRegistry, game: &Game) {
+        sockets.send_state(game);
+    }
+}
@@ -0,0 +1,269 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::chess::board::Color;
+use crate::system::rating::RatingPool;
+
+/// Rough speed of a game, ratings are kept separately for each.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Copy, Clone, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeCategory {
+    Bullet,
+    Blitz,
+    Rapid,
+    Classical,
+    Correspondence,
+    #[default]
+    Unlimited,
+}
+
+impl TimeCategory {
+    pub const ALL: [TimeCategory; 6] = [
+        TimeCategory::Bullet,
+        TimeCategory::Blitz,
+        TimeCategory::Rapid,
+        TimeCategory::Classical,
+        TimeCategory::Correspondence,
+        TimeCategory::Unlimited,
+    ];
+}
+
+impl Display for TimeCategory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TimeCategory::Bullet => "bullet",
+            TimeCategory::Blitz => "blitz",
+            TimeCategory::Rapid => "rapid",
+            TimeCategory::Classical => "classical",
+            TimeCategory::Correspondence => "correspondence",
+            TimeCategory::Unlimited => "unlimited",
+        })
+    }
+}
+
+impl FromStr for TimeCategory {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TimeCategory::ALL.iter().copied().find(|category| category.to_string() == s.to_lowercase()).ok_or(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Copy, Clone, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Variant {
+    #[default]
+    Standard,
+}
+
+impl Display for Variant {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Variant::Standard => "standard",
+        })
+    }
+}
+
+impl FromStr for Variant {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "standard" | "chess" => Ok(Variant::Standard),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How much time the players get, written as `5+3` (minutes and increment in seconds), `3d` (days per move) or `unlimited`.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Copy, Clone, Debug, Default)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum TimeControl {
+    #[default]
+    Unlimited,
+    Live {
+        /// In seconds.
+        initial: u64,
+        /// In seconds, added after every move.
+        increment: u64,
+    },
+    Correspondence {
+        days_per_move: u32,
+    },
+}
+
+impl TimeControl {
+    /// Categorizes live games by their estimated duration, assuming 40 moves per player.
+    pub fn get_category(&self) -> TimeCategory {
+        match *self {
+            TimeControl::Unlimited => TimeCategory::Unlimited,
+            TimeControl::Correspondence { .. } => TimeCategory::Correspondence,
+            TimeControl::Live { initial, increment } => match initial + 40 * increment {
+                0..=179 => TimeCategory::Bullet,
+                180..=479 => TimeCategory::Blitz,
+                480..=1499 => TimeCategory::Rapid,
+                _ => TimeCategory::Classical,
+            },
+        }
+    }
+}
+
+impl Display for TimeControl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeControl::Unlimited => write!(f, "unlimited"),
+            TimeControl::Live { initial, increment } if initial % 60 == 0 => write!(f, "{}+{}", initial / 60, increment),
+            TimeControl::Live { initial, increment } => write!(f, "{}+{}", *initial as f64 / 60.0, increment),
+            TimeControl::Correspondence { days_per_move } => write!(f, "{}d", days_per_move),
+        }
+    }
+}
+
+impl FromStr for TimeControl {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+
+        if s == "unlimited" {
+            return Ok(TimeControl::Unlimited);
+        }
+
+        if let Some(days) = s.strip_suffix('d') {
+            return match days.parse() {
+                Ok(days_per_move) if days_per_move > 0 => Ok(TimeControl::Correspondence { days_per_move }),
+                _ => Err(()),
+            };
+        }
+
+        let (minutes, increment) = s.split_once('+').ok_or(())?;
+        let minutes = minutes.parse::<f64>().map_err(|_| ())?;
+        let increment = increment.parse::<u64>().map_err(|_| ())?;
+
+        if !minutes.is_finite() || minutes < 0.0 || (minutes == 0.0 && increment == 0) {
+            return Err(());
+        }
+
+        Ok(TimeControl::Live {
+            initial: (minutes * 60.0).round() as u64,
+            increment,
+        })
+    }
+}
+
+/// The colour a player asks for when setting up a game.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Copy, Clone, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorPreference {
+    White,
+    Black,
+    #[default]
+    Random,
+}
+
+impl ColorPreference {
+    /// Both players cannot get the colour they asked for.
+    pub fn conflicts_with(&self, other: ColorPreference) -> bool {
+        *self != ColorPreference::Random && *self == other
+    }
+
+    /// The colour of the player with this preference against an opponent with the other one.
+    pub fn resolve(&self, other: ColorPreference) -> Color {
+        match (self, other) {
+            (ColorPreference::White, _) => Color::White,
+            (ColorPreference::Black, _) => Color::Black,
+            (ColorPreference::Random, ColorPreference::White) => Color::Black,
+            (ColorPreference::Random, ColorPreference::Black) => Color::White,
+            (ColorPreference::Random, ColorPreference::Random) => {
+                if rand::random() {
+                    Color::White
+                } else {
+                    Color::Black
+                }
+            }
+        }
+    }
+}
+
+impl Display for ColorPreference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ColorPreference::White => "white",
+            ColorPreference::Black => "black",
+            ColorPreference::Random => "random",
+        })
+    }
+}
+
+impl FromStr for ColorPreference {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "white" | "w" => Ok(ColorPreference::White),
+            "black" | "b" => Ok(ColorPreference::Black),
+            "random" | "r" => Ok(ColorPreference::Random),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Settings chosen when a game is set up.
+#[derive(Serialize, Deserialize, Default, Copy, Clone, Debug)]
+#[serde(default)]
+pub struct GameOptions {
+    /// Private games can only be watched by their players.
+    pub private: bool,
+    /// Rated games change the players' ratings once they conclude.
+    pub rated: bool,
+    pub time_control: TimeControl,
+    pub variant: Variant,
+}
+
+impl GameOptions {
+    pub fn get_rating_pool(&self) -> RatingPool {
+        RatingPool {
+            category: self.time_control.get_category(),
+            variant: self.variant,
+        }
+    }
+
+    /// Parses a single option word, returns false if it is not one.
+    pub fn parse_option(&mut self, option: &str) -> bool {
+        match option.to_lowercase().as_str() {
+            "private" => self.private = true,
+            "rated" => self.rated = true,
+            "casual" => self.rated = false,
+            _ => {
+                if let Ok(time_control) = option.parse::<TimeControl>() {
+                    self.time_control = time_control;
+                } else if let Ok(variant) = option.parse::<Variant>() {
+                    self.variant = variant;
+                } else {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+}
+
+impl Display for GameOptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", if self.rated { "rated" } else { "casual" })?;
+
+        match self.time_control {
+            TimeControl::Unlimited => write!(f, " {}", self.get_rating_pool())?,
+            time_control => write!(f, " {} {}", self.get_rating_pool(), time_control)?,
+        }
+
+        if self.private {
+            write!(f, " (private)")?;
+        }
+
+        Ok(())
+    }
+}
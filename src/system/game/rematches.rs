@@ -0,0 +1,139 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use serenity::model::id::MessageId;
+
+use crate::http::proto::make_rematch;
+use crate::http::web_socket::{SocketRegistry, UpdateRematchMessage};
+use crate::system::handicap::Handicap;
+use crate::system::rematch::{Rematch, RematchError};
+
+use super::{Game, GameAnnouncer, GameId, GameManager, GameSelector, PlayerId, SharedGame};
+
+impl GameManager {
+    fn remove_expired_rematches(&mut self) {
+        self.rematches.retain(|rematch| !rematch.is_expired());
+    }
+
+    pub fn get_rematch(&mut self, game: GameId) -> Option<&Rematch> {
+        self.remove_expired_rematches();
+
+        self.rematches.iter().find(|rematch| rematch.game == game)
+    }
+
+    pub fn get_rematch_by_message(&mut self, message: MessageId) -> Option<GameId> {
+        self.remove_expired_rematches();
+
+        self.rematches.iter().find(|rematch| rematch.message == Some(message)).map(|rematch| rematch.game)
+    }
+
+    /// The player's most recent game that can still be rematched, optionally the one with the given id or opponent.
+    pub fn find_rematch(&mut self, player: PlayerId, selector: Option<GameSelector>) -> Result<GameId, RematchError> {
+        self.remove_expired_rematches();
+
+        let rematch = match selector {
+            Some(GameSelector::Id(id)) => {
+                let rematch = self.rematches.iter().find(|rematch| rematch.game == id).ok_or(RematchError::NoSuchRematch(id))?;
+
+                if !rematch.is_player(player) {
+                    return Err(RematchError::NotAPlayer(id));
+                }
+
+                rematch
+            }
+            Some(GameSelector::User(opponent)) => self
+                .rematches
+                .iter()
+                .rev()
+                .find(|rematch| rematch.is_player(player) && rematch.is_player(opponent) && player != opponent)
+                .ok_or(RematchError::NoRematch)?,
+            None => self.rematches.iter().rev().find(|rematch| rematch.is_player(player)).ok_or(RematchError::NoRematch)?,
+        };
+
+        Ok(rematch.game)
+    }
+
+    /// Offers a rematch of a concluded game, once both players did the new game starts with swapped colours.
+    pub fn offer_rematch(&mut self, game: GameId, player: PlayerId, announcer: Option<GameAnnouncer>) -> Result<RematchOutcome<'_>, RematchError> {
+        self.remove_expired_rematches();
+
+        let index = self.rematches.iter().position(|rematch| rematch.game == game).ok_or(RematchError::NoSuchRematch(game))?;
+        let rematch = &mut self.rematches[index];
+
+        if !rematch.is_player(player) {
+            return Err(RematchError::NotAPlayer(game));
+        }
+
+        if rematch.offers.contains(&player) {
+            return Err(RematchError::AlreadyOffered);
+        }
+
+        rematch.offers.push(player);
+
+        if rematch.offers.len() < 2 {
+            GameManager::notify_rematch(&self.web_sockets, rematch);
+            return Ok(RematchOutcome::Offered(&self.rematches[index]));
+        }
+
+        let rematch = self.rematches.remove(index);
+
+        // The same player keeps giving the odds, with the colours swapped unless the odds require one
+        let (white_player, black_player, handicap) = match rematch.handicap {
+            Some(handicap) if handicap.odds.get_required_color().is_some() => (rematch.white_player, rematch.black_player, Some(handicap)),
+            handicap => (
+                rematch.black_player,
+                rematch.white_player,
+                handicap.map(|handicap| Handicap::new(handicap.odds, handicap.giver.get_opposite())),
+            ),
+        };
+
+        // The players of a game always differ, so creating the game cannot fail
+        let mut game = self.set_up_game(white_player, black_player, announcer.or(rematch.announcer), rematch.options).unwrap();
+        game.score = Some(rematch.score);
+
+        if let Some(handicap) = handicap {
+            game.set_handicap(handicap);
+        }
+
+        Ok(RematchOutcome::Started(self.start_game(game)))
+    }
+
+    pub(super) fn set_rematch_message(&mut self, game: GameId, message: MessageId) {
+        if let Some(rematch) = self.rematches.iter_mut().find(|rematch| rematch.game == game) {
+            rematch.message = Some(message);
+        }
+    }
+
+    fn notify_rematch(sockets: &SocketRegistry, rematch: &Rematch) {
+        let message = UpdateRematchMessage {
+            rematch: Arc::from(make_rematch(rematch)),
+        };
+
+        sockets.send_to_users(&[rematch.white_player.id, rematch.black_player.id], message);
+    }
+
+    /// Keeps a concluded game around for a while so its players can ask for a rematch.
+    pub(super) fn add_rematch(&mut self, game: &Game) {
+        if !game.can_be_rematched() {
+            return;
+        }
+
+        self.rematches.push(Rematch {
+            game: game.id,
+            white_player: game.white_player.clone(),
+            black_player: game.black_player.clone(),
+            options: game.options,
+            score: game.get_match_score(),
+            offers: Vec::new(),
+            end_time: SystemTime::now(),
+            announcer: game.announcer.clone(),
+            message: None,
+            handicap: game.handicap,
+        });
+    }
+}
+
+pub enum RematchOutcome<'a> {
+    Offered(&'a Rematch),
+    Started(SharedGame),
+}
@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use serenity::model::misc::Mentionable;
+
+use crate::chess::board::Color;
+use crate::http::http_server::UserInfo;
+use crate::http::proto::make_lobby;
+use crate::http::web_socket::{SocketRegistry, UpdateLobbyMessage};
+use crate::system::matchmaking::{Seek, SeekError, SeekId, SeekList};
+
+use super::{ColorPreference, GameAnnouncer, GameManager, PlayerId, SharedGame};
+
+impl GameManager {
+    pub fn get_seeks(&mut self) -> &[Seek] {
+        if self.seeks.remove_expired() {
+            GameManager::notify_lobby(&self.web_sockets, &self.seeks);
+        }
+
+        self.seeks.get_seeks()
+    }
+
+    /// Pairs the seek with the oldest compatible one, or leaves it open until someone else does.
+    pub fn post_seek(&mut self, mut seek: Seek) -> SeekOutcome {
+        self.seeks.remove_expired();
+
+        let pool = seek.options.get_rating_pool();
+        let ratings = &self.ratings;
+        seek.rating = ratings.get_rating(seek.user.id, pool);
+
+        match self.seeks.find_match(&seek, |user| ratings.get_rating(user, pool)) {
+            Some(id) => {
+                let other = self.seeks.take(id).unwrap();
+                SeekOutcome::Paired(self.start_seek_game(other, seek.user, seek.color, seek.announcer))
+            }
+            None => {
+                let id = self.seeks.add(seek);
+                GameManager::notify_lobby(&self.web_sockets, &self.seeks);
+                SeekOutcome::Posted(id)
+            }
+        }
+    }
+
+    pub fn accept_seek(&mut self, id: SeekId, user: UserInfo, announcer: Option<GameAnnouncer>) -> Result<SharedGame, SeekError> {
+        self.seeks.remove_expired();
+
+        let seek = self.seeks.get(id).ok_or(SeekError::NoSuchSeek(id))?;
+
+        if seek.user.id == user.id {
+            return Err(SeekError::OwnSeek);
+        }
+
+        if !seek.rating_range.contains(&self.ratings.get_rating(user.id, seek.options.get_rating_pool())) {
+            return Err(SeekError::OutOfRange);
+        }
+
+        let seek = self.seeks.take(id).unwrap();
+        Ok(self.start_seek_game(seek, user, ColorPreference::Random, announcer))
+    }
+
+    pub fn cancel_seek(&mut self, id: SeekId, user: PlayerId) -> Result<(), SeekError> {
+        match self.seeks.get(id) {
+            None => return Err(SeekError::NoSuchSeek(id)),
+            Some(seek) if seek.user.id != user => return Err(SeekError::NotYourSeek),
+            Some(_) => self.seeks.take(id),
+        };
+
+        GameManager::notify_lobby(&self.web_sockets, &self.seeks);
+        Ok(())
+    }
+
+    pub fn cancel_seeks_of(&mut self, user: PlayerId) -> usize {
+        let count = self.seeks.remove_seeks_of(user);
+
+        if count > 0 {
+            GameManager::notify_lobby(&self.web_sockets, &self.seeks);
+        }
+
+        count
+    }
+
+    fn start_seek_game(&mut self, seek: Seek, opponent: UserInfo, opponent_color: ColorPreference, announcer: Option<GameAnnouncer>) -> SharedGame {
+        // The seeker is not around when someone else takes the seek, let them know where it was posted
+        if let Some(seek_announcer) = seek
+            .announcer
+            .as_ref()
+            .filter(|seek_announcer| announcer.as_ref().map(|announcer| announcer.id) != Some(seek_announcer.id))
+        {
+            let seek_announcer = seek_announcer.clone();
+            let message = format!(
+                "{}, your seek #{} was taken by {}, game #{} has started!",
+                seek.user.id.mention(),
+                seek.id,
+                opponent.username,
+                self.next_game_id
+            );
+
+            tokio::spawn(async move {
+                let _ = seek_announcer.announce(message).await;
+            });
+        }
+
+        let (white_player, black_player) = match seek.color.resolve(opponent_color) {
+            Color::White => (seek.user, opponent),
+            Color::Black => (opponent, seek.user),
+        };
+
+        GameManager::notify_lobby(&self.web_sockets, &self.seeks);
+
+        // Seeks of the same user are never paired, so creating the game cannot fail
+        self.create_game(white_player, black_player, announcer.or(seek.announcer), seek.options).unwrap()
+    }
+
+    fn notify_lobby(sockets: &SocketRegistry, seeks: &SeekList) {
+        sockets.send_to_lobby(UpdateLobbyMessage {
+            lobby: Arc::from(make_lobby(seeks.get_seeks())),
+        });
+    }
+}
+
+pub enum SeekOutcome {
+    Posted(SeekId),
+    Paired(SharedGame),
+}
@@ -0,0 +1,182 @@
+use serenity::model::misc::Mentionable;
+
+use crate::chess::board::Color;
+use crate::chess::game::GameResult;
+use crate::http::http_server::UserInfo;
+use crate::system::simul::{Simul, SimulError, SimulGame, SimulId, SimulState};
+
+use super::{ColorPreference, Game, GameAnnouncer, GameId, GameManager, GameOptions, PlayerId, SharedGame};
+
+impl GameManager {
+    pub fn create_simul(&mut self, name: String, host: UserInfo, host_color: ColorPreference, options: GameOptions, announcer: Option<GameAnnouncer>) -> &Simul {
+        let simul = Simul::new(self.next_simul_id, name, host, host_color, options, announcer);
+        self.next_simul_id += 1;
+
+        GameManager::save_simul(&self.storage, &simul);
+        self.simuls.push(simul);
+
+        self.simuls.last().unwrap()
+    }
+
+    pub fn get_simul(&self, id: SimulId) -> Option<&Simul> {
+        self.simuls.iter().find(|simul| simul.id == id)
+    }
+
+    pub fn get_simuls(&self) -> &[Simul] {
+        &self.simuls
+    }
+
+    fn get_simul_index(&self, id: SimulId) -> Result<usize, SimulError> {
+        self.simuls.iter().position(|simul| simul.id == id).ok_or(SimulError::NoSuchSimul(id))
+    }
+
+    pub fn join_simul(&mut self, id: SimulId, user: UserInfo) -> Result<&Simul, SimulError> {
+        let index = self.get_simul_index(id)?;
+        let simul = &mut self.simuls[index];
+
+        if simul.state != SimulState::Registration {
+            return Err(SimulError::AlreadyStarted);
+        }
+
+        if simul.host.id == user.id {
+            return Err(SimulError::IsHost);
+        }
+
+        if simul.get_board(user.id).is_some() {
+            return Err(SimulError::AlreadyJoined);
+        }
+
+        simul.add_opponent(user);
+
+        GameManager::save_simul(&self.storage, simul);
+        Ok(simul)
+    }
+
+    pub fn leave_simul(&mut self, id: SimulId, user: PlayerId) -> Result<&Simul, SimulError> {
+        let index = self.get_simul_index(id)?;
+        let simul = &mut self.simuls[index];
+
+        if simul.state != SimulState::Registration {
+            return Err(SimulError::AlreadyStarted);
+        }
+
+        if !simul.remove_opponent(user) {
+            return Err(SimulError::NotJoined);
+        }
+
+        GameManager::save_simul(&self.storage, simul);
+        Ok(simul)
+    }
+
+    /// Starts a game between the host and every opponent, all of them announced in the simul's channel.
+    pub fn start_simul(&mut self, id: SimulId, user: PlayerId) -> Result<&Simul, SimulError> {
+        let index = self.get_simul_index(id)?;
+        let simul = &mut self.simuls[index];
+
+        if simul.host.id != user {
+            return Err(SimulError::NotHost);
+        }
+
+        if simul.state != SimulState::Registration {
+            return Err(SimulError::AlreadyStarted);
+        }
+
+        if simul.boards.is_empty() {
+            return Err(SimulError::NoOpponents);
+        }
+
+        simul.state = SimulState::Running;
+
+        let (host, options, announcer, boards) = (simul.host.clone(), simul.options, simul.announcer.clone(), simul.boards.clone());
+        let mut message = format!("Simul #{} ({}) has started:\n", id, self.simuls[index].name);
+
+        for (board, opponent) in boards.iter().enumerate() {
+            let (white_player, black_player) = match opponent.host_color {
+                Color::White => (host.clone(), opponent.opponent.clone()),
+                Color::Black => (opponent.opponent.clone(), host.clone()),
+            };
+
+            let mut game = self.set_up_game(white_player, black_player, announcer.clone(), options).unwrap();
+            let game_id = game.id;
+            game.simul = Some(SimulGame {
+                simul: id,
+                board: board + 1,
+                host_color: opponent.host_color,
+            });
+            self.start_game(game);
+
+            self.simuls[index].boards[board].game = Some(game_id);
+
+            message.push_str(&format!(
+                "Board {}: {} plays {:?} (game #{})\n",
+                board + 1,
+                opponent.opponent.id.mention(),
+                opponent.host_color.get_opposite(),
+                game_id
+            ));
+        }
+
+        let simul = &self.simuls[index];
+        GameManager::announce_simul(simul, message);
+        GameManager::save_simul(&self.storage, simul);
+        Ok(simul)
+    }
+
+    /// The games of a simul, none for boards that already concluded.
+    pub fn get_simul_games(&self, id: SimulId) -> Option<(&Simul, Vec<Option<SharedGame>>)> {
+        let simul = self.simuls.iter().find(|simul| simul.id == id)?;
+        let games = simul.boards.iter().map(|board| board.game.and_then(|id| self.get_game(id))).collect();
+
+        Some((simul, games))
+    }
+
+    /// The next board of the simul after the given game where it is the host's turn, wrapping around.
+    pub fn get_next_simul_board(&self, id: SimulId, after: Option<GameId>) -> Option<GameId> {
+        let (simul, games) = self.get_simul_games(id)?;
+        let current = after.and_then(|after| simul.boards.iter().position(|board| board.game == Some(after)));
+
+        // Boards where the host is to move, by board number
+        let waiting: Vec<(usize, GameId)> = games
+            .iter()
+            .zip(simul.boards.iter())
+            .enumerate()
+            .filter_map(|(index, (game, board))| {
+                let game = game.as_ref()?.lock().unwrap();
+                Some((index, game.id)).filter(|_| game.chess_game.state.current_turn == board.host_color)
+            })
+            .collect();
+
+        waiting
+            .iter()
+            .find(|(index, _)| current.is_some_and(|current| *index > current))
+            .or_else(|| waiting.first())
+            .map(|(_, game)| *game)
+    }
+
+    fn announce_simul(simul: &Simul, message: String) {
+        if let Some(announcer) = simul.announcer.clone() {
+            tokio::spawn(async move {
+                let _ = announcer.announce(message).await;
+            });
+        }
+    }
+
+    pub(super) fn record_simul_result(&mut self, game: &Game) {
+        let simuls = &mut self.simuls;
+        let simul = match game.simul.and_then(|simul| simuls.iter_mut().find(|other| other.id == simul.simul)) {
+            Some(simul) => simul,
+            None => return,
+        };
+
+        if !simul.record_result(game.id, game.chess_game.result.unwrap_or(GameResult::Ongoing)) {
+            return;
+        }
+
+        if simul.state == SimulState::Running && simul.is_finished() {
+            simul.state = SimulState::Finished;
+            GameManager::announce_simul(simul, simul.create_final_score());
+        }
+
+        GameManager::save_simul(&self.storage, simul);
+    }
+}
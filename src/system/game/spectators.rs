@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+use serenity::http::{AttachmentType, Http};
+use serenity::model::channel::Message;
+use serenity::model::id::ChannelId;
+
+use crate::http::http_server::UserInfo;
+use crate::system::events::{GameEvent, GameEventSubscriber};
+use crate::util::board_visualizer::BoardVisualizer;
+
+use super::{Game, GameId, GameLookupError, GameManager, PlayerId, SharedGame};
+
+impl GameManager {
+    fn get_watchable_game(&self, id: GameId, user: PlayerId) -> Result<SharedGame, GameLookupError> {
+        let game = self.get_game(id).ok_or(GameLookupError::NoSuchGame(id))?;
+
+        if !game.lock().unwrap().can_be_watched_by(user) {
+            return Err(GameLookupError::PrivateGame(id));
+        }
+
+        Ok(game)
+    }
+
+    pub fn add_spectator(&self, id: GameId, user: UserInfo) -> Result<SharedGame, GameLookupError> {
+        let shared = self.get_watchable_game(id, user.id)?;
+
+        {
+            let mut game = shared.lock().unwrap();
+            game.spectators.push(user);
+
+            GameManager::notify_about(&self.web_sockets, &game);
+        }
+
+        Ok(shared)
+    }
+
+    pub fn remove_spectator(&self, id: GameId, user: PlayerId) {
+        if let Some(game) = self.games.get(&id) {
+            let mut game = game.lock().unwrap();
+
+            if let Some(index) = game.spectators.iter().position(|spectator| spectator.id == user) {
+                game.spectators.remove(index);
+                GameManager::notify_about(&self.web_sockets, &game);
+            }
+        }
+    }
+
+    pub fn add_watcher(&self, id: GameId, watcher: GameWatcher) -> Result<SharedGame, GameLookupError> {
+        let shared = self.get_watchable_game(id, watcher.user.id)?;
+
+        {
+            let mut game = shared.lock().unwrap();
+            game.watchers.retain(|other| other.user.id != watcher.user.id || other.channel != watcher.channel);
+            game.watchers.push(watcher);
+
+            GameManager::notify_about(&self.web_sockets, &game);
+        }
+
+        Ok(shared)
+    }
+
+    pub fn remove_watcher(&self, id: GameId, user: PlayerId, channel: ChannelId) -> bool {
+        let mut game = match self.games.get(&id) {
+            Some(game) => game.lock().unwrap(),
+            None => return false,
+        };
+
+        let len = game.watchers.len();
+        game.watchers.retain(|watcher| watcher.user.id != user || watcher.channel != channel);
+
+        if len == game.watchers.len() {
+            return false;
+        }
+
+        GameManager::notify_about(&self.web_sockets, &game);
+        true
+    }
+}
+
+/// Posts the board to a Discord channel after every change of a watched game.
+#[derive(Clone)]
+pub struct GameWatcher {
+    pub user: UserInfo,
+    pub channel: ChannelId,
+    ctx: Arc<Http>,
+    visualizer: Arc<BoardVisualizer>,
+}
+
+impl GameWatcher {
+    pub fn new(user: UserInfo, channel: ChannelId, ctx: Arc<Http>, visualizer: Arc<BoardVisualizer>) -> Self {
+        Self { user, channel, ctx, visualizer }
+    }
+
+    pub fn render(&self, game: &Game) -> Option<Vec<u8>> {
+        self.visualizer.visualize(&game.chess_game.state.board).ok()
+    }
+
+    pub fn create_update(game: &Game) -> String {
+        let mut message = format!("Game #{}: {} vs {}. ", game.id, game.white_player.username, game.black_player.username);
+
+        match game.chess_game.result {
+            Some(result) => message.push_str(&result.pretty_message()),
+            None => message.push_str(&format!("{} to move.", game.mention_side(game.chess_game.state.current_turn))),
+        }
+
+        message
+    }
+
+    pub async fn send_board(&self, image: Vec<u8>, header: String) -> serenity::Result<Message> {
+        self.channel
+            .send_files(&self.ctx, std::iter::once(AttachmentType::from((image.as_slice(), "board.png"))), |f| {
+                f.content(header);
+                f
+            })
+            .await
+    }
+}
+
+/// Sends the board to the watchers of a game whenever its position or result changes.
+pub struct WatcherNotifier;
+
+impl GameEventSubscriber for WatcherNotifier {
+    fn notify(&self, game: &Game, events: &[GameEvent]) {
+        let changed = events
+            .iter()
+            .any(|event| matches!(event, GameEvent::MoveMade(_) | GameEvent::MovesTakenBack(_) | GameEvent::GameEnded(_)));
+
+        if !changed {
+            return;
+        }
+
+        if game.watchers.is_empty() {
+            return;
+        }
+
+        // Rendering the board takes a while, it must not hold up the other subscribers
+        let game = game.clone();
+
+        tokio::spawn(async move {
+            let image = match game.watchers[0].render(&game) {
+                Some(image) => image,
+                None => return,
+            };
+            let header = GameWatcher::create_update(&game);
+
+            for watcher in game.watchers.iter() {
+                let _ = watcher.send_board(image.clone(), header.clone()).await;
+            }
+        });
+    }
+}
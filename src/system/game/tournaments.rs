@@ -0,0 +1,186 @@
+use serenity::model::misc::Mentionable;
+
+use crate::chess::game::GameResult;
+use crate::http::http_server::UserInfo;
+use crate::system::rating::Rating;
+use crate::system::tournament::{Tournament, TournamentError, TournamentFormat, TournamentGame, TournamentId, TournamentPlayer, TournamentState};
+
+use super::{Game, GameAnnouncer, GameManager, GameOptions, PlayerId};
+
+impl GameManager {
+    pub fn create_tournament(&mut self, name: String, organizer: PlayerId, format: TournamentFormat, options: GameOptions, announcer: Option<GameAnnouncer>) -> &Tournament {
+        let tournament = Tournament::new(self.next_tournament_id, name, organizer, format, options, announcer);
+        self.next_tournament_id += 1;
+
+        GameManager::save_tournament(&self.storage, &tournament);
+        self.tournaments.push(tournament);
+
+        self.tournaments.last().unwrap()
+    }
+
+    pub fn get_tournament(&self, id: TournamentId) -> Option<&Tournament> {
+        self.tournaments.iter().find(|tournament| tournament.id == id)
+    }
+
+    pub fn get_tournaments(&self) -> &[Tournament] {
+        &self.tournaments
+    }
+
+    fn get_tournament_index(&self, id: TournamentId) -> Result<usize, TournamentError> {
+        self.tournaments.iter().position(|tournament| tournament.id == id).ok_or(TournamentError::NoSuchTournament(id))
+    }
+
+    pub fn join_tournament(&mut self, id: TournamentId, user: UserInfo) -> Result<&Tournament, TournamentError> {
+        let index = self.get_tournament_index(id)?;
+        let tournament = &mut self.tournaments[index];
+
+        if tournament.state != TournamentState::Registration {
+            return Err(TournamentError::RegistrationClosed);
+        }
+
+        if tournament.get_player(user.id).is_some() {
+            return Err(TournamentError::AlreadyJoined);
+        }
+
+        tournament.players.push(TournamentPlayer { user, rating: Rating::default() });
+
+        GameManager::save_tournament(&self.storage, tournament);
+        Ok(tournament)
+    }
+
+    pub fn leave_tournament(&mut self, id: TournamentId, user: PlayerId) -> Result<&Tournament, TournamentError> {
+        let index = self.get_tournament_index(id)?;
+        let tournament = &mut self.tournaments[index];
+
+        if tournament.state != TournamentState::Registration {
+            return Err(TournamentError::RegistrationClosed);
+        }
+
+        let len = tournament.players.len();
+        tournament.players.retain(|player| player.user.id != user);
+
+        if len == tournament.players.len() {
+            return Err(TournamentError::NotJoined);
+        }
+
+        GameManager::save_tournament(&self.storage, tournament);
+        Ok(tournament)
+    }
+
+    /// Seeds the players by their current rating and creates the games of the first round.
+    pub fn start_tournament(&mut self, id: TournamentId, user: PlayerId) -> Result<&Tournament, TournamentError> {
+        let index = self.get_tournament_index(id)?;
+        let tournament = &mut self.tournaments[index];
+
+        if tournament.organizer != user {
+            return Err(TournamentError::NotOrganizer);
+        }
+
+        if tournament.state != TournamentState::Registration {
+            return Err(TournamentError::RegistrationClosed);
+        }
+
+        if tournament.players.len() < 2 {
+            return Err(TournamentError::NotEnoughPlayers);
+        }
+
+        let pool = tournament.options.get_rating_pool();
+
+        for player in tournament.players.iter_mut() {
+            player.rating = self.ratings.get_rating(player.user.id, pool);
+        }
+
+        tournament.players.sort_by(|a, b| b.rating.rating.total_cmp(&a.rating.rating));
+        tournament.state = TournamentState::Running;
+
+        self.start_tournament_round(index);
+        Ok(&self.tournaments[index])
+    }
+
+    /// Pairs the next round and creates its games, or ends the tournament after the last round.
+    fn start_tournament_round(&mut self, index: usize) {
+        if !self.tournaments[index].pair_next_round() {
+            let tournament = &mut self.tournaments[index];
+            tournament.state = TournamentState::Finished;
+
+            let mut message = format!("Tournament #{} ({}) has finished!\n", tournament.id, tournament.name);
+
+            for (place, standing) in tournament.get_standings().iter().enumerate() {
+                message.push_str(&format!(
+                    "{}. {}: {} points (Buchholz {}, Sonneborn-Berger {})\n",
+                    place + 1,
+                    standing.player.username,
+                    standing.points,
+                    standing.buchholz,
+                    standing.sonneborn_berger
+                ));
+            }
+
+            GameManager::announce_tournament(tournament, message);
+            GameManager::save_tournament(&self.storage, tournament);
+            return;
+        }
+
+        let (id, round, options, announcer, pairings) = {
+            let tournament = &self.tournaments[index];
+            let round = tournament.rounds.len();
+
+            (tournament.id, round, tournament.options, tournament.announcer.clone(), tournament.get_current_round().unwrap().to_vec())
+        };
+
+        let mut message = format!("Round {} of tournament #{} ({}):\n", round, id, self.tournaments[index].name);
+
+        for (board, pairing) in pairings.iter().enumerate() {
+            let black = match pairing.black {
+                Some(black) => black,
+                None => {
+                    message.push_str(&format!("{} has a bye.\n", pairing.white.mention()));
+                    continue;
+                }
+            };
+
+            let white_player = self.tournaments[index].get_player(pairing.white).unwrap().user.clone();
+            let black_player = self.tournaments[index].get_player(black).unwrap().user.clone();
+
+            let mut game = self.set_up_game(white_player, black_player, announcer.clone(), options).unwrap();
+            let game_id = game.id;
+            game.tournament = Some(TournamentGame { tournament: id, round });
+            self.start_game(game);
+
+            self.tournaments[index].rounds[round - 1][board].game = Some(game_id);
+
+            message.push_str(&format!("Board {}: {} - {} (game #{})\n", board + 1, pairing.white.mention(), black.mention(), game_id));
+        }
+
+        let tournament = &self.tournaments[index];
+        GameManager::announce_tournament(tournament, message);
+        GameManager::save_tournament(&self.storage, tournament);
+    }
+
+    fn announce_tournament(tournament: &Tournament, message: String) {
+        if let Some(announcer) = tournament.announcer.clone() {
+            tokio::spawn(async move {
+                let _ = announcer.announce(message).await;
+            });
+        }
+    }
+
+    pub(super) fn record_tournament_result(&mut self, game: &Game, result: GameResult) {
+        if let Some(tournament_game) = game.tournament {
+            if let Some(tournament) = self.tournaments.iter_mut().find(|tournament| tournament.id == tournament_game.tournament) {
+                tournament.record_result(game.id, result);
+            }
+        }
+    }
+
+    /// Pairs the next round of every running tournament whose games are all over.
+    pub(super) fn start_finished_tournament_rounds(&mut self) {
+        let finished_rounds: Vec<usize> = (0..self.tournaments.len())
+            .filter(|&index| self.tournaments[index].state == TournamentState::Running && self.tournaments[index].is_round_finished())
+            .collect();
+
+        for index in finished_rounds {
+            self.start_tournament_round(index);
+        }
+    }
+}
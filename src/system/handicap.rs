@@ -0,0 +1,123 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::chess::board::{Board, Color, Square};
+
+use super::game::TimeControl;
+
+/// What the stronger player gives up, written as `knight-odds`, `rook-odds`, `queen-odds`, `pawn-odds` (pawn and move)
+/// or `time-odds:1+0` with the time control of the player giving the odds.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Copy, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Odds {
+    /// The queen's knight.
+    Knight,
+    /// The queen's rook.
+    Rook,
+    Queen,
+    /// The f-pawn, the player giving it plays black.
+    PawnAndMove,
+    Time {
+        time_control: TimeControl,
+    },
+}
+
+impl Odds {
+    /// Material odds change the starting position.
+    pub fn is_material(&self) -> bool {
+        !matches!(self, Odds::Time { .. })
+    }
+
+    /// Odds that require the player giving them to play a certain colour.
+    pub fn get_required_color(&self) -> Option<Color> {
+        match self {
+            Odds::PawnAndMove => Some(Color::Black),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Odds {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Odds::Knight => write!(f, "knight odds"),
+            Odds::Rook => write!(f, "rook odds"),
+            Odds::Queen => write!(f, "queen odds"),
+            Odds::PawnAndMove => write!(f, "pawn and move odds"),
+            Odds::Time { time_control } => write!(f, "time odds ({})", time_control),
+        }
+    }
+}
+
+impl FromStr for Odds {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+
+        if let Some(time_control) = s.strip_prefix("time-odds:") {
+            return match time_control.parse() {
+                Ok(time_control @ TimeControl::Live { .. }) => Ok(Odds::Time { time_control }),
+                _ => Err(()),
+            };
+        }
+
+        match s.as_str() {
+            "knight-odds" => Ok(Odds::Knight),
+            "rook-odds" => Ok(Odds::Rook),
+            "queen-odds" => Ok(Odds::Queen),
+            "pawn-odds" | "pawn-and-move" => Ok(Odds::PawnAndMove),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Odds given in a game by the player of one side.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Handicap {
+    pub odds: Odds,
+    pub giver: Color,
+}
+
+impl Handicap {
+    pub fn new(odds: Odds, giver: Color) -> Self {
+        Self { odds, giver }
+    }
+
+    /// Removes the material given from the starting position, time odds leave the board unchanged.
+    pub fn apply(&self, board: &mut Board) {
+        let (rank, pawn_rank) = match self.giver {
+            Color::White => (1, 2),
+            Color::Black => (8, 7),
+        };
+
+        match self.odds {
+            Odds::Knight => board.remove_piece(Square::new(2, rank)),
+            Odds::Rook => {
+                board.remove_piece(Square::new(1, rank));
+                board.state.get_castling_rights_mut_for(self.giver).long_castle = false;
+            }
+            Odds::Queen => board.remove_piece(Square::new(4, rank)),
+            Odds::PawnAndMove => board.remove_piece(Square::new(6, pawn_rank)),
+            Odds::Time { .. } => {}
+        }
+
+        board.recalculate_all_pieces_movements();
+    }
+
+    /// The time control of the given side, the game's own one unless the side gives time odds.
+    pub fn get_time_control(&self, side: Color, game_time_control: TimeControl) -> TimeControl {
+        match self.odds {
+            Odds::Time { time_control } if side == self.giver => time_control,
+            _ => game_time_control,
+        }
+    }
+}
+
+impl Display for Handicap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} given by {:?}", self.odds, self.giver)
+    }
+}
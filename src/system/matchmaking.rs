@@ -0,0 +1,174 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use serenity::model::id::UserId;
+
+use crate::http::http_server::UserInfo;
+
+use super::game::{ColorPreference, GameAnnouncer, GameOptions};
+use super::rating::Rating;
+
+type PlayerId = UserId;
+pub type SeekId = u64;
+
+/// Seeks nobody answered are dropped after this long.
+pub const SEEK_EXPIRY: Duration = Duration::from_secs(60 * 60);
+
+/// Ratings an opponent must have, written as `1200-1800`, `1200-` or `-1800`.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Copy, Clone, Debug, Default)]
+pub struct RatingRange {
+    pub min: Option<u32>,
+    pub max: Option<u32>,
+}
+
+impl RatingRange {
+    pub fn contains(&self, rating: &Rating) -> bool {
+        self.min.is_none_or(|min| rating.rating >= min as f64) && self.max.is_none_or(|max| rating.rating <= max as f64)
+    }
+
+    pub fn is_any(&self) -> bool {
+        self.min.is_none() && self.max.is_none()
+    }
+}
+
+impl Display for RatingRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (self.min, self.max) {
+            (None, None) => write!(f, "any"),
+            (min, max) => write!(f, "{}-{}", min.map(|min| min.to_string()).unwrap_or_default(), max.map(|max| max.to_string()).unwrap_or_default()),
+        }
+    }
+}
+
+impl FromStr for RatingRange {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (min, max) = s.split_once('-').ok_or(())?;
+        let parse = |value: &str| if value.is_empty() { Ok(None) } else { value.parse().map(Some).map_err(|_| ()) };
+
+        let range = RatingRange { min: parse(min)?, max: parse(max)? };
+
+        match range {
+            RatingRange { min: Some(min), max: Some(max) } if min > max => Err(()),
+            range => Ok(range),
+        }
+    }
+}
+
+/// An open request for a game against anyone whose settings fit.
+#[derive(Clone)]
+pub struct Seek {
+    pub id: SeekId,
+    pub user: UserInfo,
+    pub options: GameOptions,
+    pub color: ColorPreference,
+    pub rating_range: RatingRange,
+    /// Rating of the seeker in the pool of the game when the seek was posted.
+    pub rating: Rating,
+    pub creation_time: SystemTime,
+    /// Where the seek was posted, the game is announced there once somebody takes it.
+    pub announcer: Option<GameAnnouncer>,
+}
+
+impl Seek {
+    pub fn new(user: UserInfo, options: GameOptions, color: ColorPreference, rating_range: RatingRange, announcer: Option<GameAnnouncer>) -> Self {
+        Self {
+            id: 0,
+            user,
+            options,
+            color,
+            rating_range,
+            rating: Rating::default(),
+            creation_time: SystemTime::now(),
+            announcer,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.creation_time.elapsed().unwrap_or_default() >= SEEK_EXPIRY
+    }
+
+    /// Whether both seeks ask for the same kind of game, the ratings are checked separately.
+    pub fn is_compatible(&self, other: &Seek) -> bool {
+        self.user.id != other.user.id
+            && self.options.rated == other.options.rated
+            && self.options.time_control == other.options.time_control
+            && self.options.variant == other.options.variant
+            && !self.color.conflicts_with(other.color)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SeekError {
+    #[error("There is no open seek #{0}.")]
+    NoSuchSeek(SeekId),
+    #[error("You cannot accept your own seek.")]
+    OwnSeek,
+    #[error("Your rating is outside of the range the seek asks for.")]
+    OutOfRange,
+    #[error("The seek does not belong to you.")]
+    NotYourSeek,
+}
+
+/// The open seeks, oldest first.
+#[derive(Default)]
+pub struct SeekList {
+    seeks: Vec<Seek>,
+    next_id: SeekId,
+}
+
+impl SeekList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drops expired seeks, returns true if there were any.
+    pub fn remove_expired(&mut self) -> bool {
+        let len = self.seeks.len();
+        self.seeks.retain(|seek| !seek.is_expired());
+
+        len != self.seeks.len()
+    }
+
+    pub fn add(&mut self, mut seek: Seek) -> SeekId {
+        seek.id = self.next_id;
+        self.next_id += 1;
+        self.seeks.push(seek);
+
+        self.next_id - 1
+    }
+
+    pub fn get(&self, id: SeekId) -> Option<&Seek> {
+        self.seeks.iter().find(|seek| seek.id == id)
+    }
+
+    pub fn get_seeks(&self) -> &[Seek] {
+        &self.seeks
+    }
+
+    pub fn take(&mut self, id: SeekId) -> Option<Seek> {
+        let index = self.seeks.iter().position(|seek| seek.id == id)?;
+
+        Some(self.seeks.remove(index))
+    }
+
+    pub fn remove_seeks_of(&mut self, user: PlayerId) -> usize {
+        let len = self.seeks.len();
+        self.seeks.retain(|seek| seek.user.id != user);
+
+        len - self.seeks.len()
+    }
+
+    /// The oldest seek that pairs with the new one, `get_rating` looks up a player's rating for the seek's pool.
+    pub fn find_match<F: Fn(PlayerId) -> Rating>(&self, seek: &Seek, get_rating: F) -> Option<SeekId> {
+        let rating = get_rating(seek.user.id);
+
+        self.seeks
+            .iter()
+            .find(|other| other.is_compatible(seek) && other.rating_range.contains(&rating) && seek.rating_range.contains(&get_rating(other.user.id)))
+            .map(|other| other.id)
+    }
+}
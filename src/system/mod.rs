@@ -1 +1,17 @@
+pub mod abandonment;
+pub mod archive;
+pub mod arena;
+pub mod challenge;
+pub mod clock;
+pub mod consultation;
+pub mod correspondence;
+pub mod events;
 pub mod game;
+pub mod handicap;
+pub mod matchmaking;
+pub mod rating;
+pub mod rematch;
+pub mod simul;
+pub mod stats;
+pub mod storage;
+pub mod tournament;
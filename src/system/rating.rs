@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+use serenity::model::id::UserId;
+
+use crate::chess::board::Color;
+use crate::chess::game::GameResult;
+
+use super::game::{TimeCategory, Variant};
+
+type PlayerId = UserId;
+
+pub const DEFAULT_RATING: f64 = 1500.0;
+pub const DEFAULT_DEVIATION: f64 = 350.0;
+pub const DEFAULT_VOLATILITY: f64 = 0.06;
+/// Ratings with a higher deviation are shown with a question mark.
+pub const PROVISIONAL_DEVIATION: f64 = 110.0;
+
+/// Constrains how fast the volatility changes.
+const TAU: f64 = 0.5;
+const GLICKO2_SCALE: f64 = 173.7178;
+const CONVERGENCE_TOLERANCE: f64 = 0.000_001;
+
+/// Glicko-2 rating of a player in a single rating pool.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct Rating {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+    pub games: u32,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Rating {
+            rating: DEFAULT_RATING,
+            deviation: DEFAULT_DEVIATION,
+            volatility: DEFAULT_VOLATILITY,
+            games: 0,
+        }
+    }
+}
+
+fn g(phi: f64) -> f64 {
+    1.0 / (1.0 + 3.0 * phi * phi / (PI * PI)).sqrt()
+}
+
+impl Rating {
+    pub fn is_provisional(&self) -> bool {
+        self.deviation > PROVISIONAL_DEVIATION
+    }
+
+    /// The rating after a single game against the opponent, the score being 1 for a win, 0.5 for a draw and 0 for a loss.
+    /// Every game is treated as its own rating period.
+    pub fn update(&self, opponent: &Rating, score: f64) -> Rating {
+        self.rate(&[(*opponent, score)])
+    }
+
+    /// Glicko-2 for a rating period with the given opponents and scores, only the test of the worked example has more than one.
+    fn rate(&self, results: &[(Rating, f64)]) -> Rating {
+        let mu = (self.rating - DEFAULT_RATING) / GLICKO2_SCALE;
+        let phi = self.deviation / GLICKO2_SCALE;
+
+        let mut inverse_variance = 0.0;
+        let mut improvement = 0.0;
+
+        for (opponent, score) in results.iter() {
+            let opponent_mu = (opponent.rating - DEFAULT_RATING) / GLICKO2_SCALE;
+            let opponent_g = g(opponent.deviation / GLICKO2_SCALE);
+            let expected = 1.0 / (1.0 + (-opponent_g * (mu - opponent_mu)).exp());
+
+            inverse_variance += opponent_g * opponent_g * expected * (1.0 - expected);
+            improvement += opponent_g * (score - expected);
+        }
+
+        let variance = 1.0 / inverse_variance;
+        let delta = variance * improvement;
+
+        let volatility = self.get_new_volatility(phi, variance, delta);
+
+        let phi_star = (phi * phi + volatility * volatility).sqrt();
+        let new_phi = (1.0 / (1.0 / (phi_star * phi_star) + 1.0 / variance)).sqrt();
+        let new_mu = mu + new_phi * new_phi * improvement;
+
+        Rating {
+            rating: new_mu * GLICKO2_SCALE + DEFAULT_RATING,
+            deviation: (new_phi * GLICKO2_SCALE).min(DEFAULT_DEVIATION),
+            volatility,
+            games: self.games + results.len() as u32,
+        }
+    }
+
+    /// Finds the new volatility with the Illinois algorithm, as described in the Glicko-2 paper.
+    fn get_new_volatility(&self, phi: f64, variance: f64, delta: f64) -> f64 {
+        let a = (self.volatility * self.volatility).ln();
+
+        let f = |x: f64| {
+            let ex = x.exp();
+            let denominator = phi * phi + variance + ex;
+
+            ex * (delta * delta - phi * phi - variance - ex) / (2.0 * denominator * denominator) - (x - a) / (TAU * TAU)
+        };
+
+        let mut lower = a;
+        let mut upper = if delta * delta > phi * phi + variance {
+            (delta * delta - phi * phi - variance).ln()
+        } else {
+            let mut k = 1.0;
+
+            while f(a - k * TAU) < 0.0 {
+                k += 1.0;
+            }
+
+            a - k * TAU
+        };
+
+        let mut f_lower = f(lower);
+        let mut f_upper = f(upper);
+
+        while (upper - lower).abs() > CONVERGENCE_TOLERANCE {
+            let next = lower + (lower - upper) * f_lower / (f_upper - f_lower);
+            let f_next = f(next);
+
+            if f_next * f_upper <= 0.0 {
+                lower = upper;
+                f_lower = f_upper;
+            } else {
+                f_lower /= 2.0;
+            }
+
+            upper = next;
+            f_upper = f_next;
+        }
+
+        (lower / 2.0).exp()
+    }
+}
+
+impl Display for Rating {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.0}", self.rating)?;
+
+        if self.is_provisional() {
+            write!(f, "?")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Ratings are kept separately for every combination of time category and variant.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Copy, Clone, Debug, Default)]
+pub struct RatingPool {
+    pub category: TimeCategory,
+    pub variant: Variant,
+}
+
+impl Display for RatingPool {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.variant {
+            Variant::Standard => write!(f, "{}", self.category),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PoolRating {
+    #[serde(flatten)]
+    pub pool: RatingPool,
+    #[serde(flatten)]
+    pub rating: Rating,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PlayerRatings {
+    pub user: PlayerId,
+    pub ratings: Vec<PoolRating>,
+}
+
+impl PlayerRatings {
+    pub fn new(user: PlayerId) -> Self {
+        Self { user, ratings: Vec::new() }
+    }
+
+    /// The rating in the pool, a fresh one if the player has not played in it yet.
+    pub fn get(&self, pool: RatingPool) -> Rating {
+        self.ratings.iter().find(|rating| rating.pool == pool).map(|rating| rating.rating).unwrap_or_default()
+    }
+
+    pub fn set(&mut self, pool: RatingPool, rating: Rating) {
+        match self.ratings.iter_mut().find(|rating| rating.pool == pool) {
+            Some(existing) => existing.rating = rating,
+            None => self.ratings.push(PoolRating { pool, rating }),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RatingTable {
+    players: HashMap<PlayerId, PlayerRatings>,
+}
+
+impl RatingTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, ratings: PlayerRatings) {
+        self.players.insert(ratings.user, ratings);
+    }
+
+    pub fn get_player(&self, user: PlayerId) -> Option<&PlayerRatings> {
+        self.players.get(&user)
+    }
+
+    pub fn get_players(&self) -> impl Iterator<Item = &PlayerRatings> {
+        self.players.values()
+    }
+
+    pub fn get_rating(&self, user: PlayerId, pool: RatingPool) -> Rating {
+        self.get_player(user).map(|ratings| ratings.get(pool)).unwrap_or_default()
+    }
+
+    /// Rates a finished game for both players at once, returns false if the game had no result to rate.
+    pub fn apply_result(&mut self, white: PlayerId, black: PlayerId, pool: RatingPool, result: GameResult) -> bool {
+        let white_score = match result {
+            GameResult::Ongoing | GameResult::Aborted => return false,
+            result => match result.get_winner() {
+                Some(Color::White) => 1.0,
+                Some(Color::Black) => 0.0,
+                None => 0.5,
+            },
+        };
+
+        let white_rating = self.get_rating(white, pool);
+        let black_rating = self.get_rating(black, pool);
+
+        self.players
+            .entry(white)
+            .or_insert_with(|| PlayerRatings::new(white))
+            .set(pool, white_rating.update(&black_rating, white_score));
+        self.players
+            .entry(black)
+            .or_insert_with(|| PlayerRatings::new(black))
+            .set(pool, black_rating.update(&white_rating, 1.0 - white_score));
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rating(rating: f64, deviation: f64) -> Rating {
+        Rating {
+            rating,
+            deviation,
+            ..Rating::default()
+        }
+    }
+
+    /// The worked example from Glickman's "Example of the Glicko-2 system".
+    #[test]
+    fn matches_the_glicko2_example() {
+        let player = rating(1500.0, 200.0);
+        let results = [(rating(1400.0, 30.0), 1.0), (rating(1550.0, 100.0), 0.0), (rating(1700.0, 300.0), 0.0)];
+
+        let updated = player.rate(&results);
+
+        assert!((updated.rating - 1464.06).abs() < 0.01, "rating was {}", updated.rating);
+        assert!((updated.deviation - 151.52).abs() < 0.01, "deviation was {}", updated.deviation);
+        assert!((updated.volatility - 0.05999).abs() < 0.00001, "volatility was {}", updated.volatility);
+        assert_eq!(updated.games, 3);
+    }
+
+    #[test]
+    fn games_rate_both_players() {
+        let pool = RatingPool {
+            category: TimeCategory::Blitz,
+            variant: Variant::Standard,
+        };
+        let (white, black) = (UserId(1), UserId(2));
+        let mut table = RatingTable::new();
+
+        assert!(!table.apply_result(white, black, pool, GameResult::Aborted));
+        assert!(table.apply_result(white, black, pool, GameResult::OutOfTime(Color::Black)));
+
+        let (winner, loser) = (table.get_rating(white, pool), table.get_rating(black, pool));
+        assert!((winner.rating - DEFAULT_RATING - (DEFAULT_RATING - loser.rating)).abs() < 0.01, "equal ratings move by the same amount");
+        assert!(winner.rating > DEFAULT_RATING && winner.deviation < DEFAULT_DEVIATION);
+        assert_eq!((winner.games, loser.games), (1, 1));
+    }
+}
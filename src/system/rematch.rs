@@ -0,0 +1,108 @@
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{MessageId, UserId};
+
+use crate::chess::board::Color;
+use crate::chess::game::GameResult;
+use crate::http::http_server::UserInfo;
+
+use super::game::{GameAnnouncer, GameId, GameOptions};
+use super::handicap::Handicap;
+
+type PlayerId = UserId;
+
+/// How long after a game both players can still ask for a rematch.
+pub const REMATCH_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+pub const REMATCH_EMOJI: &str = "🔁";
+
+#[derive(Error, Debug)]
+pub enum RematchError {
+    #[error("There is no game to rematch.")]
+    NoRematch,
+    #[error("Game #{0} cannot be rematched anymore.")]
+    NoSuchRematch(GameId),
+    #[error("Game #{0} was not played by you.")]
+    NotAPlayer(GameId),
+    #[error("You already offered a rematch.")]
+    AlreadyOffered,
+}
+
+/// Points of two players over a series of rematches, in half points.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct MatchScore {
+    pub players: [PlayerId; 2],
+    pub half_points: [u32; 2],
+    pub games: u32,
+}
+
+impl MatchScore {
+    pub fn new(first: PlayerId, second: PlayerId) -> Self {
+        Self {
+            players: [first, second],
+            half_points: [0, 0],
+            games: 0,
+        }
+    }
+
+    pub fn add_result(&mut self, white: PlayerId, result: GameResult) {
+        let white_index = if self.players[0] == white { 0 } else { 1 };
+
+        match result.get_winner() {
+            Some(Color::White) => self.half_points[white_index] += 2,
+            Some(Color::Black) => self.half_points[1 - white_index] += 2,
+            None => {
+                self.half_points[0] += 1;
+                self.half_points[1] += 1;
+            }
+        }
+
+        self.games += 1;
+    }
+
+    pub fn get_points(&self, player: PlayerId) -> f64 {
+        let index = if self.players[0] == player { 0 } else { 1 };
+
+        self.half_points[index] as f64 / 2.0
+    }
+
+    /// The score written like `alice 1.5 - 0.5 bob`.
+    pub fn describe(&self, first: &UserInfo, second: &UserInfo) -> String {
+        format!("{} {} - {} {}", first.username, self.get_points(first.id), self.get_points(second.id), second.username)
+    }
+}
+
+/// A concluded game the players may play again with swapped colours.
+pub struct Rematch {
+    pub game: GameId,
+    pub white_player: UserInfo,
+    pub black_player: UserInfo,
+    pub options: GameOptions,
+    /// Score including the concluded game.
+    pub score: MatchScore,
+    pub offers: Vec<PlayerId>,
+    pub end_time: SystemTime,
+    pub announcer: Option<GameAnnouncer>,
+    /// The result announcement, reacting to it offers a rematch.
+    pub message: Option<MessageId>,
+    pub handicap: Option<Handicap>,
+}
+
+impl Rematch {
+    pub fn is_expired(&self) -> bool {
+        self.end_time.elapsed().unwrap_or_default() >= REMATCH_WINDOW
+    }
+
+    pub fn is_player(&self, player: PlayerId) -> bool {
+        self.white_player.id == player || self.black_player.id == player
+    }
+
+    pub fn get_opponent(&self, player: PlayerId) -> &UserInfo {
+        if self.white_player.id == player {
+            &self.black_player
+        } else {
+            &self.white_player
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{ChannelId, UserId};
+use serenity::model::misc::Mentionable;
+
+use crate::chess::board::Color;
+use crate::chess::game::GameResult;
+use crate::http::http_server::UserInfo;
+
+use super::game::{ColorPreference, GameAnnouncer, GameId, GameOptions};
+
+type PlayerId = UserId;
+pub type SimulId = u64;
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Copy, Clone, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SimulState {
+    Registration,
+    Running,
+    Finished,
+}
+
+#[derive(Error, Debug)]
+pub enum SimulError {
+    #[error("There is no simul #{0}.")]
+    NoSuchSimul(SimulId),
+    #[error("The simul has already started.")]
+    AlreadyStarted,
+    #[error("You already joined this simul.")]
+    AlreadyJoined,
+    #[error("You did not join this simul.")]
+    NotJoined,
+    #[error("You are hosting this simul.")]
+    IsHost,
+    #[error("Only the host can start the simul.")]
+    NotHost,
+    #[error("Nobody joined the simul yet.")]
+    NoOpponents,
+}
+
+/// Links a game to the simul it is played in.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct SimulGame {
+    pub simul: SimulId,
+    /// Board number, starting at 1.
+    pub board: usize,
+    pub host_color: Color,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SimulBoard {
+    pub opponent: UserInfo,
+    pub host_color: Color,
+    pub game: Option<GameId>,
+    pub result: Option<GameResult>,
+}
+
+impl SimulBoard {
+    /// Points of the host, none while the game is running.
+    pub fn get_host_points(&self) -> Option<f64> {
+        let result = self.result?;
+
+        Some(match result.get_winner() {
+            Some(winner) if winner == self.host_color => 1.0,
+            Some(_) => 0.0,
+            None => 0.5,
+        })
+    }
+}
+
+/// One host playing a game against every opponent at the same time.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Simul {
+    pub id: SimulId,
+    pub name: String,
+    pub host: UserInfo,
+    /// Random alternates the colours from board to board.
+    pub host_color: ColorPreference,
+    pub options: GameOptions,
+    pub state: SimulState,
+    pub boards: Vec<SimulBoard>,
+    pub announce_channel: Option<ChannelId>,
+    #[serde(skip)]
+    pub announcer: Option<GameAnnouncer>,
+}
+
+impl Simul {
+    pub fn new(id: SimulId, name: String, host: UserInfo, host_color: ColorPreference, options: GameOptions, announcer: Option<GameAnnouncer>) -> Self {
+        Self {
+            id,
+            name,
+            host,
+            host_color,
+            options,
+            state: SimulState::Registration,
+            boards: Vec::new(),
+            announce_channel: announcer.as_ref().map(|announcer| announcer.id),
+            announcer,
+        }
+    }
+
+    pub fn get_board(&self, opponent: PlayerId) -> Option<&SimulBoard> {
+        self.boards.iter().find(|board| board.opponent.id == opponent)
+    }
+
+    pub fn get_board_of_game(&self, game: GameId) -> Option<&SimulBoard> {
+        self.boards.iter().find(|board| board.game == Some(game))
+    }
+
+    pub fn add_opponent(&mut self, opponent: UserInfo) {
+        let host_color = match self.host_color {
+            ColorPreference::White => Color::White,
+            ColorPreference::Black => Color::Black,
+            ColorPreference::Random if self.boards.len().is_multiple_of(2) => Color::White,
+            ColorPreference::Random => Color::Black,
+        };
+
+        self.boards.push(SimulBoard {
+            opponent,
+            host_color,
+            game: None,
+            result: None,
+        });
+    }
+
+    /// Removes the opponent and gives the following boards the colours of the one before them.
+    pub fn remove_opponent(&mut self, opponent: PlayerId) -> bool {
+        let index = match self.boards.iter().position(|board| board.opponent.id == opponent) {
+            Some(index) => index,
+            None => return false,
+        };
+
+        let opponents: Vec<UserInfo> = self.boards.drain(index..).skip(1).map(|board| board.opponent).collect();
+
+        for opponent in opponents {
+            self.add_opponent(opponent);
+        }
+
+        true
+    }
+
+    /// Records the result of a board, returns false if the game is not played in this simul.
+    pub fn record_result(&mut self, game: GameId, result: GameResult) -> bool {
+        match self.boards.iter_mut().find(|board| board.game == Some(game)) {
+            Some(board) => {
+                board.result = Some(result);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.boards.iter().all(|board| board.result.is_some())
+    }
+
+    /// Points of the host in the finished games.
+    pub fn get_host_score(&self) -> f64 {
+        self.boards.iter().filter_map(SimulBoard::get_host_points).sum()
+    }
+
+    /// Wins, draws and losses of the host.
+    pub fn get_host_record(&self) -> (usize, usize, usize) {
+        let mut record = (0, 0, 0);
+
+        for board in self.boards.iter() {
+            match board.result.map(|result| result.get_winner()) {
+                Some(Some(winner)) if winner == board.host_color => record.0 += 1,
+                Some(None) => record.1 += 1,
+                Some(Some(_)) => record.2 += 1,
+                None => {}
+            }
+        }
+
+        record
+    }
+
+    pub fn create_final_score(&self) -> String {
+        let (wins, draws, losses) = self.get_host_record();
+        let mut message = format!(
+            "Simul #{} ({}) is over, {} scored {}/{} (+{} ={} -{}).\n",
+            self.id,
+            self.name,
+            self.host.id.mention(),
+            self.get_host_score(),
+            self.boards.len(),
+            wins,
+            draws,
+            losses
+        );
+
+        for (index, board) in self.boards.iter().enumerate() {
+            let score = board.result.map(|result| result.get_score()).unwrap_or("*");
+            message.push_str(&format!("Board {}: {} as {:?}, {}\n", index + 1, board.opponent.username, board.host_color.get_opposite(), score));
+        }
+
+        message
+    }
+}
@@ -0,0 +1,226 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::Serialize;
+use serenity::model::id::UserId;
+
+use crate::chess::board::Color;
+use crate::chess::game::GameResult;
+use crate::http::http_server::UserInfo;
+
+use super::archive::{ArchiveQuery, ArchivedGame, GameArchive, Outcome};
+use super::rating::{Rating, RatingPool, RatingTable};
+
+type PlayerId = UserId;
+
+/// Number of plies that identify the opening of a game.
+const OPENING_LENGTH: usize = 4;
+const FAVOURITE_OPENINGS: usize = 3;
+
+#[derive(Serialize, Default, Copy, Clone)]
+pub struct Score {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl Score {
+    fn add(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Win => self.wins += 1,
+            Outcome::Loss => self.losses += 1,
+            Outcome::Draw => self.draws += 1,
+        }
+    }
+
+    pub fn get_games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+}
+
+#[derive(Serialize)]
+pub struct TerminationStats {
+    pub termination: &'static str,
+    #[serde(flatten)]
+    pub score: Score,
+}
+
+#[derive(Serialize)]
+pub struct OpeningStats {
+    /// The first plies of the games, in coordinate notation.
+    pub moves: String,
+    #[serde(flatten)]
+    pub score: Score,
+}
+
+/// Aggregated over all concluded games of a player.
+#[derive(Serialize)]
+pub struct PlayerStats {
+    pub player: PlayerId,
+    pub total: Score,
+    pub as_white: Score,
+    pub as_black: Score,
+    /// Most common ways the games ended first.
+    pub terminations: Vec<TerminationStats>,
+    pub favourite_openings: Vec<OpeningStats>,
+    pub longest_win_streak: u32,
+    pub longest_loss_streak: u32,
+    pub average_plies: f64,
+    /// In seconds.
+    pub average_duration: u64,
+}
+
+fn get_termination(result: GameResult) -> &'static str {
+    match result {
+        GameResult::Ongoing => "ongoing",
+        GameResult::CheckMate(_) => "checkmate",
+        GameResult::Resignation(_) => "resignation",
+        GameResult::OutOfTime(_) => "out_of_time",
+        GameResult::Stalemated => "stalemate",
+        GameResult::InsufficientMaterial => "insufficient_material",
+        GameResult::ThreefoldRepetition => "threefold_repetition",
+        GameResult::FiftyMoves => "fifty_moves",
+        GameResult::DrawAgreed => "draw_agreed",
+        GameResult::Aborted => "aborted",
+        GameResult::Abandoned(_) => "abandonment",
+    }
+}
+
+fn get_opening(game: &ArchivedGame) -> Option<String> {
+    let moves = game.get_moves();
+
+    if moves.len() < OPENING_LENGTH {
+        return None;
+    }
+
+    Some(moves[..OPENING_LENGTH].iter().map(|m| m.to_string()).collect::<Vec<String>>().join(" "))
+}
+
+/// Sorts the scores by the number of games, keeping the order they were first seen in for ties.
+fn sort_by_games<K>(mut scores: Vec<(K, Score)>) -> Vec<(K, Score)> {
+    scores.sort_by_key(|(_, score)| Reverse(score.get_games()));
+    scores
+}
+
+fn add_score<K: PartialEq>(scores: &mut Vec<(K, Score)>, key: K, outcome: Outcome) {
+    match scores.iter_mut().find(|(other, _)| *other == key) {
+        Some((_, score)) => score.add(outcome),
+        None => {
+            let mut score = Score::default();
+            score.add(outcome);
+            scores.push((key, score));
+        }
+    }
+}
+
+impl PlayerStats {
+    pub fn compute(player: PlayerId, archive: &GameArchive) -> Self {
+        let mut games = archive.query(&ArchiveQuery::new(player));
+        games.retain(|game| game.result != GameResult::Aborted);
+        games.reverse();
+
+        let mut total = Score::default();
+        let mut as_white = Score::default();
+        let mut as_black = Score::default();
+        let mut terminations = Vec::new();
+        let mut openings = Vec::new();
+        let (mut win_streak, mut loss_streak, mut longest_win_streak, mut longest_loss_streak) = (0, 0, 0, 0);
+        let mut plies = 0;
+        let mut duration = Duration::default();
+
+        for game in games.iter() {
+            let outcome = game.get_outcome_for(player).unwrap();
+
+            total.add(outcome);
+
+            match game.get_side_of_player(player).unwrap() {
+                Color::White => as_white.add(outcome),
+                Color::Black => as_black.add(outcome),
+            }
+
+            add_score(&mut terminations, get_termination(game.result), outcome);
+
+            if let Some(opening) = get_opening(game) {
+                add_score(&mut openings, opening, outcome);
+            }
+
+            win_streak = if outcome == Outcome::Win { win_streak + 1 } else { 0 };
+            loss_streak = if outcome == Outcome::Loss { loss_streak + 1 } else { 0 };
+            longest_win_streak = longest_win_streak.max(win_streak);
+            longest_loss_streak = longest_loss_streak.max(loss_streak);
+
+            plies += game.get_moves().len();
+            duration += game.end_time.duration_since(game.start_time).unwrap_or_default();
+        }
+
+        let count = games.len().max(1);
+
+        Self {
+            player,
+            total,
+            as_white,
+            as_black,
+            terminations: sort_by_games(terminations).into_iter().map(|(termination, score)| TerminationStats { termination, score }).collect(),
+            favourite_openings: sort_by_games(openings)
+                .into_iter()
+                .take(FAVOURITE_OPENINGS)
+                .map(|(moves, score)| OpeningStats { moves, score })
+                .collect(),
+            longest_win_streak,
+            longest_loss_streak,
+            average_plies: plies as f64 / count as f64,
+            average_duration: duration.as_secs() / count as u64,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct RatingRanking {
+    pub player: UserInfo,
+    pub rating: Rating,
+}
+
+#[derive(Serialize)]
+pub struct ActivityRanking {
+    pub player: UserInfo,
+    pub games: u32,
+}
+
+/// Players who played rated games in the pool, the highest rated first.
+pub fn get_rating_leaderboard(ratings: &RatingTable, archive: &GameArchive, pool: RatingPool) -> Vec<RatingRanking> {
+    let mut rankings: Vec<RatingRanking> = ratings
+        .get_players()
+        .filter_map(|player| {
+            let rating = player.get(pool);
+
+            if rating.games == 0 {
+                return None;
+            }
+
+            Some(RatingRanking {
+                player: archive.get_user_info(player.user)?.clone(),
+                rating,
+            })
+        })
+        .collect();
+
+    rankings.sort_by(|a, b| b.rating.rating.total_cmp(&a.rating.rating));
+    rankings
+}
+
+/// Players with the most concluded games first.
+pub fn get_activity_leaderboard(archive: &GameArchive) -> Vec<ActivityRanking> {
+    let mut games: HashMap<PlayerId, (&UserInfo, u32)> = HashMap::new();
+
+    for game in archive.get_games().iter().filter(|game| game.result != GameResult::Aborted) {
+        for &player in [&game.white_player, &game.black_player].iter() {
+            games.entry(player.id).or_insert((player, 0)).1 += 1;
+        }
+    }
+
+    let mut rankings: Vec<ActivityRanking> = games.into_values().map(|(player, games)| ActivityRanking { player: player.clone(), games }).collect();
+
+    rankings.sort_by_key(|ranking| Reverse(ranking.games));
+    rankings
+}
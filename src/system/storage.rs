@@ -0,0 +1,520 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use rusqlite::{params, Connection};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serenity::model::id::ChannelId;
+
+use crate::chess::game::SerializedGame;
+use crate::config::{StorageBackend, StorageConfig};
+use crate::http::http_server::UserInfo;
+
+use super::abandonment::Activity;
+use super::archive::ArchivedGame;
+use super::arena::{Arena, ArenaId};
+use super::clock::Clock;
+use super::consultation::Consultation;
+use super::correspondence::MoveDeadline;
+use super::events::{GameEvent, GameEventSubscriber};
+use super::game::{Game, GameId, GameOptions};
+use super::handicap::Handicap;
+use super::rating::{PlayerRatings, Rating};
+use super::rematch::MatchScore;
+use super::simul::{Simul, SimulGame};
+use super::tournament::{Tournament, TournamentGame};
+
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+/// Everything needed to bring a running game back after a restart.
+#[derive(Serialize, Deserialize)]
+pub struct StoredGame {
+    pub id: GameId,
+    pub white_player: UserInfo,
+    pub black_player: UserInfo,
+    pub start_time: SystemTime,
+    pub announce_channel: Option<ChannelId>,
+    pub options: GameOptions,
+    pub white_rating: Rating,
+    pub black_rating: Rating,
+    pub tournament: Option<TournamentGame>,
+    pub arena: Option<ArenaId>,
+    pub simul: Option<SimulGame>,
+    pub deadline: Option<MoveDeadline>,
+    pub clock: Option<Clock>,
+    pub score: Option<MatchScore>,
+    /// When the side to move got its turn, so inactive players are still caught after a restart.
+    pub activity: Activity,
+    pub handicap: Option<Handicap>,
+    pub consultation: Option<Consultation>,
+    pub chess_game: SerializedGame,
+}
+
+impl StoredGame {
+    pub fn from_game(game: &Game) -> Self {
+        Self {
+            id: game.id,
+            white_player: game.white_player.clone(),
+            black_player: game.black_player.clone(),
+            start_time: game.start_time,
+            announce_channel: game.announcer.as_ref().map(|announcer| announcer.id),
+            options: game.options,
+            white_rating: game.white_rating,
+            black_rating: game.black_rating,
+            tournament: game.tournament,
+            arena: game.arena,
+            simul: game.simul,
+            deadline: game.deadline,
+            clock: game.clock,
+            score: game.score,
+            activity: game.activity,
+            handicap: game.handicap,
+            consultation: game.consultation.clone(),
+            chess_game: game.chess_game.to_serialized(),
+        }
+    }
+}
+
+/// Records read from the storage, a broken record is left out instead of keeping everything else from loading.
+pub struct LoadedRecords<T> {
+    pub records: Vec<T>,
+    /// Which records were left out and why.
+    pub skipped: Vec<String>,
+}
+
+impl<T> Default for LoadedRecords<T> {
+    fn default() -> Self {
+        Self {
+            records: Vec::new(),
+            skipped: Vec::new(),
+        }
+    }
+}
+
+impl<T> LoadedRecords<T> {
+    /// Adds the records that were left out to the others, so they are reported together.
+    pub fn into_records(self, skipped: &mut Vec<String>) -> Vec<T> {
+        skipped.extend(self.skipped);
+        self.records
+    }
+}
+
+pub trait GameStorage: Send + Sync {
+    fn save_game(&self, game: &StoredGame) -> Result<(), StorageError>;
+
+    fn remove_game(&self, id: GameId) -> Result<(), StorageError>;
+
+    fn load_games(&self) -> Result<LoadedRecords<StoredGame>, StorageError>;
+
+    fn archive_game(&self, game: &ArchivedGame) -> Result<(), StorageError>;
+
+    fn load_archive(&self) -> Result<LoadedRecords<ArchivedGame>, StorageError>;
+
+    fn save_ratings(&self, ratings: &PlayerRatings) -> Result<(), StorageError>;
+
+    fn load_ratings(&self) -> Result<LoadedRecords<PlayerRatings>, StorageError>;
+
+    fn save_tournament(&self, tournament: &Tournament) -> Result<(), StorageError>;
+
+    fn load_tournaments(&self) -> Result<LoadedRecords<Tournament>, StorageError>;
+
+    fn save_arena(&self, arena: &Arena) -> Result<(), StorageError>;
+
+    fn load_arenas(&self) -> Result<LoadedRecords<Arena>, StorageError>;
+
+    fn save_simul(&self, simul: &Simul) -> Result<(), StorageError>;
+
+    fn load_simuls(&self) -> Result<LoadedRecords<Simul>, StorageError>;
+}
+
+pub fn open_storage(config: &StorageConfig) -> Result<Arc<dyn GameStorage>, StorageError> {
+    Ok(match config.backend {
+        StorageBackend::Json => Arc::new(JsonFileStorage::new(&config.path)?),
+        StorageBackend::Sqlite => Arc::new(SqliteStorage::new(&config.path)?),
+    })
+}
+
+/// A change to the storage, waiting to be written by the `StorageWriter`.
+enum StorageWrite {
+    Game(Box<StoredGame>),
+    Archive(Box<ArchivedGame>),
+    Ratings(PlayerRatings),
+    Tournament(Tournament),
+    Arena(Arena),
+    Simul(Simul),
+}
+
+impl StorageWrite {
+    fn perform(self, storage: &dyn GameStorage) {
+        let (result, description) = match self {
+            StorageWrite::Game(game) => (storage.save_game(&game), format!("save game {}", game.id)),
+            StorageWrite::Archive(game) => (storage.archive_game(&game), format!("archive game {}", game.id)),
+            StorageWrite::Ratings(ratings) => (storage.save_ratings(&ratings), format!("save the ratings of {}", ratings.user)),
+            StorageWrite::Tournament(tournament) => (storage.save_tournament(&tournament), format!("save tournament {}", tournament.id)),
+            StorageWrite::Arena(arena) => (storage.save_arena(&arena), format!("save arena {}", arena.id)),
+            StorageWrite::Simul(simul) => (storage.save_simul(&simul), format!("save simul {}", simul.id)),
+        };
+
+        if let Err(why) = result {
+            eprintln!("Failed to {}: {}", description, why);
+        }
+    }
+}
+
+/// Writes to the storage on a thread of its own, in the order the changes were made,
+/// so neither the games nor the game manager wait for the disk.
+#[derive(Clone)]
+pub struct StorageWriter {
+    sender: Sender<StorageWrite>,
+}
+
+impl StorageWriter {
+    pub fn new(storage: Arc<dyn GameStorage>) -> Self {
+        let (sender, receiver) = channel::<StorageWrite>();
+
+        std::thread::spawn(move || {
+            for write in receiver {
+                write.perform(storage.as_ref());
+            }
+        });
+
+        Self { sender }
+    }
+
+    fn send(&self, write: StorageWrite) {
+        let _ = self.sender.send(write);
+    }
+
+    pub fn save_game(&self, game: &Game) {
+        self.send(StorageWrite::Game(Box::new(StoredGame::from_game(game))));
+    }
+
+    /// Moves a concluded game from the running games to the archive.
+    pub fn archive_game(&self, game: &ArchivedGame) {
+        self.send(StorageWrite::Archive(Box::new(game.clone())));
+    }
+
+    pub fn save_ratings(&self, ratings: &PlayerRatings) {
+        self.send(StorageWrite::Ratings(ratings.clone()));
+    }
+
+    pub fn save_tournament(&self, tournament: &Tournament) {
+        self.send(StorageWrite::Tournament(tournament.clone()));
+    }
+
+    pub fn save_arena(&self, arena: &Arena) {
+        self.send(StorageWrite::Arena(arena.clone()));
+    }
+
+    pub fn save_simul(&self, simul: &Simul) {
+        self.send(StorageWrite::Simul(simul.clone()));
+    }
+}
+
+/// Saves a game after every change, so it can be restored after a restart.
+pub struct GameSaver {
+    storage: StorageWriter,
+}
+
+impl GameSaver {
+    pub fn new(storage: StorageWriter) -> Self {
+        Self { storage }
+    }
+}
+
+impl GameEventSubscriber for GameSaver {
+    fn notify(&self, game: &Game, _: &[GameEvent]) {
+        self.storage.save_game(game);
+    }
+}
+
+/// Keeps every game in its own JSON file inside a directory, concluded games go to its `archive` subdirectory
+/// and the ratings of every player, tournaments, arenas and simuls to the `ratings`, `tournaments`, `arenas` and `simuls` subdirectories.
+pub struct JsonFileStorage {
+    directory: PathBuf,
+    archive_directory: PathBuf,
+    ratings_directory: PathBuf,
+    tournaments_directory: PathBuf,
+    arenas_directory: PathBuf,
+    simuls_directory: PathBuf,
+}
+
+impl JsonFileStorage {
+    pub fn new<P: AsRef<Path>>(directory: P) -> Result<Self, StorageError> {
+        let directory = directory.as_ref().to_path_buf();
+        let archive_directory = directory.join("archive");
+        let ratings_directory = directory.join("ratings");
+        let tournaments_directory = directory.join("tournaments");
+        let arenas_directory = directory.join("arenas");
+        let simuls_directory = directory.join("simuls");
+
+        std::fs::create_dir_all(&archive_directory)?;
+        std::fs::create_dir_all(&ratings_directory)?;
+        std::fs::create_dir_all(&tournaments_directory)?;
+        std::fs::create_dir_all(&arenas_directory)?;
+        std::fs::create_dir_all(&simuls_directory)?;
+
+        Ok(Self {
+            directory,
+            archive_directory,
+            ratings_directory,
+            tournaments_directory,
+            arenas_directory,
+            simuls_directory,
+        })
+    }
+
+    fn get_game_path(&self, id: GameId) -> PathBuf {
+        self.directory.join(format!("{}.json", id))
+    }
+
+    fn write_file<T: Serialize>(path: PathBuf, value: &T) -> Result<(), StorageError> {
+        let temp_path = path.with_extension("json.tmp");
+
+        // Write to a temporary file first so a crash never leaves a half written game behind
+        std::fs::write(&temp_path, serde_json::to_vec(value)?)?;
+        std::fs::rename(&temp_path, &path)?;
+
+        Ok(())
+    }
+
+    fn read_directory<T: DeserializeOwned>(directory: &Path) -> Result<LoadedRecords<T>, StorageError> {
+        let mut loaded = LoadedRecords::default();
+
+        for entry in std::fs::read_dir(directory)? {
+            let path = entry?.path();
+
+            if path.extension().is_some_and(|extension| extension == "json") {
+                match serde_json::from_slice(&std::fs::read(&path)?) {
+                    Ok(value) => loaded.records.push(value),
+                    Err(why) => loaded.skipped.push(format!("{}: {}", path.display(), why)),
+                }
+            }
+        }
+
+        Ok(loaded)
+    }
+}
+
+impl GameStorage for JsonFileStorage {
+    fn save_game(&self, game: &StoredGame) -> Result<(), StorageError> {
+        JsonFileStorage::write_file(self.get_game_path(game.id), game)
+    }
+
+    fn remove_game(&self, id: GameId) -> Result<(), StorageError> {
+        match std::fs::remove_file(self.get_game_path(id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn load_games(&self) -> Result<LoadedRecords<StoredGame>, StorageError> {
+        JsonFileStorage::read_directory(&self.directory)
+    }
+
+    fn archive_game(&self, game: &ArchivedGame) -> Result<(), StorageError> {
+        JsonFileStorage::write_file(self.archive_directory.join(format!("{}.json", game.id)), game)?;
+        self.remove_game(game.id)
+    }
+
+    fn load_archive(&self) -> Result<LoadedRecords<ArchivedGame>, StorageError> {
+        JsonFileStorage::read_directory(&self.archive_directory)
+    }
+
+    fn save_ratings(&self, ratings: &PlayerRatings) -> Result<(), StorageError> {
+        JsonFileStorage::write_file(self.ratings_directory.join(format!("{}.json", ratings.user)), ratings)
+    }
+
+    fn load_ratings(&self) -> Result<LoadedRecords<PlayerRatings>, StorageError> {
+        JsonFileStorage::read_directory(&self.ratings_directory)
+    }
+
+    fn save_tournament(&self, tournament: &Tournament) -> Result<(), StorageError> {
+        JsonFileStorage::write_file(self.tournaments_directory.join(format!("{}.json", tournament.id)), tournament)
+    }
+
+    fn load_tournaments(&self) -> Result<LoadedRecords<Tournament>, StorageError> {
+        JsonFileStorage::read_directory(&self.tournaments_directory)
+    }
+
+    fn save_arena(&self, arena: &Arena) -> Result<(), StorageError> {
+        JsonFileStorage::write_file(self.arenas_directory.join(format!("{}.json", arena.id)), arena)
+    }
+
+    fn load_arenas(&self) -> Result<LoadedRecords<Arena>, StorageError> {
+        JsonFileStorage::read_directory(&self.arenas_directory)
+    }
+
+    fn save_simul(&self, simul: &Simul) -> Result<(), StorageError> {
+        JsonFileStorage::write_file(self.simuls_directory.join(format!("{}.json", simul.id)), simul)
+    }
+
+    fn load_simuls(&self) -> Result<LoadedRecords<Simul>, StorageError> {
+        JsonFileStorage::read_directory(&self.simuls_directory)
+    }
+}
+
+/// Keeps running games, concluded games and ratings in tables of an embedded SQLite database.
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, StorageError> {
+        let connection = Connection::open(path)?;
+        connection.execute("CREATE TABLE IF NOT EXISTS games (id INTEGER PRIMARY KEY, data TEXT NOT NULL)", params![])?;
+        connection.execute("CREATE TABLE IF NOT EXISTS archive (id INTEGER PRIMARY KEY, data TEXT NOT NULL)", params![])?;
+        connection.execute("CREATE TABLE IF NOT EXISTS ratings (id INTEGER PRIMARY KEY, data TEXT NOT NULL)", params![])?;
+        connection.execute("CREATE TABLE IF NOT EXISTS tournaments (id INTEGER PRIMARY KEY, data TEXT NOT NULL)", params![])?;
+        connection.execute("CREATE TABLE IF NOT EXISTS arenas (id INTEGER PRIMARY KEY, data TEXT NOT NULL)", params![])?;
+        connection.execute("CREATE TABLE IF NOT EXISTS simuls (id INTEGER PRIMARY KEY, data TEXT NOT NULL)", params![])?;
+
+        Ok(Self { connection: Mutex::new(connection) })
+    }
+
+    fn load_table<T: DeserializeOwned>(&self, sql: &str) -> Result<LoadedRecords<T>, StorageError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(sql)?;
+
+        let rows = statement.query_map(params![], |row| row.get::<_, String>(0))?;
+        let mut loaded = LoadedRecords::default();
+
+        for data in rows {
+            match serde_json::from_str(&data?) {
+                Ok(value) => loaded.records.push(value),
+                Err(why) => loaded.skipped.push(format!("row of \"{}\": {}", sql, why)),
+            }
+        }
+
+        Ok(loaded)
+    }
+}
+
+impl GameStorage for SqliteStorage {
+    fn save_game(&self, game: &StoredGame) -> Result<(), StorageError> {
+        let data = serde_json::to_string(game)?;
+
+        self.connection
+            .lock()
+            .unwrap()
+            .execute("INSERT OR REPLACE INTO games (id, data) VALUES (?1, ?2)", params![game.id as i64, data])?;
+
+        Ok(())
+    }
+
+    fn remove_game(&self, id: GameId) -> Result<(), StorageError> {
+        self.connection.lock().unwrap().execute("DELETE FROM games WHERE id = ?1", params![id as i64])?;
+
+        Ok(())
+    }
+
+    fn load_games(&self) -> Result<LoadedRecords<StoredGame>, StorageError> {
+        self.load_table("SELECT data FROM games ORDER BY id")
+    }
+
+    fn archive_game(&self, game: &ArchivedGame) -> Result<(), StorageError> {
+        let data = serde_json::to_string(game)?;
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+
+        transaction.execute("INSERT OR REPLACE INTO archive (id, data) VALUES (?1, ?2)", params![game.id as i64, data])?;
+        transaction.execute("DELETE FROM games WHERE id = ?1", params![game.id as i64])?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn load_archive(&self) -> Result<LoadedRecords<ArchivedGame>, StorageError> {
+        self.load_table("SELECT data FROM archive ORDER BY id")
+    }
+
+    fn save_ratings(&self, ratings: &PlayerRatings) -> Result<(), StorageError> {
+        let data = serde_json::to_string(ratings)?;
+
+        self.connection
+            .lock()
+            .unwrap()
+            .execute("INSERT OR REPLACE INTO ratings (id, data) VALUES (?1, ?2)", params![ratings.user.0 as i64, data])?;
+
+        Ok(())
+    }
+
+    fn load_ratings(&self) -> Result<LoadedRecords<PlayerRatings>, StorageError> {
+        self.load_table("SELECT data FROM ratings")
+    }
+
+    fn save_tournament(&self, tournament: &Tournament) -> Result<(), StorageError> {
+        let data = serde_json::to_string(tournament)?;
+
+        self.connection
+            .lock()
+            .unwrap()
+            .execute("INSERT OR REPLACE INTO tournaments (id, data) VALUES (?1, ?2)", params![tournament.id as i64, data])?;
+
+        Ok(())
+    }
+
+    fn load_tournaments(&self) -> Result<LoadedRecords<Tournament>, StorageError> {
+        self.load_table("SELECT data FROM tournaments ORDER BY id")
+    }
+
+    fn save_arena(&self, arena: &Arena) -> Result<(), StorageError> {
+        let data = serde_json::to_string(arena)?;
+
+        self.connection
+            .lock()
+            .unwrap()
+            .execute("INSERT OR REPLACE INTO arenas (id, data) VALUES (?1, ?2)", params![arena.id as i64, data])?;
+
+        Ok(())
+    }
+
+    fn load_arenas(&self) -> Result<LoadedRecords<Arena>, StorageError> {
+        self.load_table("SELECT data FROM arenas ORDER BY id")
+    }
+
+    fn save_simul(&self, simul: &Simul) -> Result<(), StorageError> {
+        let data = serde_json::to_string(simul)?;
+
+        self.connection
+            .lock()
+            .unwrap()
+            .execute("INSERT OR REPLACE INTO simuls (id, data) VALUES (?1, ?2)", params![simul.id as i64, data])?;
+
+        Ok(())
+    }
+
+    fn load_simuls(&self) -> Result<LoadedRecords<Simul>, StorageError> {
+        self.load_table("SELECT data FROM simuls ORDER BY id")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broken_files_are_reported_instead_of_loaded() {
+        let directory = std::env::temp_dir().join(format!("chess-bot-storage-{}", std::process::id()));
+        let storage = JsonFileStorage::new(&directory).unwrap();
+        std::fs::write(directory.join("1.json"), "{ not a game").unwrap();
+
+        let loaded = storage.load_games();
+        std::fs::remove_dir_all(&directory).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert!(loaded.records.is_empty());
+        assert_eq!(loaded.skipped.len(), 1);
+        assert!(loaded.skipped[0].contains("1.json"), "skipped {:?}", loaded.skipped);
+    }
+}
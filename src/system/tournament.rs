@@ -0,0 +1,541 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{ChannelId, UserId};
+
+use crate::chess::board::Color;
+use crate::chess::game::GameResult;
+use crate::http::http_server::UserInfo;
+
+use super::game::{GameAnnouncer, GameId, GameOptions};
+use super::rating::Rating;
+
+type PlayerId = UserId;
+pub type TournamentId = u64;
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Copy, Clone, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum TournamentFormat {
+    RoundRobin,
+    Swiss { rounds: u32 },
+}
+
+impl Display for TournamentFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TournamentFormat::RoundRobin => write!(f, "round-robin"),
+            TournamentFormat::Swiss { rounds } => write!(f, "{}-round Swiss", rounds),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Copy, Clone, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum TournamentState {
+    Registration,
+    Running,
+    Finished,
+}
+
+#[derive(Error, Debug)]
+pub enum TournamentError {
+    #[error("There is no tournament #{0}.")]
+    NoSuchTournament(TournamentId),
+    #[error("The tournament is no longer open for registration.")]
+    RegistrationClosed,
+    #[error("You already joined this tournament.")]
+    AlreadyJoined,
+    #[error("You are not registered in this tournament.")]
+    NotJoined,
+    #[error("Only the organizer can start the tournament.")]
+    NotOrganizer,
+    #[error("At least two players are needed to start.")]
+    NotEnoughPlayers,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TournamentPlayer {
+    pub user: UserInfo,
+    /// Rating when the tournament started, used for seeding.
+    pub rating: Rating,
+}
+
+/// A board of a round. Without a black player the white player has a bye.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Pairing {
+    pub white: PlayerId,
+    pub black: Option<PlayerId>,
+    pub game: Option<GameId>,
+    pub result: Option<GameResult>,
+}
+
+impl Pairing {
+    pub fn is_bye(&self) -> bool {
+        self.black.is_none()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.is_bye() || self.result.is_some()
+    }
+
+    /// Aborted games were never played, they score nothing and the players count as not having met.
+    pub fn is_aborted(&self) -> bool {
+        self.result == Some(GameResult::Aborted)
+    }
+
+    pub fn get_opponent(&self, player: PlayerId) -> Option<PlayerId> {
+        if self.is_aborted() {
+            None
+        } else if self.white == player {
+            self.black
+        } else if self.black == Some(player) {
+            Some(self.white)
+        } else {
+            None
+        }
+    }
+
+    /// Points of the player in half points, None if they are not on this board or the game is not over.
+    pub fn get_half_points(&self, player: PlayerId) -> Option<u32> {
+        if self.is_bye() {
+            return if self.white == player { Some(2) } else { None };
+        }
+
+        let side = if self.white == player {
+            Color::White
+        } else if self.black == Some(player) {
+            Color::Black
+        } else {
+            return None;
+        };
+
+        if self.is_aborted() {
+            return Some(0);
+        }
+
+        match self.result?.get_winner() {
+            Some(winner) if winner == side => Some(2),
+            Some(_) => Some(0),
+            None => Some(1),
+        }
+    }
+
+    pub fn get_color_of(&self, player: PlayerId) -> Option<Color> {
+        if self.is_bye() || self.is_aborted() {
+            None
+        } else if self.white == player {
+            Some(Color::White)
+        } else if self.black == Some(player) {
+            Some(Color::Black)
+        } else {
+            None
+        }
+    }
+}
+
+/// Identifies the board a game is played on, kept with the game.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct TournamentGame {
+    pub tournament: TournamentId,
+    pub round: usize,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Standing {
+    pub player: UserInfo,
+    pub points: f64,
+    pub buchholz: f64,
+    pub sonneborn_berger: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Tournament {
+    pub id: TournamentId,
+    pub name: String,
+    pub organizer: PlayerId,
+    pub format: TournamentFormat,
+    pub options: GameOptions,
+    pub state: TournamentState,
+    pub players: Vec<TournamentPlayer>,
+    pub rounds: Vec<Vec<Pairing>>,
+    pub announce_channel: Option<ChannelId>,
+    #[serde(skip)]
+    pub announcer: Option<GameAnnouncer>,
+}
+
+impl Tournament {
+    pub fn new(id: TournamentId, name: String, organizer: PlayerId, format: TournamentFormat, options: GameOptions, announcer: Option<GameAnnouncer>) -> Self {
+        Self {
+            id,
+            name,
+            organizer,
+            format,
+            options,
+            state: TournamentState::Registration,
+            players: Vec::new(),
+            rounds: Vec::new(),
+            announce_channel: announcer.as_ref().map(|announcer| announcer.id),
+            announcer,
+        }
+    }
+
+    pub fn get_player(&self, player: PlayerId) -> Option<&TournamentPlayer> {
+        self.players.iter().find(|other| other.user.id == player)
+    }
+
+    pub fn get_total_rounds(&self) -> usize {
+        match self.format {
+            TournamentFormat::RoundRobin => self.players.len() - 1 + self.players.len() % 2,
+            TournamentFormat::Swiss { rounds } => rounds as usize,
+        }
+    }
+
+    pub fn get_current_round(&self) -> Option<&[Pairing]> {
+        self.rounds.last().map(|round| round.as_slice())
+    }
+
+    pub fn is_round_finished(&self) -> bool {
+        self.rounds.last().is_none_or(|round| round.iter().all(Pairing::is_finished))
+    }
+
+    /// Stores the result of a tournament game, returns false if the game is not part of the current round.
+    pub fn record_result(&mut self, game: GameId, result: GameResult) -> bool {
+        match self.rounds.last_mut().and_then(|round| round.iter_mut().find(|pairing| pairing.game == Some(game))) {
+            Some(pairing) => {
+                pairing.result = Some(result);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get_half_points(&self, player: PlayerId) -> u32 {
+        self.rounds.iter().flatten().filter_map(|pairing| pairing.get_half_points(player)).sum()
+    }
+
+    fn get_opponents(&self, player: PlayerId) -> Vec<PlayerId> {
+        self.rounds.iter().flatten().filter_map(|pairing| pairing.get_opponent(player)).collect()
+    }
+
+    fn has_had_bye(&self, player: PlayerId) -> bool {
+        self.rounds.iter().flatten().any(|pairing| pairing.is_bye() && pairing.white == player)
+    }
+
+    fn get_colors(&self, player: PlayerId) -> Vec<Color> {
+        self.rounds.iter().flatten().filter_map(|pairing| pairing.get_color_of(player)).collect()
+    }
+
+    /// Gives white to the player who had it less often, then to whoever had black last, otherwise alternates by round.
+    fn assign_colors(&self, first: PlayerId, second: PlayerId) -> (PlayerId, PlayerId) {
+        let balance = |player| self.get_colors(player).iter().map(|color| if *color == Color::White { 1 } else { -1 }).sum::<i32>();
+        let last = |player| self.get_colors(player).last().copied();
+
+        let first_is_white = match balance(first).cmp(&balance(second)) {
+            std::cmp::Ordering::Less => true,
+            std::cmp::Ordering::Greater => false,
+            std::cmp::Ordering::Equal => match (last(first), last(second)) {
+                (Some(Color::Black), Some(Color::White)) | (Some(Color::Black), None) => true,
+                (Some(Color::White), Some(Color::Black)) | (None, Some(Color::Black)) => false,
+                _ => self.rounds.len().is_multiple_of(2),
+            },
+        };
+
+        if first_is_white {
+            (first, second)
+        } else {
+            (second, first)
+        }
+    }
+
+    /// Players ordered by points and then by their seed.
+    fn get_ranked_players(&self) -> Vec<PlayerId> {
+        let mut players: Vec<(PlayerId, u32, f64)> = self.players.iter().map(|player| (player.user.id, self.get_half_points(player.user.id), player.rating.rating)).collect();
+        players.sort_by(|a, b| b.1.cmp(&a.1).then(b.2.total_cmp(&a.2)));
+
+        players.into_iter().map(|(player, _, _)| player).collect()
+    }
+
+    /// Pairs the next round, returns false if all rounds have been played.
+    pub fn pair_next_round(&mut self) -> bool {
+        if self.rounds.len() >= self.get_total_rounds() {
+            return false;
+        }
+
+        let pairs = match self.format {
+            TournamentFormat::RoundRobin => self.pair_round_robin(),
+            TournamentFormat::Swiss { .. } => self.pair_swiss(),
+        };
+
+        let round = pairs
+            .into_iter()
+            .map(|(first, second)| {
+                let (white, black) = match second {
+                    Some(second) => {
+                        let (white, black) = self.assign_colors(first, second);
+                        (white, Some(black))
+                    }
+                    None => (first, None),
+                };
+
+                Pairing {
+                    white,
+                    black,
+                    game: None,
+                    result: None,
+                }
+            })
+            .collect();
+
+        self.rounds.push(round);
+        true
+    }
+
+    /// Circle method, the first seed stays in place while everyone else rotates.
+    fn pair_round_robin(&self) -> Vec<(PlayerId, Option<PlayerId>)> {
+        let mut seeded: Vec<Option<PlayerId>> = self.players.iter().map(|player| Some(player.user.id)).collect();
+
+        if seeded.len() % 2 == 1 {
+            seeded.push(None);
+        }
+
+        let count = seeded.len();
+        let mut rotating = seeded.split_off(1);
+        rotating.rotate_right(self.rounds.len() % (count - 1));
+        seeded.extend(rotating);
+
+        (0..count / 2)
+            .filter_map(|index| match (seeded[index], seeded[count - 1 - index]) {
+                (Some(first), second) => Some((first, second)),
+                (None, Some(second)) => Some((second, None)),
+                (None, None) => None,
+            })
+            .collect()
+    }
+
+    /// Simplified Dutch system: within a score group the top half meets the bottom half, rematches are avoided
+    /// by trying the next candidates and floating players down to the next group.
+    fn pair_swiss(&self) -> Vec<(PlayerId, Option<PlayerId>)> {
+        let mut ranked = self.get_ranked_players();
+        let mut bye = None;
+
+        if ranked.len() % 2 == 1 {
+            let index = ranked.iter().rposition(|&player| !self.has_had_bye(player)).unwrap_or(ranked.len() - 1);
+            bye = Some(ranked.remove(index));
+        }
+
+        let points: Vec<u32> = ranked.iter().map(|&player| self.get_half_points(player)).collect();
+        let opponents: Vec<Vec<PlayerId>> = ranked.iter().map(|&player| self.get_opponents(player)).collect();
+        let indices: Vec<usize> = (0..ranked.len()).collect();
+
+        let found = BracketSearch::new(&points, &opponents, &ranked, true)
+            .pair(&indices)
+            .or_else(|| BracketSearch::new(&points, &opponents, &ranked, false).pair(&indices));
+
+        let mut pairs: Vec<(PlayerId, Option<PlayerId>)> = found.unwrap_or_default().into_iter().map(|(first, second)| (ranked[first], Some(ranked[second]))).collect();
+        pairs.extend(bye.map(|player| (player, None)));
+        pairs
+    }
+
+    pub fn get_standings(&self) -> Vec<Standing> {
+        let points = |player| self.get_half_points(player) as f64 / 2.0;
+
+        let mut standings: Vec<(Standing, f64)> = self
+            .players
+            .iter()
+            .map(|player| {
+                let id = player.user.id;
+                let mut buchholz = 0.0;
+                let mut sonneborn_berger = 0.0;
+
+                for pairing in self.rounds.iter().flatten() {
+                    if let (Some(opponent), Some(result)) = (pairing.get_opponent(id), pairing.get_half_points(id)) {
+                        buchholz += points(opponent);
+                        sonneborn_berger += points(opponent) * result as f64 / 2.0;
+                    }
+                }
+
+                (
+                    Standing {
+                        player: player.user.clone(),
+                        points: points(id),
+                        buchholz,
+                        sonneborn_berger,
+                    },
+                    player.rating.rating,
+                )
+            })
+            .collect();
+
+        standings.sort_by(|(a, a_rating), (b, b_rating)| {
+            b.points
+                .total_cmp(&a.points)
+                .then(b.buchholz.total_cmp(&a.buchholz))
+                .then(b.sonneborn_berger.total_cmp(&a.sonneborn_berger))
+                .then(b_rating.total_cmp(a_rating))
+        });
+
+        standings.into_iter().map(|(standing, _)| standing).collect()
+    }
+}
+
+/// Positions `BracketSearch` looks at before it gives up, rematches are allowed after that.
+const MAX_PAIRING_STEPS: usize = 10_000;
+
+/// Backtracking search for the pairings of a Swiss round.
+struct BracketSearch<'a> {
+    points: &'a [u32],
+    opponents: &'a [Vec<PlayerId>],
+    players: &'a [PlayerId],
+    avoid_rematches: bool,
+    /// Sets of remaining ranks that are known to have no pairing, so they are not searched again.
+    failed: HashSet<Vec<usize>>,
+    steps: usize,
+}
+
+impl<'a> BracketSearch<'a> {
+    fn new(points: &'a [u32], opponents: &'a [Vec<PlayerId>], players: &'a [PlayerId], avoid_rematches: bool) -> Self {
+        Self {
+            points,
+            opponents,
+            players,
+            avoid_rematches,
+            failed: HashSet::new(),
+            steps: 0,
+        }
+    }
+
+    /// Pairs the players at the given ranks, the best ranked first. Each player prefers the opponent half a score group below.
+    /// Returns None if there is no pairing or it took more than `MAX_PAIRING_STEPS` to find one.
+    fn pair(&mut self, remaining: &[usize]) -> Option<Vec<(usize, usize)>> {
+        let (&first, rest) = match remaining.split_first() {
+            Some(split) => split,
+            None => return Some(Vec::new()),
+        };
+
+        if self.steps >= MAX_PAIRING_STEPS || self.failed.contains(remaining) {
+            return None;
+        }
+
+        self.steps += 1;
+
+        let points = self.points;
+        let group_size = rest.iter().take_while(|&&other| points[other] == points[first]).count() + 1;
+        let preferred = group_size / 2;
+
+        // Bottom half of the score group from the middle down, then the top half upwards, then lower groups
+        let mut candidates: Vec<usize> = (preferred.max(1)..group_size).chain((1..preferred.max(1)).rev()).map(|index| index - 1).collect();
+        candidates.extend(group_size - 1..rest.len());
+
+        for candidate in candidates {
+            let second = rest[candidate];
+
+            if self.avoid_rematches && self.opponents[first].contains(&self.players[second]) {
+                continue;
+            }
+
+            let others: Vec<usize> = rest.iter().copied().filter(|&other| other != second).collect();
+
+            if let Some(mut pairs) = self.pair(&others) {
+                pairs.insert(0, (first, second));
+                return Some(pairs);
+            }
+        }
+
+        self.failed.insert(remaining.to_vec());
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tournament(players: u64, format: TournamentFormat) -> Tournament {
+        let mut tournament = Tournament::new(1, "Test".to_string(), UserId(1), format, GameOptions::default(), None);
+
+        for id in 1..=players {
+            tournament.players.push(TournamentPlayer {
+                user: UserInfo {
+                    id: UserId(id),
+                    username: format!("player{}", id),
+                    discriminator: "0000".to_string(),
+                    avatar: None,
+                },
+                rating: Rating::default(),
+            });
+        }
+
+        tournament.state = TournamentState::Running;
+        tournament
+    }
+
+    /// Pairs the next round and ends all of its games with the result.
+    fn play_round(tournament: &mut Tournament, result: GameResult) {
+        assert!(tournament.pair_next_round());
+
+        for pairing in tournament.rounds.last_mut().unwrap().iter_mut().filter(|pairing| !pairing.is_bye()) {
+            pairing.result = Some(result);
+        }
+    }
+
+    fn assert_everyone_paired_once(tournament: &Tournament) {
+        let mut seen: Vec<PlayerId> = tournament
+            .get_current_round()
+            .unwrap()
+            .iter()
+            .flat_map(|pairing| std::iter::once(pairing.white).chain(pairing.black))
+            .collect();
+        seen.sort_unstable();
+        seen.dedup();
+
+        assert_eq!(seen.len(), tournament.players.len());
+    }
+
+    #[test]
+    fn odd_player_counts_give_one_bye_per_round() {
+        let mut tournament = tournament(5, TournamentFormat::Swiss { rounds: 5 });
+
+        for _ in 0..5 {
+            play_round(&mut tournament, GameResult::DrawAgreed);
+            assert_everyone_paired_once(&tournament);
+            assert_eq!(tournament.get_current_round().unwrap().iter().filter(|pairing| pairing.is_bye()).count(), 1);
+        }
+
+        for player in tournament.players.iter() {
+            let byes = tournament.rounds.iter().flatten().filter(|pairing| pairing.is_bye() && pairing.white == player.user.id).count();
+            assert_eq!(byes, 1);
+        }
+    }
+
+    #[test]
+    fn rematches_are_paired_once_everyone_has_met() {
+        let mut tournament = tournament(16, TournamentFormat::RoundRobin);
+
+        for _ in 0..15 {
+            play_round(&mut tournament, GameResult::Resignation(Color::Black));
+        }
+
+        tournament.format = TournamentFormat::Swiss { rounds: 17 };
+
+        for _ in 0..2 {
+            play_round(&mut tournament, GameResult::DrawAgreed);
+            assert_everyone_paired_once(&tournament);
+        }
+    }
+
+    #[test]
+    fn aborted_games_do_not_count() {
+        let mut tournament = tournament(2, TournamentFormat::Swiss { rounds: 2 });
+        play_round(&mut tournament, GameResult::Aborted);
+
+        let pairing = &tournament.rounds[0][0];
+        let (white, black) = (pairing.white, pairing.black.unwrap());
+
+        assert_eq!(tournament.get_half_points(white), 0);
+        assert_eq!(tournament.get_half_points(black), 0);
+        assert!(tournament.get_opponents(white).is_empty());
+        assert!(tournament.get_colors(white).is_empty());
+        assert!(tournament.get_standings().iter().all(|standing| standing.buchholz == 0.0));
+    }
+}
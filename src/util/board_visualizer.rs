@@ -1,3 +1,4 @@
+use crate::chess::attacks::AttackMap;
 use crate::chess::board::{Board, Color as PieceColor, Square};
 use crate::chess::pieces::Type;
 
@@ -21,6 +22,8 @@ pub struct Config {
     pub dark_tile_color_highlighted: Color,
     pub text_on_light_color: Color,
     pub text_on_dark_color: Color,
+    pub white_control_color: Color,
+    pub black_control_color: Color,
     pub text_font: &'static [u8],
     pub text_font_size: usize,
     pub pieces_image: &'static [u8],
@@ -37,18 +40,42 @@ pub struct BoardVisualizer {
 }
 
 const BOARD_SIZE: usize = 8;
+const MAX_CONTROL_INTENSITY: i32 = 3;
 
 impl BoardVisualizer {
     pub fn new(config: Config) -> Self {
         Self {
             font: Font::try_from_bytes(config.text_font).unwrap(),
             scale: Scale::uniform(config.text_font_size as f32),
-            piece_image: image::load_from_memory_with_format(&config.pieces_image as &[u8], config.pieces_image_format).unwrap().to_rgba(),
+            piece_image: image::load_from_memory_with_format(config.pieces_image, config.pieces_image_format).unwrap().to_rgba8(),
             config,
         }
     }
 
     pub fn visualize(&self, board: &Board) -> Result<Vec<u8>, ImageError> {
+        self.visualize_with_control(board, None)
+    }
+
+    /// Renders the board with every square tinted by which side controls it and how strongly.
+    pub fn visualize_heatmap(&self, board: &Board) -> Result<Vec<u8>, ImageError> {
+        self.visualize_with_control(board, Some(&board.get_attack_map()))
+    }
+
+    fn get_control_tint(&self, attack_map: &AttackMap, square: Square) -> Option<Color> {
+        let balance = attack_map.get_control(square).get_balance();
+
+        if balance == 0 {
+            return None;
+        }
+
+        let mut tint = if balance > 0 { self.config.white_control_color } else { self.config.black_control_color };
+        let intensity = balance.abs().min(MAX_CONTROL_INTENSITY);
+        tint.0[3] = (tint.0[3] as i32 * intensity / MAX_CONTROL_INTENSITY) as u8;
+
+        Some(tint)
+    }
+
+    fn visualize_with_control(&self, board: &Board, attack_map: Option<&AttackMap>) -> Result<Vec<u8>, ImageError> {
         let mut image: RgbaImage = ImageBuffer::from_fn((self.config.tile_size * 8) as u32, (self.config.tile_size * 8 + self.config.bottom_fill_size) as u32, |_, _| {
             self.config.bottom_fill_color
         });
@@ -62,7 +89,7 @@ impl BoardVisualizer {
                 let tile_start_y = (self.config.bottom_fill_size / 2) + (BOARD_SIZE - rank as usize) * self.config.tile_size;
 
                 // Draw tile colors
-                let color = if board.highlighted_squares.contains(&square) {
+                let mut color = if board.highlighted_squares.contains(&square) {
                     if square.is_light() {
                         self.config.light_tile_color_highlighted
                     } else {
@@ -74,6 +101,10 @@ impl BoardVisualizer {
                     self.config.dark_tile_color
                 };
 
+                if let Some(tint) = attack_map.and_then(|attack_map| self.get_control_tint(attack_map, square)) {
+                    color.blend(&tint);
+                }
+
                 let rect = Rect::at(tile_start_x as i32, tile_start_y as i32).of_size(self.config.tile_size as u32, self.config.tile_size as u32);
 
                 draw_filled_rect_mut(&mut image, rect, color);
@@ -122,8 +153,8 @@ impl BoardVisualizer {
                             self.config.piece_size as u32,
                         ),
                         self.piece_image.view(
-                            (piece_x * self.config.piece_size as u32) as u32,
-                            (piece_y * self.config.piece_size as u32) as u32,
+                            piece_x * self.config.piece_size as u32,
+                            piece_y * self.config.piece_size as u32,
                             self.config.piece_size as u32,
                             self.config.piece_size as u32,
                         ),